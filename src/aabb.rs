@@ -11,6 +11,7 @@ impl AABB {
     pub fn hit(&self, ray: &Ray, tmin: f32, tmax: f32) -> Option<f32> {
         // TODO What happens if the ray starts inside the bounding box?
         let mut min = tmin;
+        let mut tmax = tmax;
         for a in 0..3 {
             let t0 = ((self.min[a] - ray.origin[a]) / ray.direction[a])
                 .min((self.max[a] - ray.origin[a]) / ray.direction[a]);
@@ -18,7 +19,7 @@ impl AABB {
                 .max((self.max[a] - ray.origin[a]) / ray.direction[a]);
 
             min = t0.max(min);
-            let tmax = t1.min(tmax);
+            tmax = t1.min(tmax);
 
             if tmax <= min {
                 return None;
@@ -46,6 +47,24 @@ impl AABB {
         AABB { min, max }
     }
 
+    /// Smallest box around the points, padded a bit so it isn't flat when they're on a plane
+    /// of the axes, as a ray can't hit a flat box
+    pub fn surrounding_points(points: &[Point]) -> AABB {
+        let mut min = Point::infinity();
+        let mut max = Point::neg_infinity();
+        for point in points {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(point[axis]);
+                max[axis] = max[axis].max(point[axis]);
+            }
+        }
+
+        AABB {
+            min: min - Vec3::from(0.0001),
+            max: max + Vec3::from(0.0001),
+        }
+    }
+
     pub fn center(&self) -> Point {
         (self.min + self.max) / 2.
     }
@@ -0,0 +1,66 @@
+use crate::vec3::*;
+
+/// Stops sampling a pixel once its estimate is good enough, so flat areas take
+/// `min_samples` and the noisy ones can go up to `max_samples`. The image still takes
+/// `samples_per_pixel` of the world for every pixel at most, as a budget.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u16,
+    pub max_samples: u16,
+    /// Relative standard error of the pixel's luminance under which it counts as converged
+    pub noise_threshold: f32,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            max_samples: 1024,
+            noise_threshold: 0.01,
+        }
+    }
+}
+
+/// Running mean and variance of the luminance of the samples of a pixel,
+/// using Welford's algorithm
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PixelStats {
+    pub count: u32,
+    pub mean: f32,
+    pub m2: f32,
+}
+
+impl PixelStats {
+    pub fn add(&mut self, color: Color) {
+        let value = luminance(color);
+        // NaNs get discarded when the pixel is resolved, so they don't count as noise
+        let value = if value.is_finite() { value } else { 0.0 };
+
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 {
+            return f32::INFINITY;
+        }
+        self.m2 / (self.count - 1) as f32
+    }
+
+    /// Standard error of the mean, relative to the mean
+    pub fn relative_error(&self) -> f32 {
+        (self.variance() / self.count as f32).sqrt() / self.mean.max(0.001)
+    }
+}
+
+impl AdaptiveSampling {
+    pub fn converged(&self, stats: &PixelStats) -> bool {
+        stats.count >= self.min_samples as u32 && stats.relative_error() < self.noise_threshold
+    }
+}
+
+pub fn luminance(color: Color) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
@@ -0,0 +1,131 @@
+use crate::{framebuffer::*, hit_record::*, vec3::*};
+use std::{io, path::Path};
+
+/// Auxiliary data about the first hit of the camera rays of one pixel
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AOVSample {
+    pub albedo: Color,
+    pub normal: Vec3,
+    pub depth: f32,
+    pub position: Point,
+    pub u: f32,
+    pub v: f32,
+    /// Id of the object, 0 for the background
+    pub object_id: u32,
+}
+
+impl AOVSample {
+    pub fn hit(hit_record: &HitRecord, albedo: Color) -> Self {
+        Self {
+            albedo,
+            normal: hit_record.normal,
+            depth: hit_record.t,
+            position: hit_record.point,
+            u: hit_record.u,
+            v: hit_record.v,
+            object_id: hit_record.object_id,
+        }
+    }
+
+    pub fn miss(background: Color) -> Self {
+        Self {
+            albedo: background,
+            ..Self::default()
+        }
+    }
+
+    /// Sums another sample into this one. The object id is the one of the first hit,
+    /// as averaging ids doesn't make sense.
+    pub fn accumulate(&mut self, other: &Self) {
+        self.albedo += other.albedo;
+        self.normal += other.normal;
+        self.depth += other.depth;
+        self.position += other.position;
+        self.u += other.u;
+        self.v += other.v;
+        if self.object_id == 0 {
+            self.object_id = other.object_id;
+        }
+    }
+
+    pub fn average(&self, samples: u32) -> Self {
+        let scale = 1.0 / samples as f32;
+        let normal = if self.normal.length_squared() > 0.0 {
+            self.normal.normalize()
+        } else {
+            self.normal
+        };
+
+        Self {
+            albedo: self.albedo * scale,
+            normal,
+            depth: self.depth * scale,
+            position: self.position * scale,
+            u: self.u * scale,
+            v: self.v * scale,
+            object_id: self.object_id,
+        }
+    }
+}
+
+/// Auxiliary buffers rendered alongside the beauty image
+pub struct AOVBuffers {
+    pub albedo: Framebuffer,
+    pub normal: Framebuffer,
+    /// Distance from the camera, in every channel
+    pub depth: Framebuffer,
+    pub position: Framebuffer,
+    /// u and v in the first two channels
+    pub uv: Framebuffer,
+    /// Object id in every channel
+    pub object_id: Framebuffer,
+}
+
+impl AOVBuffers {
+    pub fn from_samples(width: usize, height: usize, samples: &[AOVSample]) -> Self {
+        let buffer = |f: &dyn Fn(&AOVSample) -> Vec3| {
+            Framebuffer::from_pixels(width, height, samples.iter().map(f).collect())
+        };
+
+        Self {
+            albedo: buffer(&|sample| sample.albedo),
+            normal: buffer(&|sample| sample.normal),
+            depth: buffer(&|sample| Vec3::from(sample.depth)),
+            position: buffer(&|sample| sample.position),
+            uv: buffer(&|sample| Vec3::new(sample.u, sample.v, 0.)),
+            object_id: buffer(&|sample| Vec3::from(sample.object_id as f32)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Framebuffer)> {
+        vec![
+            ("albedo", &self.albedo),
+            ("normal", &self.normal),
+            ("depth", &self.depth),
+            ("position", &self.position),
+            ("uv", &self.uv),
+            ("object_id", &self.object_id),
+        ]
+        .into_iter()
+    }
+
+    /// Saves every buffer next to `path`, so `image.exr` gets `image.albedo.exr`,
+    /// `image.normal.exr`, etc
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("image");
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("exr");
+
+        for (name, buffer) in self.iter() {
+            buffer.save(path.with_file_name(format!("{}.{}.{}", stem, name, extension)))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{
+    framebuffer::*, renderer::*, scenes::*, session::RenderSession, stats::RenderStats, tiles::Tile,
+};
+#[cfg(feature = "bench")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Scenes the benchmark renders by default. Between them they cover the BVH with lots of
+/// objects, sphere tracing of SDFs, volumes and every kind of material.
+pub const BENCH_SCENES: [Scene; 7] = [
+    Scene::ManySpheres,
+    Scene::CornellBox,
+    Scene::CornellSmokes,
+    Scene::FinalScene,
+    Scene::MengerSponge,
+    Scene::MandelBulb,
+    Scene::Knot,
+];
+
+/// Settings every scene of a benchmark is rendered with, instead of the scene's own, so
+/// runs on different versions do the same work
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchSettings {
+    pub width: u16,
+    pub samples_per_pixel: u16,
+    pub seed: u64,
+    /// Times each scene is rendered. The fastest one is kept, as it's the one with the
+    /// least noise from everything else running on the machine.
+    pub runs: u32,
+}
+
+impl Default for BenchSettings {
+    fn default() -> Self {
+        Self {
+            width: 200,
+            samples_per_pixel: 16,
+            seed: 0,
+            runs: 3,
+        }
+    }
+}
+
+/// How a scene did in a benchmark
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchResult {
+    pub scene: String,
+    pub width: u16,
+    pub height: usize,
+    pub samples_per_pixel: u16,
+    pub seed: u64,
+    /// Time to build the world, with its BVHs
+    pub build_time: Duration,
+    /// Counts of the fastest render, and its time
+    pub stats: RenderStats,
+    /// Most memory allocated at once while building and rendering the scene. It's only
+    /// counted with the `bench` feature, and is 0 otherwise.
+    pub peak_memory: u64,
+}
+
+/// Renders a scene with the benchmark settings
+pub fn bench_scene(scene: Scene, settings: &BenchSettings) -> BenchResult {
+    let mut height = 0;
+    let mut fastest: Option<(Duration, RenderStats, u64)> = None;
+
+    for _ in 0..settings.runs.max(1) {
+        reset_peak_memory();
+        let memory_before = allocated_memory();
+
+        let start = Instant::now();
+        let mut world = generate_world(scene);
+        world.image_width = settings.width;
+        world.samples_per_pixel = settings.samples_per_pixel;
+        world.adaptive = None;
+        world.seed = settings.seed;
+        let build_time = start.elapsed();
+        height = world.image_height();
+
+        let session = RenderSession::new();
+        render_world_with(&world, &session, |_: &Tile, _: &Framebuffer| {});
+        let stats = session.stats();
+        let peak_memory = peak_memory().saturating_sub(memory_before);
+
+        let faster = match &fastest {
+            Some((_, fastest, _)) => stats.elapsed < fastest.elapsed,
+            None => true,
+        };
+        if faster {
+            fastest = Some((build_time, stats, peak_memory));
+        }
+    }
+
+    let (build_time, stats, peak_memory) = fastest.unwrap();
+    BenchResult {
+        scene: scene.name().to_string(),
+        width: settings.width,
+        height,
+        samples_per_pixel: settings.samples_per_pixel,
+        seed: settings.seed,
+        build_time,
+        stats,
+        peak_memory,
+    }
+}
+
+/// Results of a benchmark, which can be saved as CSV and read back to compare with
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BenchReport {
+    pub results: Vec<BenchResult>,
+}
+
+const COLUMNS: [&str; 13] = [
+    "scene",
+    "width",
+    "height",
+    "spp",
+    "seed",
+    "build_seconds",
+    "render_seconds",
+    "samples",
+    "rays",
+    "rays_per_second",
+    "intersection_tests",
+    "bvh_nodes_visited",
+    "peak_memory_bytes",
+];
+
+/// Writes the report as CSV, with a row for every scene
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", COLUMNS.join(","))?;
+        for result in &self.results {
+            writeln!(
+                f,
+                "{},{},{},{},{},{:.6},{:.6},{},{},{:.0},{},{},{}",
+                result.scene,
+                result.width,
+                result.height,
+                result.samples_per_pixel,
+                result.seed,
+                result.build_time.as_secs_f64(),
+                result.stats.elapsed.as_secs_f64(),
+                result.stats.samples,
+                result.stats.rays,
+                result.stats.rays_per_second(),
+                result.stats.intersection_tests,
+                result.stats.bvh_nodes_visited,
+                result.peak_memory,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a report written by `Display`. The columns are found by their name in the header,
+/// and the ones that can be computed from others, like rays per second, are ignored.
+impl FromStr for BenchReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().filter(|line| !line.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or("the report is empty")?
+            .split(',')
+            .map(str::trim)
+            .collect();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|column| *column == name)
+                .ok_or(format!("the report has no `{}` column", name))
+        };
+        let columns = COLUMNS
+            .iter()
+            .map(|name| Ok((*name, column(name)?)))
+            .collect::<Result<HashMap<_, _>, String>>()?;
+
+        let mut results = Vec::new();
+        for (row, line) in lines.enumerate() {
+            let values: Vec<&str> = line.split(',').map(str::trim).collect();
+            let value = |name: &str| values.get(columns[name]).copied().unwrap_or("");
+            let count = |name: &str| parse_field::<u64>(value(name), name, row + 1);
+            let seconds =
+                |name: &str| parse_field(value(name), name, row + 1).map(Duration::from_secs_f64);
+
+            results.push(BenchResult {
+                scene: value("scene").to_string(),
+                width: parse_field(value("width"), "width", row + 1)?,
+                height: parse_field(value("height"), "height", row + 1)?,
+                samples_per_pixel: parse_field(value("spp"), "spp", row + 1)?,
+                seed: count("seed")?,
+                build_time: seconds("build_seconds")?,
+                stats: RenderStats {
+                    samples: count("samples")?,
+                    rays: count("rays")?,
+                    intersection_tests: count("intersection_tests")?,
+                    bvh_nodes_visited: count("bvh_nodes_visited")?,
+                    elapsed: seconds("render_seconds")?,
+                },
+                peak_memory: count("peak_memory_bytes")?,
+            });
+        }
+
+        Ok(Self { results })
+    }
+}
+
+fn parse_field<T: FromStr>(text: &str, name: &str, row: usize) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("row {}: `{}` isn't a valid {}", row, text, name))
+}
+
+/// A scene of a report next to the same scene in a baseline
+#[derive(Clone, Debug)]
+pub struct BenchComparison<'a> {
+    pub result: &'a BenchResult,
+    pub baseline: &'a BenchResult,
+}
+
+impl BenchComparison<'_> {
+    /// Change of the render time, as a fraction of the baseline. Negative is faster.
+    pub fn time_change(&self) -> f64 {
+        change(
+            self.result.stats.elapsed.as_secs_f64(),
+            self.baseline.stats.elapsed.as_secs_f64(),
+        )
+    }
+
+    pub fn memory_change(&self) -> f64 {
+        change(
+            self.result.peak_memory as f64,
+            self.baseline.peak_memory as f64,
+        )
+    }
+
+    /// Whether both were rendered with the same settings, so their times can be compared
+    pub fn same_settings(&self) -> bool {
+        let (a, b) = (self.result, self.baseline);
+        (a.width, a.height, a.samples_per_pixel, a.seed)
+            == (b.width, b.height, b.samples_per_pixel, b.seed)
+    }
+
+    /// Whether it's more than `tolerance` percent slower than the baseline. Renders with
+    /// different settings are never regressions, as their times can't be compared.
+    pub fn regressed(&self, tolerance: f64) -> bool {
+        self.same_settings() && self.time_change() * 100. > tolerance
+    }
+
+    /// Whether the renders traced different rays or tested different objects. With the
+    /// same settings that means the renderer changed what it does, not just how fast.
+    pub fn different_work(&self) -> bool {
+        let (a, b) = (&self.result.stats, &self.baseline.stats);
+        (a.rays, a.intersection_tests, a.bvh_nodes_visited)
+            != (b.rays, b.intersection_tests, b.bvh_nodes_visited)
+    }
+}
+
+fn change(value: f64, baseline: f64) -> f64 {
+    if baseline > 0. {
+        value / baseline - 1.
+    } else {
+        0.
+    }
+}
+
+impl BenchReport {
+    /// Pairs every scene with the one of the same name in the baseline. Scenes that are
+    /// only in one of the reports are left out.
+    pub fn compare<'a>(&'a self, baseline: &'a BenchReport) -> Vec<BenchComparison<'a>> {
+        self.results
+            .iter()
+            .filter_map(|result| {
+                let baseline = baseline
+                    .results
+                    .iter()
+                    .find(|baseline| baseline.scene == result.scene)?;
+                Some(BenchComparison { result, baseline })
+            })
+            .collect()
+    }
+}
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static PEAK_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+/// System allocator that keeps count of the memory allocated, for the benchmarks to
+/// report it. It's the global allocator with the `bench` feature.
+#[cfg(feature = "bench")]
+pub(crate) struct CountingAllocator;
+
+#[cfg(feature = "bench")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            count_allocation(layout.size() as u64);
+        }
+        pointer
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc_zeroed(layout);
+        if !pointer.is_null() {
+            count_allocation(layout.size() as u64);
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        ALLOCATED.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, pointer: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_pointer = System.realloc(pointer, layout, new_size);
+        if !new_pointer.is_null() {
+            ALLOCATED.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+            count_allocation(new_size as u64);
+        }
+        new_pointer
+    }
+}
+
+#[cfg(feature = "bench")]
+fn count_allocation(size: u64) {
+    let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_ALLOCATED.fetch_max(allocated, Ordering::Relaxed);
+}
+
+/// Memory allocated right now through `CountingAllocator`
+fn allocated_memory() -> u64 {
+    ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// Most memory allocated at once since the last `reset_peak_memory`
+fn peak_memory() -> u64 {
+    PEAK_ALLOCATED.load(Ordering::Relaxed)
+}
+
+fn reset_peak_memory() {
+    PEAK_ALLOCATED.store(allocated_memory(), Ordering::Relaxed);
+}
@@ -1,7 +1,8 @@
-use crate::{aabb::*, hit_record::*, hittable::*, ray::*};
-use rand::*;
+use crate::{aabb::*, hit_record::*, hittable::*, ray::*, sampler::*, stats::*};
 use std::cmp::Ordering;
 
+/// Tree of bounding boxes over a lot of objects, so a ray only gets tested against the
+/// ones it can hit
 pub struct BVHNode {
     left: Option<Box<dyn Hittable>>,
     right: Option<Box<dyn Hittable>>,
@@ -9,7 +10,15 @@ pub struct BVHNode {
 }
 
 impl Hittable for BVHNode {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        count_bvh_node();
         if self.bbox.hit(ray, taemin, t_max).is_none() {
             return false;
         }
@@ -18,16 +27,22 @@ impl Hittable for BVHNode {
         let mut hit_right = false;
 
         if let Some(left) = &self.left {
-            hit_left = left.hit(ray, taemin, t_max, hit_record);
+            hit_left = left.hit(ray, taemin, t_max, hit_record, sampler);
         }
         let t_max = if hit_left { hit_record.t } else { t_max };
         if let Some(right) = &self.right {
-            hit_right = right.hit(ray, taemin, t_max, hit_record);
+            hit_right = right.hit(ray, taemin, t_max, hit_record, sampler);
         }
 
         hit_left || hit_right
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        for child in self.left.iter_mut().chain(self.right.iter_mut()) {
+            child.number_objects(next);
+        }
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         Some(self.bbox)
     }
@@ -35,7 +50,7 @@ impl Hittable for BVHNode {
 
 impl BVHNode {
     pub fn new(mut objects: Vec<Box<dyn Hittable>>, time0: f32, time1: f32) -> Self {
-        let axis = rand::thread_rng().gen_range(0, 2);
+        let axis = longest_axis(&objects, time0, time1);
 
         let comparator = if axis == 0 {
             box_x_compare
@@ -85,6 +100,31 @@ impl BVHNode {
     }
 }
 
+/// Splitting along the axis where the objects are most spread out gives tighter boxes,
+/// and unlike picking one at random, always builds the same tree
+fn longest_axis(objects: &[Box<dyn Hittable>], time0: f32, time1: f32) -> usize {
+    let bbox = objects
+        .iter()
+        .filter_map(|object| object.bounding_box(time0, time1))
+        .fold(None, |acc: Option<AABB>, bbox| {
+            Some(acc.map_or(bbox, |acc| acc.surrounding_box(bbox)))
+        });
+
+    match bbox {
+        Some(bbox) => {
+            let extent = bbox.max - bbox.min;
+            if extent.x >= extent.y && extent.x >= extent.z {
+                0
+            } else if extent.y >= extent.z {
+                1
+            } else {
+                2
+            }
+        }
+        None => 0,
+    }
+}
+
 fn box_compare<'a>(a: &'a dyn Hittable, b: &'a dyn Hittable, axis: usize) -> Ordering {
     if let Some(box_a) = a.bounding_box(0.0, 0.0) {
         if let Some(box_b) = b.bounding_box(0.0, 0.0) {
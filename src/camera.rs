@@ -1,6 +1,8 @@
-use crate::{ray::*, vec3::*};
-use rand::*;
+use crate::{ray::*, sampler::*, vec3::*};
 
+/// Camera of a world. `CameraSettings` is easier to set up, and builds it for the image's
+/// aspect ratio.
+#[derive(Debug)]
 pub struct Camera {
     pub origin: Point,
     pub lower_left_corner: Point,
@@ -55,8 +57,15 @@ impl Camera {
         }
     }
 
-    pub fn ray(&self, s: f32, t: f32) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
+    /// Widens or narrows the view to a new aspect ratio, keeping the vertical field of view
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        let center = self.lower_left_corner + self.horizontal / 2.0 + self.vertical / 2.0;
+        self.horizontal = self.horizontal.normalize() * self.vertical.length() * aspect_ratio;
+        self.lower_left_corner = center - self.horizontal / 2.0 - self.vertical / 2.0;
+    }
+
+    pub fn ray(&self, s: f32, t: f32, sampler: &mut Sampler) -> Ray {
+        let rd = self.lens_radius * Vec3::in_unit_disk(sampler.get_2d());
         let offset = self.u * rd.x + self.v * rd.y;
         Ray {
             origin: self.origin + offset,
@@ -64,7 +73,59 @@ impl Camera {
                 - self.origin
                 - offset)
                 .normalize(),
-            time: rand::thread_rng().gen_range(self.time0, self.time1),
+            time: self.time0 + sampler.get_1d() * (self.time1 - self.time0),
         }
     }
 }
+
+/// Where the camera is and how it's set up, to build a `Camera` once the aspect ratio of
+/// the image is known
+#[derive(Copy, Clone, Debug)]
+pub struct CameraSettings {
+    pub look_from: Point,
+    pub look_at: Point,
+    /// Which way is up, to tilt the camera
+    pub up: Vec3,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    /// Diameter of the lens. 0 is a pinhole, which has everything in focus.
+    pub aperture: f32,
+    /// Distance from `look_from` to `look_at` if it isn't set
+    pub focus_distance: Option<f32>,
+    /// The shutter is open between these times, which moving objects are blurred over
+    pub time: (f32, f32),
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            look_from: Point::new(13., 2., 3.),
+            look_at: Point::zeros(),
+            up: Vec3::new(0., 1., 0.),
+            fov: 20.,
+            aperture: 0.,
+            focus_distance: None,
+            time: (0., 1.),
+        }
+    }
+}
+
+impl CameraSettings {
+    pub fn build(&self, aspect_ratio: f32) -> Camera {
+        let focus_distance = self
+            .focus_distance
+            .unwrap_or_else(|| (self.look_from - self.look_at).length());
+
+        Camera::new(
+            self.look_from,
+            self.look_at,
+            self.up,
+            self.fov,
+            aspect_ratio,
+            self.aperture,
+            focus_distance,
+            self.time.0,
+            self.time.1,
+        )
+    }
+}
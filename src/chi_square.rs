@@ -0,0 +1,305 @@
+//! Chi-square goodness of fit test for the routines that sample directions: checks that the
+//! directions a routine generates are distributed like the density it says they have.
+//!
+//! The sphere of directions is split into bins of the same solid angle. The density is
+//! integrated over every bin to get how many samples it should get, and those counts are
+//! compared with how many the generated directions put in it. Bins that should get less
+//! than a few samples are merged, as the test isn't reliable for them.
+
+use crate::{sampler::*, vec3::*};
+use std::f64::consts::PI;
+
+pub struct ChiSquareTest {
+    /// Bins along the cosine of the angle with the z axis
+    pub theta_bins: usize,
+    /// Bins around the z axis
+    pub phi_bins: usize,
+    pub samples: usize,
+    /// Points per side of the grid every bin is integrated with
+    pub supersampling: usize,
+    /// Probability of rejecting a routine that is right
+    pub significance: f64,
+    pub seed: u64,
+}
+
+impl Default for ChiSquareTest {
+    fn default() -> Self {
+        Self {
+            theta_bins: 20,
+            phi_bins: 40,
+            samples: 100_000,
+            supersampling: 32,
+            significance: 0.001,
+            seed: 0,
+        }
+    }
+}
+
+/// Fewest samples a bin should get to be tested on its own
+const MIN_EXPECTED: f64 = 5.;
+
+impl ChiSquareTest {
+    /// Checks that `generate` gives directions distributed like `value`, which returns the
+    /// density of a direction with respect to solid angle. The directions don't need to be
+    /// normalized.
+    pub fn run<G, V>(&self, mut generate: G, mut value: V) -> Result<(), String>
+    where
+        G: FnMut(&mut Sampler) -> Vec3,
+        V: FnMut(Vec3, &mut Sampler) -> f32,
+    {
+        let mut sampler = Sampler::new(self.seed, 0);
+
+        let mut observed = vec![0.; self.theta_bins * self.phi_bins];
+        let mut zero_density = 0;
+        for _ in 0..self.samples {
+            let direction = generate(&mut sampler);
+            if value(direction, &mut sampler) <= 0. {
+                zero_density += 1;
+            }
+            match self.bin(direction) {
+                Some(bin) => observed[bin] += 1.,
+                None => return Err(format!("generated an invalid direction {:?}", direction)),
+            }
+        }
+        // Directions right on the edge of a shape can miss it by rounding, but no more
+        if zero_density as f64 > self.samples as f64 * 1e-4 {
+            return Err(format!(
+                "{} of the {} generated directions have a density of 0",
+                zero_density, self.samples
+            ));
+        }
+
+        let expected: Vec<f64> = self
+            .bin_integrals(&mut value, &mut sampler)
+            .iter()
+            .map(|integral| integral * self.samples as f64)
+            .collect();
+
+        let (statistic, degrees_of_freedom) = chi_square_statistic(&observed, &expected);
+        if degrees_of_freedom == 0 {
+            return Err("the density is too concentrated to test, use more bins".to_string());
+        }
+
+        let p_value = chi_square_p_value(statistic, degrees_of_freedom);
+        if p_value < self.significance {
+            return Err(format!(
+                "the generated directions don't follow the density: chi-square is {:.2} with \
+                 {} degrees of freedom, p-value {:.2e}",
+                statistic, degrees_of_freedom, p_value
+            ));
+        }
+        Ok(())
+    }
+
+    /// Integral of the density over the whole sphere, which should be 1
+    pub fn integral<V>(&self, mut value: V) -> f64
+    where
+        V: FnMut(Vec3, &mut Sampler) -> f32,
+    {
+        let mut sampler = Sampler::new(self.seed, 1);
+        self.bin_integrals(&mut value, &mut sampler).iter().sum()
+    }
+
+    fn bin(&self, direction: Vec3) -> Option<usize> {
+        let direction = direction.normalize();
+        if !direction.x.is_finite() || !direction.y.is_finite() || !direction.z.is_finite() {
+            return None;
+        }
+
+        let cos_theta = (direction.z as f64).clamp(-1., 1.);
+        let phi = (direction.y as f64).atan2(direction.x as f64);
+        let theta_bin = ((cos_theta + 1.) / 2. * self.theta_bins as f64) as usize;
+        let phi_bin = ((phi + PI) / (2. * PI) * self.phi_bins as f64) as usize;
+        Some(theta_bin.min(self.theta_bins - 1) * self.phi_bins + phi_bin.min(self.phi_bins - 1))
+    }
+
+    /// Integral of the density over every bin, with the midpoint rule on a grid in cosine
+    /// of theta and phi, where the solid angle of every cell is the same
+    fn bin_integrals<V>(&self, value: &mut V, sampler: &mut Sampler) -> Vec<f64>
+    where
+        V: FnMut(Vec3, &mut Sampler) -> f32,
+    {
+        let cos_theta_step = 2. / (self.theta_bins * self.supersampling) as f64;
+        let phi_step = 2. * PI / (self.phi_bins * self.supersampling) as f64;
+        let cell_solid_angle = cos_theta_step * phi_step;
+
+        let mut integrals = vec![0.; self.theta_bins * self.phi_bins];
+        for theta_cell in 0..self.theta_bins * self.supersampling {
+            let cos_theta = -1. + (theta_cell as f64 + 0.5) * cos_theta_step;
+            let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+
+            for phi_cell in 0..self.phi_bins * self.supersampling {
+                let phi = -PI + (phi_cell as f64 + 0.5) * phi_step;
+                let direction = Vec3::new(
+                    (sin_theta * phi.cos()) as f32,
+                    (sin_theta * phi.sin()) as f32,
+                    cos_theta as f32,
+                );
+
+                let bin = (theta_cell / self.supersampling) * self.phi_bins
+                    + phi_cell / self.supersampling;
+                integrals[bin] += value(direction, sampler) as f64 * cell_solid_angle;
+            }
+        }
+        integrals
+    }
+}
+
+/// Pearson's statistic, merging the bins that should get the fewest samples until they
+/// should get enough together, and testing them as one. The integrals of the bins on the
+/// edge of the density aren't exact, so a few samples in a bin that should get none are
+/// fine. Returns it with its degrees of freedom.
+fn chi_square_statistic(observed: &[f64], expected: &[f64]) -> (f64, usize) {
+    let mut bins: Vec<(f64, f64)> = observed
+        .iter()
+        .copied()
+        .zip(expected.iter().copied())
+        .collect();
+    bins.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut statistic = 0.;
+    let mut tested_bins = 0;
+    let (mut merged_observed, mut merged_expected) = (0., 0.);
+    for (observed, expected) in bins {
+        if merged_expected < MIN_EXPECTED {
+            merged_observed += observed;
+            merged_expected += expected;
+        } else {
+            statistic += (observed - expected).powi(2) / expected;
+            tested_bins += 1;
+        }
+    }
+
+    if merged_expected > 0. {
+        statistic += (merged_observed - merged_expected).powi(2) / merged_expected;
+        tested_bins += 1;
+    } else if merged_observed > 0. {
+        // Samples where there should be none at all
+        return (f64::INFINITY, tested_bins.max(1));
+    }
+
+    (statistic, tested_bins.saturating_sub(1))
+}
+
+/// Probability of a statistic at least this big if the samples follow the density
+fn chi_square_p_value(statistic: f64, degrees_of_freedom: usize) -> f64 {
+    if statistic.is_infinite() {
+        return 0.;
+    }
+    regularized_gamma_q(degrees_of_freedom as f64 / 2., statistic / 2.)
+}
+
+/// Upper regularized incomplete gamma function, as in Numerical Recipes
+fn regularized_gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 1.;
+    }
+
+    if x < a + 1. {
+        // Series of the lower function
+        let mut term = 1. / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..1000 {
+            n += 1.;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1. - sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+    } else {
+        // Continued fraction, with Lentz's method
+        let tiny = 1e-300;
+        let mut b = x + 1. - a;
+        let mut c = 1. / tiny;
+        let mut d = 1. / b;
+        let mut fraction = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1. / d;
+            let delta = d * c;
+            fraction *= delta;
+            if (delta - 1.).abs() < 1e-15 {
+                break;
+            }
+        }
+        (-x + a * x.ln() - ln_gamma(a)).exp() * fraction
+    }
+}
+
+/// Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate() {
+        series += coefficient / (x + 1. + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn p_values_match_known_ones() {
+        // From chi-square tables
+        assert!((chi_square_p_value(3.841, 1) - 0.05).abs() < 1e-3);
+        assert!((chi_square_p_value(18.307, 10) - 0.05).abs() < 1e-3);
+        assert!((chi_square_p_value(23.209, 10) - 0.01).abs() < 1e-3);
+        assert!((chi_square_p_value(124.342, 100) - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn uniform_sphere_sampling_passes() {
+        let test = ChiSquareTest::default();
+        let uniform = |sampler: &mut Sampler| {
+            let (r1, r2) = sampler.get_2d();
+            let z = 1. - 2. * r2;
+            let r = (1. - z * z).sqrt();
+            let phi = 2. * std::f32::consts::PI * r1;
+            Vec3::new(r * phi.cos(), r * phi.sin(), z)
+        };
+        let density = |_: Vec3, _: &mut Sampler| 1. / (4. * std::f32::consts::PI);
+
+        assert!((test.integral(density) - 1.).abs() < 1e-3);
+        test.run(uniform, density).unwrap();
+    }
+
+    #[test]
+    fn wrong_density_fails() {
+        let test = ChiSquareTest::default();
+        // Cosine weighted directions, said to be uniform over the hemisphere
+        let cosine = |sampler: &mut Sampler| Vec3::cosine_direction(sampler.get_2d());
+        let uniform = |direction: Vec3, _: &mut Sampler| {
+            if direction.z > 0. {
+                1. / (2. * std::f32::consts::PI)
+            } else {
+                0.
+            }
+        };
+
+        assert!(test.run(cosine, uniform).is_err());
+    }
+}
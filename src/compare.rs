@@ -0,0 +1,328 @@
+//! Error metrics between a render and a reference image, to check that changes to the
+//! renderer still converge to the same picture
+
+use crate::{framebuffer::*, tonemap::*, vec3::*};
+use std::{fmt, str::FromStr};
+
+/// How far an image is from the reference. RMSE, relMSE and PSNR are on the linear values,
+/// SSIM on the luminance of the sRGB display values, clamped to [0, 1].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageMetrics {
+    pub rmse: f64,
+    /// Squared error divided by the squared reference value, so dark and bright parts of
+    /// the image count the same
+    pub rel_mse: f64,
+    /// Peak signal to noise ratio in decibels, with 1 as the peak. Infinite for identical
+    /// images.
+    pub psnr: f64,
+    /// Structural similarity, 1 for identical images
+    pub ssim: f64,
+    /// Largest difference of a pixel, averaged over its channels
+    pub max_difference: f32,
+}
+
+/// Keeps relMSE from blowing up where the reference is black
+const REL_MSE_EPSILON: f64 = 0.01;
+
+/// Compares two images of the same size. Panics if they aren't.
+pub fn compare_images(image: &Framebuffer, reference: &Framebuffer) -> ImageMetrics {
+    assert_eq!(
+        (image.width, image.height),
+        (reference.width, reference.height),
+        "Images of different sizes"
+    );
+
+    let mut squared_error = 0.;
+    let mut relative_squared_error = 0.;
+    let mut max_difference = 0_f32;
+    for (color, reference) in image.pixels().iter().zip(reference.pixels()) {
+        for channel in 0..3 {
+            let value = color[channel] as f64;
+            let reference = reference[channel] as f64;
+            let error = (value - reference) * (value - reference);
+            squared_error += error;
+            relative_squared_error += error / (reference * reference + REL_MSE_EPSILON);
+        }
+        max_difference = max_difference.max(pixel_difference(*color, *reference));
+    }
+
+    let values = (image.pixels().len() * 3).max(1) as f64;
+    let mse = squared_error / values;
+    ImageMetrics {
+        rmse: mse.sqrt(),
+        rel_mse: relative_squared_error / values,
+        psnr: -10. * mse.log10(),
+        ssim: ssim(image, reference),
+        max_difference,
+    }
+}
+
+fn pixel_difference(color: Color, reference: Color) -> f32 {
+    let difference = color - reference;
+    (difference.x.abs() + difference.y.abs() + difference.z.abs()) / 3.
+}
+
+/// Mean SSIM, with the usual 11x11 Gaussian window of sigma 1.5
+fn ssim(image: &Framebuffer, reference: &Framebuffer) -> f64 {
+    let (width, height) = (image.width, image.height);
+    if width == 0 || height == 0 {
+        return 1.;
+    }
+
+    let a = luminance(image);
+    let b = luminance(reference);
+    let product = |x: &[f64], y: &[f64]| x.iter().zip(y).map(|(x, y)| x * y).collect::<Vec<_>>();
+
+    let kernel = gaussian_kernel(5, 1.5);
+    let blur = |values: &[f64]| blur(values, width, height, &kernel);
+    let mean_a = blur(&a);
+    let mean_b = blur(&b);
+    let mean_aa = blur(&product(&a, &a));
+    let mean_bb = blur(&product(&b, &b));
+    let mean_ab = blur(&product(&a, &b));
+
+    let c1 = 0.01_f64.powi(2);
+    let c2 = 0.03_f64.powi(2);
+    let total: f64 = (0..width * height)
+        .map(|i| {
+            let variance_a = mean_aa[i] - mean_a[i] * mean_a[i];
+            let variance_b = mean_bb[i] - mean_b[i] * mean_b[i];
+            let covariance = mean_ab[i] - mean_a[i] * mean_b[i];
+            ((2. * mean_a[i] * mean_b[i] + c1) * (2. * covariance + c2))
+                / ((mean_a[i] * mean_a[i] + mean_b[i] * mean_b[i] + c1)
+                    * (variance_a + variance_b + c2))
+        })
+        .sum();
+    total / (width * height) as f64
+}
+
+/// Luminance of the display values, which is what SSIM is meant to measure
+fn luminance(image: &Framebuffer) -> Vec<f64> {
+    image
+        .pixels()
+        .iter()
+        .map(|color| {
+            let color = color.nan_to_zero();
+            0.2126 * srgb_oetf(color.x) as f64
+                + 0.7152 * srgb_oetf(color.y) as f64
+                + 0.0722 * srgb_oetf(color.z) as f64
+        })
+        .collect()
+}
+
+fn gaussian_kernel(radius: usize, sigma: f64) -> Vec<f64> {
+    let kernel: Vec<f64> = (0..=2 * radius)
+        .map(|i| {
+            let x = i as f64 - radius as f64;
+            (-x * x / (2. * sigma * sigma)).exp()
+        })
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    kernel.into_iter().map(|weight| weight / sum).collect()
+}
+
+/// Separable blur, repeating the pixels of the edges
+fn blur(values: &[f64], width: usize, height: usize, kernel: &[f64]) -> Vec<f64> {
+    let radius = kernel.len() as isize / 2;
+    let clamp = |value: isize, size: usize| value.clamp(0, size as isize - 1) as usize;
+
+    let mut horizontal = vec![0.; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[x + y * width] = kernel
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| {
+                    let sample_x = clamp(x as isize + i as isize - radius, width);
+                    weight * values[sample_x + y * width]
+                })
+                .sum();
+        }
+    }
+
+    let mut blurred = vec![0.; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            blurred[x + y * width] = kernel
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| {
+                    let sample_y = clamp(y as isize + i as isize - radius, height);
+                    weight * horizontal[x + sample_y * width]
+                })
+                .sum();
+        }
+    }
+    blurred
+}
+
+/// Difference that 99% of the pixels are under, which makes a better scale for the
+/// difference image than the largest one, as a few fireflies don't leave the rest black
+pub fn difference_scale(image: &Framebuffer, reference: &Framebuffer) -> f32 {
+    let mut differences: Vec<f32> = image
+        .pixels()
+        .iter()
+        .zip(reference.pixels())
+        .map(|(color, reference)| pixel_difference(*color, *reference))
+        .collect();
+    if differences.is_empty() {
+        return 0.;
+    }
+    differences.sort_by(f32::total_cmp);
+    differences[(differences.len() - 1) * 99 / 100]
+}
+
+/// False color image of the difference of every pixel, going from black where they're the
+/// same, through purple, red and yellow, to white where the difference is `scale` or more
+pub fn difference_image(image: &Framebuffer, reference: &Framebuffer, scale: f32) -> Framebuffer {
+    assert_eq!(
+        (image.width, image.height),
+        (reference.width, reference.height),
+        "Images of different sizes"
+    );
+
+    let pixels = image
+        .pixels()
+        .iter()
+        .zip(reference.pixels())
+        .map(|(color, reference)| {
+            let difference = pixel_difference(*color, *reference);
+            let t = if scale > 0. {
+                (difference / scale).clamp(0., 1.)
+            } else {
+                0.
+            };
+            false_color(t)
+        })
+        .collect();
+    Framebuffer::from_pixels(image.width, image.height, pixels)
+}
+
+/// Maps 0 to 1 to a color, roughly following the inferno colormap
+fn false_color(t: f32) -> Color {
+    const STOPS: [[f32; 3]; 5] = [
+        [0., 0., 0.],
+        [0.34, 0.06, 0.43],
+        [0.73, 0.21, 0.33],
+        [0.98, 0.55, 0.04],
+        [0.99, 1., 0.64],
+    ];
+
+    let position = t * (STOPS.len() - 1) as f32;
+    let index = (position as usize).min(STOPS.len() - 2);
+    let fraction = position - index as f32;
+    let [r0, g0, b0] = STOPS[index];
+    let [r1, g1, b1] = STOPS[index + 1];
+    // The stops are sRGB, and the framebuffer is linear
+    Color::new(
+        srgb_eotf(r0 + (r1 - r0) * fraction),
+        srgb_eotf(g0 + (g1 - g0) * fraction),
+        srgb_eotf(b0 + (b1 - b0) * fraction),
+    )
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Metric {
+    Rmse,
+    RelMse,
+    Psnr,
+    Ssim,
+}
+
+impl Metric {
+    pub fn value(&self, metrics: &ImageMetrics) -> f64 {
+        match self {
+            Self::Rmse => metrics.rmse,
+            Self::RelMse => metrics.rel_mse,
+            Self::Psnr => metrics.psnr,
+            Self::Ssim => metrics.ssim,
+        }
+    }
+
+    /// Whether the image is close enough to the reference. PSNR and SSIM have to be at least
+    /// the threshold, the errors at most.
+    pub fn passes(&self, metrics: &ImageMetrics, threshold: f64) -> bool {
+        let value = self.value(metrics);
+        match self {
+            Self::Rmse | Self::RelMse => value <= threshold,
+            Self::Psnr | Self::Ssim => value >= threshold,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rmse" => Ok(Self::Rmse),
+            "relmse" => Ok(Self::RelMse),
+            "psnr" => Ok(Self::Psnr),
+            "ssim" => Ok(Self::Ssim),
+            _ => Err(format!(
+                "Unknown metric: {}, expected rmse, relmse, psnr or ssim",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ImageMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "RMSE    {:.6}", self.rmse)?;
+        writeln!(f, "relMSE  {:.6}", self.rel_mse)?;
+        writeln!(f, "PSNR    {:.2} dB", self.psnr)?;
+        writeln!(f, "SSIM    {:.6}", self.ssim)?;
+        write!(f, "Largest difference {:.6}", self.max_difference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image with some detail, for SSIM to have structure to compare
+    fn image() -> Framebuffer {
+        let (width, height) = (24, 16);
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f32, (i / width) as f32);
+                Color::new(x / width as f32, y / height as f32, (x * y).sin().abs())
+            })
+            .collect();
+        Framebuffer::from_pixels(width, height, pixels)
+    }
+
+    #[test]
+    fn identical_images_have_no_error() {
+        let metrics = compare_images(&image(), &image());
+        assert_eq!(metrics.rmse, 0.);
+        assert_eq!(metrics.rel_mse, 0.);
+        assert_eq!(metrics.psnr, f64::INFINITY);
+        assert_eq!(metrics.ssim, 1.);
+        assert_eq!(metrics.max_difference, 0.);
+    }
+
+    #[test]
+    fn constant_offsets_are_the_rmse() {
+        let reference = image();
+        let pixels = reference
+            .pixels()
+            .iter()
+            .map(|color| *color + Color::from(0.25))
+            .collect();
+        let image = Framebuffer::from_pixels(reference.width, reference.height, pixels);
+
+        let metrics = compare_images(&image, &reference);
+        assert!((metrics.rmse - 0.25).abs() < 1e-6, "{}", metrics.rmse);
+        assert!((metrics.psnr - -20. * 0.25_f64.log10()).abs() < 1e-4);
+        assert!((metrics.max_difference - 0.25).abs() < 1e-6);
+        assert!(metrics.ssim < 1.);
+    }
+
+    #[test]
+    #[should_panic(expected = "Images of different sizes")]
+    fn images_of_different_sizes_are_rejected() {
+        compare_images(&image(), &Framebuffer::new(16, 24));
+    }
+}
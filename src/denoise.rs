@@ -0,0 +1,159 @@
+use crate::{adaptive::luminance, aov::*, framebuffer::*, vec3::*};
+use rayon::prelude::*;
+
+/// Edge-avoiding À-Trous wavelet filter (Dammertz et al. 2010), guided by the AOVs.
+///
+/// The albedo is divided out before filtering and multiplied back in afterwards, so
+/// textures stay sharp and only the lighting gets blurred.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Denoiser {
+    /// Every iteration doubles the size of the filter, 5 gives a 125x125 footprint
+    pub iterations: u32,
+    /// How different two colors can be before they stop being blended. It halves every iteration.
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    /// Relative to the depth of the pixel
+    pub sigma_depth: f32,
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            sigma_color: 0.6,
+            sigma_normal: 0.3,
+            sigma_depth: 0.02,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+const KERNEL: [f32; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+impl Denoiser {
+    pub fn denoise(&self, image: &Framebuffer, aovs: &AOVBuffers) -> Framebuffer {
+        let width = image.width;
+        let height = image.height;
+        let albedo = aovs.albedo.pixels();
+
+        // Demodulate the albedo
+        let mut irradiance: Vec<Color> = image
+            .pixels()
+            .iter()
+            .zip(albedo)
+            .map(|(color, albedo)| *color / albedo.max(0.01))
+            .collect();
+        remove_fireflies(&mut irradiance, width, height);
+
+        let mut sigma_color = self.sigma_color;
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let input = &irradiance;
+
+            irradiance = (0..height)
+                .into_par_iter()
+                .map(|y| {
+                    (0..width)
+                        .map(|x| self.filter_pixel(input, aovs, x, y, step, sigma_color))
+                        .collect::<Vec<Color>>()
+                })
+                .flatten()
+                .collect();
+
+            sigma_color *= 0.5;
+        }
+
+        let pixels = irradiance
+            .iter()
+            .zip(albedo)
+            .map(|(irradiance, albedo)| *irradiance * albedo.max(0.01))
+            .collect();
+
+        let mut output = Framebuffer::from_pixels(width, height, pixels);
+        output.display = image.display;
+        output
+    }
+
+    fn filter_pixel(
+        &self,
+        input: &[Color],
+        aovs: &AOVBuffers,
+        x: usize,
+        y: usize,
+        step: isize,
+        sigma_color: f32,
+    ) -> Color {
+        let width = aovs.albedo.width as isize;
+        let height = aovs.albedo.height as isize;
+        let albedo = aovs.albedo.pixels();
+        let normal = aovs.normal.pixels();
+        let depth = aovs.depth.pixels();
+
+        let p = x + y * width as usize;
+        let mut sum = Color::zeros();
+        let mut weight_sum = 0.0;
+
+        for (j, ky) in KERNEL.iter().enumerate() {
+            let qy = y as isize + (j as isize - 2) * step;
+            if qy < 0 || qy >= height {
+                continue;
+            }
+
+            for (i, kx) in KERNEL.iter().enumerate() {
+                let qx = x as isize + (i as isize - 2) * step;
+                if qx < 0 || qx >= width {
+                    continue;
+                }
+                let q = (qx + qy * width) as usize;
+
+                let color_distance = (input[p] - input[q]).length_squared();
+                let normal_distance = (normal[p] - normal[q]).length_squared();
+                let albedo_distance = (albedo[p] - albedo[q]).length_squared();
+                let depth_distance = (depth[p].x - depth[q].x).abs()
+                    / (self.sigma_depth * depth[p].x * step as f32 + 0.0001);
+
+                let exponent = color_distance / (sigma_color * sigma_color)
+                    + normal_distance / (self.sigma_normal * self.sigma_normal)
+                    + albedo_distance / (self.sigma_albedo * self.sigma_albedo)
+                    + depth_distance;
+                let weight = kx * ky * (-exponent).exp();
+
+                sum += weight * input[q];
+                weight_sum += weight;
+            }
+        }
+
+        // The center pixel always has a weight > 0, so this never divides by 0
+        sum / weight_sum
+    }
+}
+
+/// How many times brighter than all of its neighbours a pixel has to be to count as a firefly.
+/// Noise and small highlights are often a bit brighter, and the filter takes care of them.
+const FIREFLY_RATIO: f32 = 4.;
+
+/// Isolated pixels that are much brighter than everything around them would be kept by the
+/// edge-stopping functions, so they get clamped to the brightest of their neighbours first
+fn remove_fireflies(pixels: &mut [Color], width: usize, height: usize) {
+    let input = pixels.to_vec();
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut max = 0.0_f32;
+            for qy in y.saturating_sub(1)..(y + 2).min(height) {
+                for qx in x.saturating_sub(1)..(x + 2).min(width) {
+                    if qx != x || qy != y {
+                        max = max.max(luminance(input[qx + qy * width]));
+                    }
+                }
+            }
+
+            let p = x + y * width;
+            let current = luminance(input[p]);
+            if current > FIREFLY_RATIO * max && current > 0.0 {
+                pixels[p] = input[p] * (max / current);
+            }
+        }
+    }
+}
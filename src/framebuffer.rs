@@ -0,0 +1,585 @@
+use crate::{tonemap::*, vec3::*};
+use image as img;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// Linear, unclamped RGB radiance for every pixel of a render.
+/// Rows are stored top to bottom, pixels left to right.
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    /// How the 8 bit formats turn radiance into display values. HDR formats ignore it.
+    pub display: DisplayTransform,
+    pixels: Vec<Color>,
+}
+
+/// Most pixels an image read from a file can have, as many as one 65535 pixels on each side
+pub(crate) const MAX_PIXELS: usize = u16::MAX as usize * u16::MAX as usize;
+
+/// Pixels of an image of the size a file says it has, unless that's more than `MAX_PIXELS`.
+/// Files can't be trusted, so it's checked before allocating anything for them.
+pub(crate) fn checked_pixel_count(width: usize, height: usize) -> Option<usize> {
+    width
+        .checked_mul(height)
+        .filter(|&count| count <= MAX_PIXELS)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    /// Binary P6 PPM
+    Ppm,
+    /// Portable float map, 32 bit float per channel
+    Pfm,
+    /// Uncompressed scanline OpenEXR with 16 bit half channels
+    ExrHalf,
+    /// Uncompressed scanline OpenEXR with 32 bit float channels
+    ExrFloat,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "ppm" => Some(Self::Ppm),
+            "pfm" => Some(Self::Pfm),
+            "exr" => Some(Self::ExrHalf),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "ppm" => Ok(Self::Ppm),
+            "pfm" => Ok(Self::Pfm),
+            "exr" | "exr-half" => Ok(Self::ExrHalf),
+            "exr-float" => Ok(Self::ExrFloat),
+            _ => Err(format!("Unknown image format: {}", s)),
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            display: DisplayTransform::default(),
+            pixels: vec![Color::zeros(); width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height, "Wrong number of pixels");
+        Self {
+            width,
+            height,
+            display: DisplayTransform::default(),
+            pixels,
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[x + y * self.width]
+    }
+
+    #[inline(always)]
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[x + y * self.width] = color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// 8 bit display values, packed as RGBRGB...
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|color| self.display.to_rgb8(*color))
+            .collect()
+    }
+
+    /// ASCII P3 PPM, which is what the wasm canvas consumes
+    pub fn to_ppm_ascii(&self) -> String {
+        let mut string = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for color in &self.pixels {
+            let [r, g, b] = self.display.to_rgb8(*color);
+            string.push_str(&format!("{} {} {}\n", r, g, b));
+        }
+        string
+    }
+
+    /// Saves the image, picking the format from the file extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        match ImageFormat::from_path(path) {
+            Some(format) => self.save_as(path, format),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown image extension for {}", path.display()),
+            )),
+        }
+    }
+
+    pub fn save_as<P: AsRef<Path>>(&self, path: P, format: ImageFormat) -> io::Result<()> {
+        let path = path.as_ref();
+        match format {
+            ImageFormat::Png => self.write_png(path),
+            _ => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                match format {
+                    ImageFormat::Ppm => self.write_ppm(&mut writer)?,
+                    ImageFormat::Pfm => self.write_pfm(&mut writer)?,
+                    ImageFormat::ExrHalf => self.write_exr(&mut writer, ExrPixelType::Half)?,
+                    ImageFormat::ExrFloat => self.write_exr(&mut writer, ExrPixelType::Float)?,
+                    ImageFormat::Png => unreachable!(),
+                }
+                writer.flush()
+            }
+        }
+    }
+
+    /// Loads an image, picking the format from the file extension. PFM and EXR keep their
+    /// linear values, the rest are taken as 8 bit sRGB and turned back into linear.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Pfm) => Self::read_pfm(&mut BufReader::new(File::open(path)?)),
+            Some(ImageFormat::ExrHalf) | Some(ImageFormat::ExrFloat) => {
+                Self::read_exr(&mut BufReader::new(File::open(path)?))
+            }
+            _ => {
+                let image = img::open(path)
+                    .map_err(|why| invalid_data(why.to_string()))?
+                    .to_rgb();
+                let (width, height) = image.dimensions();
+                let pixels = image
+                    .pixels()
+                    .map(|pixel| {
+                        let [r, g, b] = pixel.0;
+                        Color::new(
+                            srgb_eotf(r as f32 / 255.),
+                            srgb_eotf(g as f32 / 255.),
+                            srgb_eotf(b as f32 / 255.),
+                        )
+                    })
+                    .collect();
+                Ok(Self::from_pixels(width as usize, height as usize, pixels))
+            }
+        }
+    }
+
+    pub fn write_png(&self, path: &Path) -> io::Result<()> {
+        let buffer = img::RgbImage::from_raw(self.width as u32, self.height as u32, self.to_rgb8())
+            .expect("Framebuffer size doesn't match its pixels");
+        buffer.save(path).map_err(io::Error::other)
+    }
+
+    pub fn write_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.to_rgb8())
+    }
+
+    pub fn write_pfm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        // Negative scale means little endian
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+
+        // PFM scanlines go from bottom to top
+        for row in self.pixels.chunks(self.width).rev() {
+            for color in row {
+                for channel in &[color.x, color.y, color.z] {
+                    writer.write_all(&channel.to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a color PFM, or a grayscale one into all three channels
+    pub fn read_pfm<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_data("the PFM header is cut short".to_string()));
+            }
+            header.extend(line.split_whitespace().map(str::to_string));
+        }
+
+        let channels = match header[0].as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid_data("not a PFM file".to_string())),
+        };
+        let invalid_value =
+            |index: usize| invalid_data(format!("invalid PFM header value {}", header[index]));
+        let size = |index: usize| {
+            header[index]
+                .parse::<usize>()
+                .map_err(|_| invalid_value(index))
+        };
+        let width = size(1)?;
+        let height = size(2)?;
+        let little_endian = header[3].parse::<f32>().map_err(|_| invalid_value(3))? < 0.;
+        let pixel_count = checked_pixel_count(width, height).ok_or_else(|| {
+            invalid_data(format!("the PFM image is {}x{}, too big", width, height))
+        })?;
+
+        // Only as many as have been read, so a file that's cut short runs out first, and
+        // doesn't get all of them allocated up front
+        let mut pixels = Vec::with_capacity(pixel_count.min(1 << 20));
+        for _ in 0..pixel_count {
+            let mut values = [0.; 3];
+            for value in values.iter_mut().take(channels) {
+                let mut bytes = [0; 4];
+                reader.read_exact(&mut bytes)?;
+                *value = if little_endian {
+                    f32::from_le_bytes(bytes)
+                } else {
+                    f32::from_be_bytes(bytes)
+                };
+            }
+            pixels.push(if channels == 1 {
+                Color::from(values[0])
+            } else {
+                Color::new(values[0], values[1], values[2])
+            });
+        }
+        // Scanlines go from bottom to top
+        let pixels = pixels
+            .chunks(width.max(1))
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+
+        Ok(Self::from_pixels(width, height, pixels))
+    }
+
+    /// Reads the uncompressed scanline EXR files `write_exr` writes, or any other with
+    /// R, G and B channels and no compression. Other channels are skipped.
+    pub fn read_exr<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let mut bytes = ExrBytes { data: &data, at: 0 };
+
+        if bytes.i32()? != 20_000_630 {
+            return Err(invalid_data("not an EXR file".to_string()));
+        }
+        // Tiled, deep and multipart flags
+        if bytes.i32()? & 0x1a00 != 0 {
+            return Err(invalid_data(
+                "only single part scanline EXR files can be read".to_string(),
+            ));
+        }
+
+        let mut channels = Vec::new();
+        let mut window = None;
+        loop {
+            let name = bytes.string()?;
+            if name.is_empty() {
+                break;
+            }
+            let _kind = bytes.string()?;
+            let size = bytes.i32()? as usize;
+            let mut value = ExrBytes {
+                data: bytes.take(size)?,
+                at: 0,
+            };
+            match name.as_str() {
+                "channels" => loop {
+                    let channel = value.string()?;
+                    if channel.is_empty() {
+                        break;
+                    }
+                    let pixel_type = value.i32()?;
+                    // pLinear, reserved bytes and sampling
+                    value.take(12)?;
+                    channels.push((channel, pixel_type));
+                },
+                "compression" if value.take(1)?[0] != 0 => {
+                    return Err(invalid_data(
+                        "only uncompressed EXR files can be read".to_string(),
+                    ));
+                }
+                "dataWindow" => {
+                    window = Some([value.i32()?, value.i32()?, value.i32()?, value.i32()?]);
+                }
+                _ => {}
+            }
+        }
+
+        let [x_min, y_min, x_max, y_max] =
+            window.ok_or_else(|| invalid_data("the EXR file has no dataWindow".to_string()))?;
+        let size = |min: i32, max: i32| {
+            max.checked_sub(min)
+                .and_then(|size| size.checked_add(1))
+                .map(|size| size.max(0) as usize)
+        };
+        let too_big = || invalid_data("the EXR dataWindow is too big".to_string());
+        let width = size(x_min, x_max).ok_or_else(too_big)?;
+        let height = size(y_min, y_max).ok_or_else(too_big)?;
+        // Every pixel takes some bytes, so a file can't have more of them than it has bytes
+        let pixel_count = checked_pixel_count(width, height)
+            .filter(|&count| count <= data.len())
+            .ok_or_else(too_big)?;
+        // Offsets of the scanlines, which come in order anyway
+        bytes.take(height.checked_mul(8).ok_or_else(too_big)?)?;
+
+        let mut pixels = vec![Color::zeros(); pixel_count];
+        for _ in 0..height {
+            // Through i64, so a y from the file can't overflow
+            let y = (bytes.i32()? as i64 - y_min as i64) as usize;
+            let _size = bytes.i32()?;
+            if y >= height {
+                return Err(invalid_data("EXR scanline out of the image".to_string()));
+            }
+            let row = &mut pixels[y * width..(y + 1) * width];
+
+            for (name, pixel_type) in &channels {
+                let channel = match name.as_str() {
+                    "R" => Some(0),
+                    "G" => Some(1),
+                    "B" => Some(2),
+                    _ => None,
+                };
+                for color in row.iter_mut() {
+                    let value = match pixel_type {
+                        0 => bytes.i32()? as u32 as f32,
+                        1 => {
+                            let value = bytes.take(2)?;
+                            f16_to_f32(u16::from_le_bytes([value[0], value[1]]))
+                        }
+                        _ => f32::from_bits(bytes.i32()? as u32),
+                    };
+                    if let Some(channel) = channel {
+                        color[channel] = value;
+                    }
+                }
+            }
+        }
+
+        Ok(Self::from_pixels(width, height, pixels))
+    }
+
+    pub fn write_exr<W: Write>(&self, writer: &mut W, pixel_type: ExrPixelType) -> io::Result<()> {
+        let width = self.width as i32;
+        let height = self.height as i32;
+
+        let mut header = Vec::new();
+        // Magic number and version 2, single part scanline file
+        header.extend_from_slice(&20_000_630_i32.to_le_bytes());
+        header.extend_from_slice(&2_i32.to_le_bytes());
+
+        // Channels have to be sorted alphabetically
+        let mut channels = Vec::new();
+        for name in &[b"B", b"G", b"R"] {
+            channels.extend_from_slice(*name);
+            channels.push(0);
+            channels.extend_from_slice(&(pixel_type as i32).to_le_bytes());
+            // pLinear and reserved bytes
+            channels.extend_from_slice(&[0, 0, 0, 0]);
+            // x and y sampling
+            channels.extend_from_slice(&1_i32.to_le_bytes());
+            channels.extend_from_slice(&1_i32.to_le_bytes());
+        }
+        channels.push(0);
+        exr_attribute(&mut header, "channels", "chlist", &channels);
+
+        // No compression
+        exr_attribute(&mut header, "compression", "compression", &[0]);
+
+        let mut window = Vec::new();
+        for value in &[0, 0, width - 1, height - 1] {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+        exr_attribute(&mut header, "dataWindow", "box2i", &window);
+        exr_attribute(&mut header, "displayWindow", "box2i", &window);
+
+        // Increasing y
+        exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        exr_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1_f32.to_le_bytes(),
+        );
+        exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        exr_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1_f32.to_le_bytes(),
+        );
+        header.push(0);
+
+        // Every scanline is its own chunk: y, byte count and the channels one after the other
+        let bytes_per_channel = match pixel_type {
+            ExrPixelType::Half => 2,
+            ExrPixelType::Float => 4,
+        };
+        let data_size = self.width * 3 * bytes_per_channel;
+        let chunk_size = (8 + data_size) as u64;
+        let first_chunk = (header.len() + 8 * self.height) as u64;
+
+        writer.write_all(&header)?;
+        for y in 0..self.height as u64 {
+            writer.write_all(&(first_chunk + y * chunk_size).to_le_bytes())?;
+        }
+
+        let mut data = Vec::with_capacity(data_size);
+        for (y, row) in self.pixels.chunks(self.width).enumerate() {
+            data.clear();
+            for channel in 0..3 {
+                // B, G, R
+                let channel = 2 - channel;
+                for color in row {
+                    match pixel_type {
+                        ExrPixelType::Half => {
+                            data.extend_from_slice(&f32_to_f16(color[channel]).to_le_bytes())
+                        }
+                        ExrPixelType::Float => {
+                            data.extend_from_slice(&color[channel].to_le_bytes())
+                        }
+                    }
+                }
+            }
+
+            writer.write_all(&(y as i32).to_le_bytes())?;
+            writer.write_all(&(data_size as i32).to_le_bytes())?;
+            writer.write_all(&data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExrPixelType {
+    Half = 1,
+    Float = 2,
+}
+
+/// Reads the little endian values of an EXR file, one after the other
+struct ExrBytes<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> ExrBytes<'a> {
+    fn take(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        let cut_short = || invalid_data("the EXR file is cut short".to_string());
+        let end = self.at.checked_add(count).ok_or_else(cut_short)?;
+        let bytes = self.data.get(self.at..end).ok_or_else(cut_short)?;
+        self.at = end;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Null terminated string
+    fn string(&mut self) -> io::Result<String> {
+        let length = self.data[self.at..]
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| invalid_data("the EXR file is cut short".to_string()))?;
+        let string = String::from_utf8_lossy(self.take(length)?).into_owned();
+        self.take(1)?;
+        Ok(string)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Converts to IEEE 754 half precision, rounding to nearest even
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    // Infinity and NaN
+    if exponent == 0xff {
+        let nan_bit = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan_bit;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+
+    // Too big, becomes infinity
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // Subnormal or zero
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = remainder > halfway || (remainder == halfway && half_mantissa & 1 == 1);
+        return sign | (half_mantissa + round_up as u32) as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    // Rounding can carry into the exponent, which still gives the right result
+    sign | (half + round_up as u32) as u16
+}
+
+/// Converts from IEEE 754 half precision
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x03ff) as u32;
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        // Subnormal, it's normal as a float
+        (0, _) => {
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x03ff;
+            sign | ((127 - 15 + 1 - shift) << 23) | (mantissa << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
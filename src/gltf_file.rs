@@ -0,0 +1,474 @@
+//! glTF 2.0 scenes, from a `.gltf` with its buffers and images or from a `.glb`, as a `World`.
+//!
+//! The meshes of every node are put in their place in a single `TriangleMesh`. Materials are
+//! matched as well as this renderer can: emissive ones are lights, blended ones with some
+//! transparency are glass, metallic ones are metals with their roughness as the fuzz, and the
+//! rest are lambertian, with their base color texture and vertex colors. Normal, occlusion
+//! and metallic-roughness textures aren't used.
+//!
+//! The first perspective camera is the camera of the world, and if there isn't one, the
+//! camera looks at everything from a corner. Punctual lights don't have a size, so they get
+//! a small one, and give about as much light as they would: point lights are spheres, spot
+//! lights are squares that only light forwards, without their cone, and directional lights
+//! are a sun far away. With no lights at all there's a sky, so the scene can still be seen.
+
+use crate::{
+    aabb::*, camera::*, hittable::mesh::*, hittable::sphere::*, hittable::triangle::*, material::*,
+    scenes::World, texture, texture::Texture, vec3::*, world_builder::*,
+};
+use gltf::{
+    camera::Projection, image::Format, khr_lights_punctual::Kind, material::AlphaMode, mesh::Mode,
+};
+use std::{f32::consts::PI, io, path::Path, sync::Arc};
+
+/// Radius of point lights, and half the side of spot lights, in meters like the rest of glTF
+const LIGHT_RADIUS: f32 = 0.05;
+/// Angular radius of the sun of directional lights, in degrees
+const SUN_RADIUS: f32 = 0.5;
+
+/// Loads a glTF scene, the default one or the first one if there's no default. Errors start
+/// with the path of the file.
+pub fn load_gltf(path: impl AsRef<Path>) -> io::Result<World> {
+    let path = path.as_ref();
+    let (document, buffers, images) = gltf::import(path).map_err(|why| match why {
+        gltf::Error::Io(why) => io::Error::new(why.kind(), format!("{}: {}", path.display(), why)),
+        why => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), why),
+        ),
+    })?;
+
+    let mut loader = GltfLoader {
+        buffers: &buffers,
+        textures: images.iter().map(image_texture).collect(),
+        mesh: Mesh::default(),
+        emissive: Vec::new(),
+        camera: None,
+        spheres: Vec::new(),
+        squares: Vec::new(),
+        suns: Vec::new(),
+    };
+    loader.mesh.materials = document
+        .materials()
+        .map(|material| loader.material(&material))
+        .collect();
+    // For primitives without a material
+    loader
+        .mesh
+        .materials
+        .push(Material::Lambertian(Color::from(0.8)));
+
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            loader.node(&node, &IDENTITY);
+        }
+    }
+    loader.mesh.validate().map_err(|why| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), why),
+        )
+    })?;
+
+    Ok(loader.build())
+}
+
+/// Column major, like glTF
+type Matrix = [[f32; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1., 0., 0., 0.],
+    [0., 1., 0., 0.],
+    [0., 0., 1., 0.],
+    [0., 0., 0., 1.],
+];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.; 4]; 4];
+    for (column, result) in result.iter_mut().enumerate() {
+        for (row, result) in result.iter_mut().enumerate() {
+            *result = (0..4).map(|k| a[k][row] * b[column][k]).sum();
+        }
+    }
+    result
+}
+
+fn column(matrix: &Matrix, index: usize) -> Vec3 {
+    Vec3::new(matrix[index][0], matrix[index][1], matrix[index][2])
+}
+
+fn transform_point(matrix: &Matrix, [x, y, z]: [f32; 3]) -> Point {
+    column(matrix, 0) * x + column(matrix, 1) * y + column(matrix, 2) * z + column(matrix, 3)
+}
+
+fn transform_vector(matrix: &Matrix, vector: Vec3) -> Vec3 {
+    column(matrix, 0) * vector.x + column(matrix, 1) * vector.y + column(matrix, 2) * vector.z
+}
+
+/// Normals are transformed by the inverse transpose, which is the cofactors over the
+/// determinant. Only the sign of the determinant matters once they are normalized.
+fn transform_normal(matrix: &Matrix, [x, y, z]: [f32; 3]) -> Vec3 {
+    let (a, b, c) = (column(matrix, 0), column(matrix, 1), column(matrix, 2));
+    let normal = b.cross(&c) * x + c.cross(&a) * y + a.cross(&b) * z;
+    normal.normalize() * determinant(matrix).signum()
+}
+
+fn determinant(matrix: &Matrix) -> f32 {
+    column(matrix, 0).dot(&column(matrix, 1).cross(&column(matrix, 2)))
+}
+
+/// Texture of an image, which is sRGB like the other textures
+fn image_texture(image: &gltf::image::Data) -> Texture {
+    let (channels, bytes) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 | Format::B8G8R8 => (3, 1),
+        Format::R8G8B8A8 | Format::B8G8R8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+    };
+    let bgr = matches!(image.format, Format::B8G8R8 | Format::B8G8R8A8);
+
+    let mut data = Vec::with_capacity(3 * image.pixels.len() / (channels * bytes));
+    for pixel in image.pixels.chunks_exact(channels * bytes) {
+        // The most significant byte of 16 bit channels, which are in native endianness
+        let channel = |index: usize| {
+            if bytes == 2 {
+                (u16::from_ne_bytes([pixel[2 * index], pixel[2 * index + 1]]) >> 8) as u8
+            } else {
+                pixel[index]
+            }
+        };
+        let rgb = match channels {
+            // Gray
+            1 | 2 => [channel(0); 3],
+            _ if bgr => [channel(2), channel(1), channel(0)],
+            _ => [channel(0), channel(1), channel(2)],
+        };
+        data.extend_from_slice(&rgb);
+    }
+    texture::rgb_image(image.width as usize, image.height as usize, data)
+}
+
+/// Texture times a color, which is how glTF uses the factors of its textures
+fn tinted(texture: Texture, tint: Color) -> Texture {
+    Arc::new(move |u, v, point| texture(u, v, point) * tint)
+}
+
+struct GltfLoader<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    /// Of every image, in order
+    textures: Vec<Texture>,
+    mesh: Mesh,
+    /// Triangles of emissive materials, which are sampled like lights
+    emissive: Vec<Triangle>,
+    /// First perspective camera, and its aspect ratio if it has one
+    camera: Option<(CameraSettings, Option<f32>)>,
+    /// Point lights
+    spheres: Vec<Sphere>,
+    /// Triangles of the squares of spot lights
+    squares: Vec<Triangle>,
+    /// Direction the light of directional lights goes towards, and their illuminance
+    suns: Vec<(Vec3, Color)>,
+}
+
+impl<'a> GltfLoader<'a> {
+    fn material(&self, material: &gltf::Material) -> Material {
+        let texture = |info: Option<gltf::texture::Info>| {
+            info.map(|info| self.textures[info.texture().source().index()].clone())
+        };
+
+        let emissive = Color::from_array(&material.emissive_factor());
+        if emissive.length_squared() > 0. {
+            return match texture(material.emissive_texture()) {
+                Some(emission) => Material::DiffuseLightTexture(tinted(emission, emissive)),
+                None => Material::DiffuseLight(emissive),
+            };
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, alpha] = pbr.base_color_factor();
+        let base_color = Color::new(r, g, b);
+        if material.alpha_mode() == AlphaMode::Blend && alpha < 1. {
+            return Material::Dielectric(1.5);
+        }
+        if pbr.metallic_factor() >= 0.5 {
+            return Material::Metal(base_color, pbr.roughness_factor().clamp(0., 1.));
+        }
+        match texture(pbr.base_color_texture()) {
+            Some(albedo) => Material::LambertianTexture(tinted(albedo, base_color)),
+            None => Material::Lambertian(base_color),
+        }
+    }
+
+    /// Adds a node and its children, where `parent` is the transform of its parent
+    fn node(&mut self, node: &gltf::Node, parent: &Matrix) {
+        let transform = multiply(parent, &node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.primitive(&primitive, &transform);
+            }
+        }
+        if let Some(camera) = node.camera() {
+            self.camera(&camera, &transform);
+        }
+        if let Some(light) = node.light() {
+            self.light(&light, &transform);
+        }
+
+        for child in node.children() {
+            self.node(&child, &transform);
+        }
+    }
+
+    fn primitive(&mut self, primitive: &gltf::Primitive, transform: &Matrix) {
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let positions = match reader.read_positions() {
+            Some(positions) => positions,
+            None => return,
+        };
+        let material = primitive
+            .material()
+            .index()
+            .unwrap_or(self.mesh.materials.len() - 1);
+
+        let first = self.mesh.positions.len();
+        self.mesh
+            .positions
+            .extend(positions.map(|position| transform_point(transform, position)));
+        let count = self.mesh.positions.len() - first;
+
+        // The other buffers are indexed like the positions, so the ones of the primitives
+        // before that didn't have them get filled in to keep them in step
+        if let Some(normals) = reader.read_normals() {
+            self.mesh.normals.resize(first, Vec3::zeros());
+            self.mesh
+                .normals
+                .extend(normals.map(|normal| transform_normal(transform, normal)));
+        }
+        // glTF has the origin of images at the top
+        if let Some(uvs) = reader.read_tex_coords(0) {
+            self.mesh.uvs.resize(first, (0., 0.));
+            self.mesh
+                .uvs
+                .extend(uvs.into_f32().map(|[u, v]| (u, 1. - v)));
+        }
+        // Vertex colors multiply the base color, and are only used without a texture
+        if let (Material::Lambertian(base_color), Some(colors)) =
+            (&self.mesh.materials[material], reader.read_colors(0))
+        {
+            let base_color = *base_color;
+            self.mesh.colors.resize(first, Color::zeros());
+            self.mesh.colors.extend(
+                colors
+                    .into_rgb_f32()
+                    .map(|color| Color::from_array(&color) * base_color),
+            );
+        }
+        let has_normals = self.mesh.normals.len() == first + count;
+        let has_uvs = self.mesh.uvs.len() == first + count;
+        let has_colors = self.mesh.colors.len() == first + count;
+
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|index| index as usize).collect(),
+            None => (0..count).collect(),
+        };
+        let triangles: Vec<[usize; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            // Every other triangle of a strip is the other way around
+            Mode::TriangleStrip => (2..indices.len())
+                .map(|i| match i % 2 {
+                    0 => [indices[i - 2], indices[i - 1], indices[i]],
+                    _ => [indices[i - 1], indices[i - 2], indices[i]],
+                })
+                .collect(),
+            Mode::TriangleFan => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            // Points and lines can't be seen
+            _ => Vec::new(),
+        };
+
+        // A mirroring transform turns the triangles around, which glTF undoes
+        let mirrored = determinant(transform) < 0.;
+        let is_light = matches!(
+            self.mesh.materials[material],
+            Material::DiffuseLight(_) | Material::DiffuseLightTexture(_)
+        );
+        for [a, b, c] in triangles {
+            let triangle = if mirrored {
+                [first + a, first + c, first + b]
+            } else {
+                [first + a, first + b, first + c]
+            };
+            self.mesh.faces.push(MeshFace {
+                positions: triangle,
+                normals: if has_normals { Some(triangle) } else { None },
+                uvs: if has_uvs { Some(triangle) } else { None },
+                colors: if has_colors { Some(triangle) } else { None },
+                material,
+            });
+
+            if is_light
+                && triangle
+                    .iter()
+                    .all(|index| *index < self.mesh.positions.len())
+            {
+                let [a, b, c] = triangle;
+                let positions = &self.mesh.positions;
+                self.emissive.push(Triangle::new(
+                    positions[a],
+                    positions[b],
+                    positions[c],
+                    self.mesh.materials[material].clone(),
+                ));
+            }
+        }
+    }
+
+    /// Cameras look towards -z, with +y up
+    fn camera(&mut self, camera: &gltf::Camera, transform: &Matrix) {
+        let perspective = match camera.projection() {
+            Projection::Perspective(perspective) => perspective,
+            Projection::Orthographic(_) => return,
+        };
+        if self.camera.is_some() {
+            return;
+        }
+
+        let look_from = transform_point(transform, [0., 0., 0.]);
+        let forward = transform_vector(transform, Vec3::new(0., 0., -1.)).normalize();
+        self.camera = Some((
+            CameraSettings {
+                look_from,
+                look_at: look_from + forward,
+                up: transform_vector(transform, Vec3::new(0., 1., 0.)).normalize(),
+                fov: perspective.yfov().to_degrees(),
+                aperture: 0.,
+                focus_distance: None,
+                ..CameraSettings::default()
+            },
+            perspective.aspect_ratio(),
+        ));
+    }
+
+    fn light(&mut self, light: &gltf::khr_lights_punctual::Light, transform: &Matrix) {
+        let color = Color::from_array(&light.color()) * light.intensity();
+        let position = transform_point(transform, [0., 0., 0.]);
+
+        match light.kind() {
+            // A sphere with the same power as a point with this intensity, in candela
+            Kind::Point => self.spheres.push(Sphere::new(
+                position,
+                LIGHT_RADIUS,
+                Material::DiffuseLight(color / (PI * LIGHT_RADIUS * LIGHT_RADIUS)),
+            )),
+            // A square with the same intensity in front of it, facing -z
+            Kind::Spot { .. } => {
+                let right = transform_vector(transform, Vec3::new(1., 0., 0.)).normalize();
+                let up = transform_vector(transform, Vec3::new(0., 1., 0.)).normalize();
+                let forward = transform_vector(transform, Vec3::new(0., 0., -1.));
+                // Right handed, so the triangles face forward
+                let (right, up) = if right.cross(&up).dot(&forward) < 0. {
+                    (right, up)
+                } else {
+                    (-right, up)
+                };
+                let corner = |x: f32, y: f32| position + (right * x + up * y) * LIGHT_RADIUS;
+                let side = 2. * LIGHT_RADIUS;
+                let material = Material::DiffuseLight(color / (side * side));
+                self.squares.push(Triangle::new(
+                    corner(-1., -1.),
+                    corner(1., 1.),
+                    corner(-1., 1.),
+                    material.clone(),
+                ));
+                self.squares.push(Triangle::new(
+                    corner(-1., -1.),
+                    corner(1., -1.),
+                    corner(1., 1.),
+                    material,
+                ));
+            }
+            Kind::Directional => {
+                let direction = transform_vector(transform, Vec3::new(0., 0., -1.)).normalize();
+                self.suns.push((direction, color));
+            }
+        }
+    }
+
+    fn build(mut self) -> World {
+        let bounds = if self.mesh.positions.is_empty() {
+            AABB::surrounding_points(&[Point::from(-1.), Point::from(1.)])
+        } else {
+            AABB::surrounding_points(&self.mesh.positions)
+        };
+        let center = bounds.center();
+        let radius = ((bounds.max - bounds.min).length() / 2.).max(1e-3);
+
+        let mut builder = WorldBuilder::new();
+        match self.camera {
+            Some((camera, aspect_ratio)) => {
+                builder = builder.camera(camera);
+                if let Some(aspect_ratio) = aspect_ratio {
+                    builder = builder.aspect_ratio(aspect_ratio);
+                }
+            }
+            None => {
+                let fov = 40.;
+                let distance = radius / (fov / 2f32).to_radians().sin();
+                builder = builder.camera(CameraSettings {
+                    look_from: center + Vec3::new(13., 2., 3.).normalize() * distance,
+                    look_at: center,
+                    fov,
+                    ..CameraSettings::default()
+                });
+            }
+        }
+
+        let has_lights = !(self.spheres.is_empty()
+            && self.squares.is_empty()
+            && self.suns.is_empty()
+            && self.emissive.is_empty());
+        if !has_lights {
+            builder = builder.background_gradient(Color::new(0.5, 0.7, 1.), Color::ones());
+        }
+
+        // Far enough that it's the same direction from everywhere in the scene, and with the
+        // same illuminance as the directional light, in lux
+        let sun_angle = SUN_RADIUS.to_radians();
+        let sun_distance = 1000. * radius;
+        for (direction, illuminance) in self.suns {
+            self.spheres.push(Sphere::new(
+                center - direction * sun_distance,
+                sun_distance * sun_angle.sin(),
+                Material::DiffuseLight(illuminance / (PI * sun_angle.sin().powi(2))),
+            ));
+        }
+        for sphere in self.spheres {
+            builder = builder.add_light(sphere.clone()).add_object(sphere);
+        }
+        for triangle in self.squares {
+            builder = builder.add_light(triangle.clone()).add_object(triangle);
+        }
+        // Already in the mesh
+        for triangle in self.emissive {
+            builder = builder.add_light(triangle);
+        }
+
+        if !self.mesh.faces.is_empty() {
+            builder = builder.add_object(TriangleMesh::new(self.mesh));
+        }
+        builder.build()
+    }
+}
@@ -9,6 +9,8 @@ pub struct HitRecord {
     pub material: Material,
     pub u: f32,
     pub v: f32,
+    /// Object that was hit, from `World::number_objects`
+    pub object_id: u32,
 }
 
 impl HitRecord {
@@ -32,6 +34,7 @@ impl Default for HitRecord {
             material: Material::Lambertian(Color::zeros()),
             u: 0.0,
             v: 0.0,
+            object_id: 0,
         }
     }
 }
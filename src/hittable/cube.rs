@@ -4,84 +4,92 @@ use crate::{
     material::*,
 };
 
+/// Box aligned with the axes, from its smallest to its largest corner
 pub struct Cube {
     box_min: Point,
     box_max: Point,
     sides: HittableList,
+    id: u32,
 }
 
 impl Cube {
     pub fn new(box_min: Point, box_max: Point, material: Material) -> Self {
         let mut sides = HittableList::new();
 
-        sides.add(Box::new(Rect {
-            a0: box_min.x,
-            a1: box_max.x,
-            b0: box_min.y,
-            b1: box_max.y,
-            k: box_min.z,
-            material: material.clone(),
-            plane: Plane::XY,
-        }));
-        sides.add(Box::new(Rect {
-            a0: box_min.x,
-            a1: box_max.x,
-            b0: box_min.y,
-            b1: box_max.y,
-            k: box_max.z,
-            material: material.clone(),
-            plane: Plane::XY,
-        }));
+        sides.add(Box::new(Rect::new(
+            Plane::XY,
+            (box_min.x, box_max.x),
+            (box_min.y, box_max.y),
+            box_min.z,
+            material.clone(),
+        )));
+        sides.add(Box::new(Rect::new(
+            Plane::XY,
+            (box_min.x, box_max.x),
+            (box_min.y, box_max.y),
+            box_max.z,
+            material.clone(),
+        )));
 
-        sides.add(Box::new(Rect {
-            a0: box_min.x,
-            a1: box_max.x,
-            b0: box_min.z,
-            b1: box_max.z,
-            k: box_min.y,
-            material: material.clone(),
-            plane: Plane::XZ,
-        }));
-        sides.add(Box::new(Rect {
-            a0: box_min.x,
-            a1: box_max.x,
-            b0: box_min.z,
-            b1: box_max.z,
-            k: box_max.y,
-            material: material.clone(),
-            plane: Plane::XZ,
-        }));
+        sides.add(Box::new(Rect::new(
+            Plane::XZ,
+            (box_min.x, box_max.x),
+            (box_min.z, box_max.z),
+            box_min.y,
+            material.clone(),
+        )));
+        sides.add(Box::new(Rect::new(
+            Plane::XZ,
+            (box_min.x, box_max.x),
+            (box_min.z, box_max.z),
+            box_max.y,
+            material.clone(),
+        )));
 
-        sides.add(Box::new(Rect {
-            a0: box_min.y,
-            a1: box_max.y,
-            b0: box_min.z,
-            b1: box_max.z,
-            k: box_min.x,
-            material: material.clone(),
-            plane: Plane::YZ,
-        }));
-        sides.add(Box::new(Rect {
-            a0: box_min.y,
-            a1: box_max.y,
-            b0: box_min.z,
-            b1: box_max.z,
-            k: box_max.x,
+        sides.add(Box::new(Rect::new(
+            Plane::YZ,
+            (box_min.y, box_max.y),
+            (box_min.z, box_max.z),
+            box_min.x,
+            material.clone(),
+        )));
+        sides.add(Box::new(Rect::new(
+            Plane::YZ,
+            (box_min.y, box_max.y),
+            (box_min.z, box_max.z),
+            box_max.x,
             material,
-            plane: Plane::YZ,
-        }));
+        )));
 
         Self {
             box_min,
             box_max,
             sides,
+            id: 0,
         }
     }
 }
 
 impl Hittable for Cube {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
-        self.sides.hit(ray, taemin, t_max, hit_record)
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        if !self.sides.hit(ray, taemin, t_max, hit_record, sampler) {
+            return false;
+        }
+
+        // The sides are one object
+        hit_record.object_id = self.id;
+        true
+    }
+
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
@@ -91,11 +99,11 @@ impl Hittable for Cube {
         })
     }
 
-    fn pdf_value(&self, point: &Point, vector: &Vec3) -> f32 {
-        self.sides.pdf_value(point, vector)
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
+        self.sides.pdf_value(point, vector, sampler)
     }
 
-    fn random(&self, point: &Point) -> Vec3 {
-        self.sides.random(point)
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        self.sides.random(point, sampler)
     }
 }
@@ -1,95 +1,222 @@
 use crate::{hittable::*, material::*, onb::*};
 use std::f32::consts::PI;
 
+/// Cylinder between the centers of its two ends, which are closed by disks unless `caps`
+/// is off
 #[derive(Clone)]
 pub struct Cylinder {
-    pub center: Point,
+    pub start: Point,
+    pub end: Point,
     pub radius: f32,
-    // pub axis: Vec3,
-    // TODO Need to reimplement axis. Currently only works for vertical axis
+    /// Without the caps it's a tube, open at both ends
+    pub caps: bool,
     pub material: Material,
+    /// Tells it apart in the `object_id` AOV, set by `World::number_objects`
+    pub id: u32,
 }
 
-impl Hittable for Cylinder {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
-        let axis = Vec3::new(0., 1., 0.);
-        let oc = ray.origin - self.center;
-        let card = axis.dot(&ray.direction);
-        let caoc = axis.dot(&ray.origin);
-        let a = 1.0 - card * card;
-        let b = oc.dot(&ray.direction) - caoc * card;
-        let c = oc.length_squared() - caoc * caoc - self.radius * self.radius;
-        let h = b * b - a * c;
-
-        if h < 0.0 {
-            return false;
-        }
+/// Where the line of a ray goes through the surface of a cylinder
+#[derive(Copy, Clone)]
+struct Crossing {
+    t: f32,
+    outward_normal: Vec3,
+    u: f32,
+    v: f32,
+}
 
-        let h = h.sqrt();
+impl Cylinder {
+    pub fn new(start: Point, end: Point, radius: f32, material: Material) -> Self {
+        Self {
+            start,
+            end,
+            radius,
+            caps: true,
+            material,
+            id: 0,
+        }
+    }
 
-        let temp = (-b - h) / a;
-        if taemin < temp && temp < t_max {
-            hit_record.t = temp;
-            hit_record.point = ray.at(temp);
+    fn height(&self) -> f32 {
+        (self.end - self.start).length()
+    }
 
-            let outward_normal = {
-                let new_center = Point::new(self.center.x, hit_record.point.y, self.center.z);
-                (hit_record.point - new_center).normalize()
-            };
+    fn area(&self) -> f32 {
+        let caps = if self.caps {
+            2. * PI * self.radius * self.radius
+        } else {
+            0.
+        };
+        2. * PI * self.radius * self.height() + caps
+    }
 
-            hit_record.set_face_normal(&ray, &outward_normal);
-            hit_record.material = self.material.clone();
+    /// Every place the line of the ray goes through the cylinder, at any t. The side is
+    /// found in the plane across the axis, with the ray relative to the center, so long
+    /// cylinders far from the origin are still precise.
+    fn crossings(&self, ray: &Ray) -> [Option<Crossing>; 4] {
+        let mut crossings = [None; 4];
+
+        let frame = ONB::build_from_w(self.end - self.start);
+        let half_height = self.height() / 2.;
+        let center = self.start + (self.end - self.start) / 2.;
+        let local = |vector: Vec3| {
+            Vec3::new(
+                vector.dot(&frame.u),
+                vector.dot(&frame.v),
+                vector.dot(&frame.w),
+            )
+        };
+        let origin = local(ray.origin - center);
+        let direction = local(ray.direction);
+
+        let a = direction.x * direction.x + direction.y * direction.y;
+        let b = origin.x * direction.x + origin.y * direction.y;
+        let c = origin.x * origin.x + origin.y * origin.y - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        // A ray along the axis can only go through the caps
+        if a > 0. && discriminant >= 0. {
+            let root = discriminant.sqrt();
+            for (crossing, t) in crossings
+                .iter_mut()
+                .zip(&[(-b - root) / a, (-b + root) / a])
+            {
+                let point = origin + *t * direction;
+                if point.z.abs() > half_height {
+                    continue;
+                }
+                *crossing = Some(Crossing {
+                    t: *t,
+                    outward_normal: (frame.u * point.x + frame.v * point.y) / self.radius,
+                    u: (point.y.atan2(point.x) + PI) / (2. * PI),
+                    v: (point.z + half_height) / (2. * half_height),
+                });
+            }
+        }
 
-            return true;
+        if self.caps && direction.z != 0. {
+            for (crossing, side) in crossings[2..].iter_mut().zip(&[-1., 1.]) {
+                let t = (side * half_height - origin.z) / direction.z;
+                let point = origin + t * direction;
+                if point.x * point.x + point.y * point.y > self.radius * self.radius {
+                    continue;
+                }
+                *crossing = Some(Crossing {
+                    t,
+                    outward_normal: frame.w * *side,
+                    u: (point.x / self.radius + 1.) / 2.,
+                    v: (point.y / self.radius + 1.) / 2.,
+                });
+            }
         }
 
-        let temp = (-b + h) / a;
-        if taemin < temp && temp < t_max {
-            hit_record.t = temp;
-            hit_record.point = ray.at(temp);
+        crossings
+    }
+}
 
-            let outward_normal = {
-                let new_center = Point::new(self.center.x, hit_record.point.y, self.center.z);
-                (hit_record.point - new_center).normalize()
-            };
+impl Hittable for Cylinder {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        count_intersection_test();
+
+        let closest = self
+            .crossings(ray)
+            .iter()
+            .flatten()
+            .filter(|crossing| taemin < crossing.t && crossing.t < t_max)
+            .min_by(|a, b| a.t.partial_cmp(&b.t).unwrap())
+            .copied();
+        let crossing = match closest {
+            Some(crossing) => crossing,
+            None => return false,
+        };
 
-            hit_record.set_face_normal(&ray, &outward_normal);
-            hit_record.material = self.material.clone();
+        hit_record.t = crossing.t;
+        hit_record.point = ray.at(crossing.t);
+        hit_record.set_face_normal(ray, &crossing.outward_normal);
+        hit_record.u = crossing.u.clamp(0., 1.);
+        hit_record.v = crossing.v.clamp(0., 1.);
+        hit_record.material = self.material.clone();
+        hit_record.object_id = self.id;
 
-            return true;
-        }
+        true
+    }
 
-        false
+    /// The rims of the ends reach out `radius` times the sine of the angle between the
+    /// axis and each direction
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
-        None
-    }
+        let axis = (self.end - self.start).normalize();
+        let extent = |component: f32| self.radius * (1. - component * component).max(0.).sqrt();
+        let extent = Vec3::new(extent(axis.x), extent(axis.y), extent(axis.z));
 
-    fn pdf_value(&self, point: &Point, vector: &Vec3) -> f32 {
-        // TODO Fix this
+        let start = AABB {
+            min: self.start - extent,
+            max: self.start + extent,
+        };
+        let end = AABB {
+            min: self.end - extent,
+            max: self.end + extent,
+        };
+        Some(start.surrounding_box(end))
+    }
 
-        let mut hit_record = HitRecord::default();
+    /// `random` picks points evenly over the surface, so every point the direction goes
+    /// through could have been picked
+    fn pdf_value(&self, point: &Point, vector: &Vec3, _sampler: &mut Sampler) -> f32 {
         let ray = Ray {
             origin: *point,
             direction: *vector,
             time: 0.,
         };
-        if !self.hit(&ray, 0.001, f32::INFINITY, &mut hit_record) {
-            return 0.;
-        }
-
-        let cos_theta_max =
-            (1. - self.radius * self.radius / (self.center - *point).length_squared()).sqrt();
-        let solid_angle = 2. * PI * (1. - cos_theta_max);
-
-        1. / solid_angle
+        let area = self.area();
+
+        self.crossings(&ray)
+            .iter()
+            .flatten()
+            .filter(|crossing| crossing.t > 0.001)
+            .map(|crossing| {
+                let distance_squared = crossing.t.powi(2) * vector.length_squared();
+                let cosine = vector.dot(&crossing.outward_normal).abs() / vector.length();
+                if cosine != 0. {
+                    distance_squared / (cosine * area)
+                } else {
+                    0.
+                }
+            })
+            .sum()
     }
 
-    fn random(&self, point: &Point) -> Vec3 {
-        let direction = self.center - *point;
-        let distance_squared = direction.length_squared();
-        let uvw = ONB::build_from_w(direction);
-        uvw.local(Vec3::random_to_sphere(self.radius, distance_squared))
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        let frame = ONB::build_from_w(self.end - self.start);
+        let side_area = 2. * PI * self.radius * self.height();
+        let part = sampler.get_1d() * self.area();
+        let (a, b) = sampler.get_2d();
+
+        let random_point = if part < side_area {
+            let angle = 2. * PI * a;
+            self.start
+                + (self.end - self.start) * b
+                + self.radius * (angle.cos() * frame.u + angle.sin() * frame.v)
+        } else {
+            // One of the caps, with the same chance as their share of the area
+            let center = if part - side_area < PI * self.radius * self.radius {
+                self.start
+            } else {
+                self.end
+            };
+            let angle = 2. * PI * a;
+            let distance = self.radius * b.sqrt();
+            center + distance * (angle.cos() * frame.u + angle.sin() * frame.v)
+        };
+
+        random_point - *point
     }
 }
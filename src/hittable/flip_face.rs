@@ -1,12 +1,26 @@
 use crate::hittable::*;
 
+/// Turns an object inside out, so a light shines to the other side
 pub struct FlipFace {
     pub hittable: Box<dyn Hittable>,
 }
 
+impl FlipFace {
+    pub fn new(hittable: Box<dyn Hittable>) -> Self {
+        Self { hittable }
+    }
+}
+
 impl Hittable for FlipFace {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
-        if !self.hittable.hit(ray, taemin, t_max, hit_record) {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        if !self.hittable.hit(ray, taemin, t_max, hit_record, sampler) {
             return false;
         }
 
@@ -15,7 +29,19 @@ impl Hittable for FlipFace {
         true
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        self.hittable.number_objects(next);
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         self.hittable.bounding_box(t0, t1)
     }
+
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
+        self.hittable.pdf_value(point, vector, sampler)
+    }
+
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        self.hittable.random(point, sampler)
+    }
 }
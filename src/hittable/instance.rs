@@ -0,0 +1,79 @@
+use crate::{
+    hittable::{transform::*, *},
+    material::*,
+    matrix::*,
+};
+use std::sync::Arc;
+
+/// Copy of a prototype object, placed with its own transform and optionally made of
+/// another material. Instances only keep a reference to the prototype, so a thousand trees
+/// take the memory of one mesh, and share its BVH.
+pub struct Instance {
+    transform: Transform,
+    /// Replaces the materials of the prototype
+    pub material: Option<Material>,
+    /// Its own, so instances of the same prototype can be told apart
+    id: u32,
+}
+
+impl Instance {
+    /// Panics if the matrix can't be inverted, like with `Transform`
+    pub fn new(prototype: &Arc<dyn Hittable>, matrix: Matrix) -> Self {
+        Self {
+            transform: Transform::shared(prototype.clone(), matrix),
+            material: None,
+            id: 0,
+        }
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn prototype(&self) -> &Arc<dyn Hittable> {
+        &self.transform.hittable
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        self.transform.matrix()
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        if !self.transform.hit(ray, taemin, t_max, hit_record, sampler) {
+            return false;
+        }
+
+        if let Some(material) = &self.material {
+            hit_record.material = material.clone();
+        }
+        hit_record.object_id = self.id;
+
+        true
+    }
+
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.transform.bounding_box(t0, t1)
+    }
+
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
+        self.transform.pdf_value(point, vector, sampler)
+    }
+
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        self.transform.random(point, sampler)
+    }
+}
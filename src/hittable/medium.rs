@@ -1,10 +1,13 @@
 use crate::{hittable::*, material::*};
 use rand::Rng;
 
+/// Smoke or fog filling the inside of an object, which scatters light in any direction
 pub struct ConstantMedium {
     pub hittable: Box<dyn Hittable>,
     pub phase_function: Material,
     pub neg_inv_density: f32,
+    /// Tells it apart in the `object_id` AOV, set by `World::number_objects`
+    pub id: u32,
 }
 
 impl ConstantMedium {
@@ -13,24 +16,32 @@ impl ConstantMedium {
             hittable,
             neg_inv_density: (-1.0 / density),
             phase_function: Material::Isotropic(color),
+            id: 0,
         }
     }
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
         let mut rec1 = HitRecord::default();
         let mut rec2 = HitRecord::default();
 
         if !self
             .hittable
-            .hit(ray, -f32::INFINITY, f32::INFINITY, &mut rec1)
+            .hit(ray, -f32::INFINITY, f32::INFINITY, &mut rec1, sampler)
         {
             return false;
         }
         if !self
             .hittable
-            .hit(ray, rec1.t + 0.0001, f32::INFINITY, &mut rec2)
+            .hit(ray, rec1.t + 0.0001, f32::INFINITY, &mut rec2, sampler)
         {
             return false;
         }
@@ -50,7 +61,7 @@ impl Hittable for ConstantMedium {
 
         let ray_length = ray.direction.length();
         let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-        let hit_distance = self.neg_inv_density * rand::thread_rng().gen::<f32>().ln();
+        let hit_distance = self.neg_inv_density * sampler.gen::<f32>().ln();
 
         if hit_distance > distance_inside_boundary {
             return false;
@@ -62,10 +73,15 @@ impl Hittable for ConstantMedium {
         hit_record.normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
         hit_record.front_face = true; // also arbitrary
         hit_record.material = self.phase_function.clone();
+        hit_record.object_id = self.id;
 
         true
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         self.hittable.bounding_box(t0, t1)
     }
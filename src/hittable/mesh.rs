@@ -0,0 +1,232 @@
+use crate::{bvh::*, hittable::*, material::*};
+use std::sync::Arc;
+
+/// Vertices, normals, texture coordinates and colors of a mesh, and the triangles that index
+/// them. Every triangle has a material from `materials`, so parts of the mesh can look
+/// different.
+#[derive(Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Point>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub colors: Vec<Color>,
+    pub faces: Vec<MeshFace>,
+    pub materials: Vec<Material>,
+    /// Problems the loader got past, like an MTL file it couldn't read
+    pub warnings: Vec<String>,
+}
+
+/// Triangle of a `Mesh`, with the indices of its 3 corners in the buffers. Without normals
+/// it's flat, and without texture coordinates `u` and `v` are the barycentric coordinates.
+/// With colors, they replace the albedo of a lambertian material.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub colors: Option<[usize; 3]>,
+    pub material: usize,
+}
+
+impl Mesh {
+    /// Checks that every index of the faces is in its buffer
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |indices: Option<[usize; 3]>, length: usize, buffer: &str| match indices {
+            Some(indices) if indices.iter().any(|index| *index >= length) => Err(format!(
+                "a face uses {} {:?}, but there are {}",
+                buffer, indices, length
+            )),
+            _ => Ok(()),
+        };
+
+        for face in &self.faces {
+            check(Some(face.positions), self.positions.len(), "positions")?;
+            check(face.normals, self.normals.len(), "normals")?;
+            check(face.uvs, self.uvs.len(), "texture coordinates")?;
+            check(face.colors, self.colors.len(), "colors")?;
+            if face.material >= self.materials.len() {
+                return Err(format!(
+                    "a face uses material {}, but there are {}",
+                    face.material,
+                    self.materials.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn corners(&self, face: &MeshFace) -> [Point; 3] {
+        let [a, b, c] = face.positions;
+        [self.positions[a], self.positions[b], self.positions[c]]
+    }
+}
+
+/// Mesh of triangles, with a BVH over them so it can have a lot
+pub struct TriangleMesh {
+    mesh: Arc<Mesh>,
+    bvh: Option<BVHNode>,
+    id: u32,
+}
+
+impl TriangleMesh {
+    /// Panics if a face has an index out of its buffer, `Mesh::validate` checks them
+    pub fn new(mesh: Mesh) -> Self {
+        if let Err(why) = mesh.validate() {
+            panic!("Invalid mesh: {}", why);
+        }
+
+        let mesh = Arc::new(mesh);
+        let triangles: Vec<Box<dyn Hittable>> = (0..mesh.faces.len())
+            .map(|face| {
+                Box::new(MeshTriangle {
+                    mesh: mesh.clone(),
+                    face,
+                }) as Box<dyn Hittable>
+            })
+            .collect();
+        let bvh = if triangles.is_empty() {
+            None
+        } else {
+            Some(BVHNode::new(triangles, 0., 0.))
+        };
+
+        Self { mesh, bvh, id: 0 }
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        let bvh = match &self.bvh {
+            Some(bvh) => bvh,
+            None => return false,
+        };
+        if !bvh.hit(ray, taemin, t_max, hit_record, sampler) {
+            return false;
+        }
+
+        // The triangles are one object
+        hit_record.object_id = self.id;
+        true
+    }
+
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.bvh.as_ref()?.bounding_box(t0, t1)
+    }
+}
+
+/// Triangle of a mesh in its BVH
+struct MeshTriangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        count_intersection_test();
+
+        let face = &self.mesh.faces[self.face];
+        let [v0, v1, v2] = self.mesh.corners(face);
+        let v1v0 = v1 - v0;
+        let v2v0 = v2 - v0;
+        let rov0 = ray.origin - v0;
+
+        let normal = v1v0.cross(&v2v0);
+        let q = rov0.cross(&ray.direction);
+        let d_inv = ray.direction.dot(&normal);
+        // Parallel to the triangle
+        if d_inv == 0.0 {
+            return false;
+        }
+
+        let u = (-q).dot(&v2v0) / d_inv;
+        let v = q.dot(&v1v0) / d_inv;
+        let t = (-normal).dot(&rov0) / d_inv;
+
+        if !(0.0..=1.0).contains(&u) || v < 0.0 || (u + v) > 1.0 {
+            return false;
+        }
+        if taemin > t || t > t_max {
+            return false;
+        }
+        let w = 1. - u - v;
+
+        hit_record.t = t;
+        hit_record.point = ray.at(t);
+        hit_record.set_face_normal(ray, &normal.normalize());
+
+        if let Some([n0, n1, n2]) = face.normals {
+            let normals = &self.mesh.normals;
+            let mut shading_normal =
+                (normals[n0] * w + normals[n1] * u + normals[n2] * v).normalize();
+            // On the side of the triangle the ray hits
+            if shading_normal.dot(&hit_record.normal) < 0. {
+                shading_normal = -shading_normal;
+            }
+            // Near the silhouette it can still point away from the ray, where the flat
+            // normal is kept instead
+            if shading_normal.dot(&ray.direction) < 0. {
+                hit_record.normal = shading_normal;
+            }
+        }
+
+        let (hit_u, hit_v) = match face.uvs {
+            Some([t0, t1, t2]) => {
+                let uvs = &self.mesh.uvs;
+                (
+                    uvs[t0].0 * w + uvs[t1].0 * u + uvs[t2].0 * v,
+                    uvs[t0].1 * w + uvs[t1].1 * u + uvs[t2].1 * v,
+                )
+            }
+            None => (u, v),
+        };
+        hit_record.u = wrap(hit_u);
+        hit_record.v = wrap(hit_v);
+        hit_record.material = match (face.colors, &self.mesh.materials[face.material]) {
+            (Some([c0, c1, c2]), Material::Lambertian(_))
+            | (Some([c0, c1, c2]), Material::LambertianTexture(_)) => {
+                let colors = &self.mesh.colors;
+                Material::Lambertian(colors[c0] * w + colors[c1] * u + colors[c2] * v)
+            }
+            (_, material) => material.clone(),
+        };
+
+        true
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(AABB::surrounding_points(
+            &self.mesh.corners(&self.mesh.faces[self.face]),
+        ))
+    }
+}
+
+/// Texture coordinates out of [0, 1] repeat the texture
+fn wrap(coordinate: f32) -> f32 {
+    if (0. ..=1.).contains(&coordinate) {
+        coordinate
+    } else {
+        coordinate.rem_euclid(1.)
+    }
+}
@@ -1,26 +1,47 @@
-use crate::{aabb::*, hit_record::*, ray::*, vec3::*};
+use crate::{aabb::*, hit_record::*, ray::*, sampler::*, stats::*, vec3::*};
 
 pub mod cube;
 pub mod cylinder;
 pub mod flip_face;
+pub mod instance;
 pub mod medium;
+pub mod mesh;
 pub mod moving_sphere;
 pub mod pyramid;
 pub mod rectangle;
-pub mod rotate;
 pub mod sdf;
 pub mod sphere;
-pub mod translate;
+pub mod transform;
 pub mod triangle;
 
+/// Hands out the next id while `World::number_objects` goes through a world
+pub(crate) fn take_object_id(next: &mut u32) -> u32 {
+    let id = *next;
+    *next += 1;
+    id
+}
+
+/// Anything a ray can hit. Lights also have to implement `pdf_value` and `random`,
+/// so the renderer can send rays towards them.
 pub trait Hittable: Sync + Send {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, hit_record: &mut HitRecord) -> bool;
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool;
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB>;
 
-    fn pdf_value(&self, _point: &Point, _vector: &Vec3) -> f32 {
+    fn pdf_value(&self, _point: &Point, _vector: &Vec3, _sampler: &mut Sampler) -> f32 {
         0.
     }
-    fn random(&self, _point: &Point) -> Vec3 {
+    fn random(&self, _point: &Point, _sampler: &mut Sampler) -> Vec3 {
         Vec3::new(1., 0., 0.)
     }
+
+    /// Gives the objects inside ids for the `object_id` AOV, from `next` on. Objects that
+    /// are made of others get one id for all of them.
+    fn number_objects(&mut self, _next: &mut u32) {}
 }
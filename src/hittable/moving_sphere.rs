@@ -1,5 +1,6 @@
 use crate::{hittable::*, material::*};
 
+/// Sphere that moves in a straight line, from `center0` at `time0` to `center1` at `time1`
 pub struct MovingSphere {
     pub center0: Point,
     pub center1: Point,
@@ -7,10 +8,42 @@ pub struct MovingSphere {
     pub time1: f32,
     pub radius: f32,
     pub material: Material,
+    /// Tells it apart in the `object_id` AOV, set by `World::number_objects`
+    pub id: u32,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point,
+        center1: Point,
+        time0: f32,
+        time1: f32,
+        radius: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+            id: 0,
+        }
+    }
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        count_intersection_test();
+
         let center = self.center(ray.time);
 
         let oc = ray.origin - center;
@@ -27,8 +60,9 @@ impl Hittable for MovingSphere {
                 hit_record.t = temp;
                 hit_record.point = ray.at(temp);
                 let outward_normal = (hit_record.point - center) / self.radius;
-                hit_record.set_face_normal(&ray, &outward_normal);
+                hit_record.set_face_normal(ray, &outward_normal);
                 hit_record.material = self.material.clone();
+                hit_record.object_id = self.id;
 
                 return true;
             }
@@ -38,8 +72,9 @@ impl Hittable for MovingSphere {
                 hit_record.t = temp;
                 hit_record.point = ray.at(temp);
                 let outward_normal = (hit_record.point - center) / self.radius;
-                hit_record.set_face_normal(&ray, &outward_normal);
+                hit_record.set_face_normal(ray, &outward_normal);
                 hit_record.material = self.material.clone();
+                hit_record.object_id = self.id;
 
                 return true;
             }
@@ -48,6 +83,10 @@ impl Hittable for MovingSphere {
         false
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         let box0 = AABB {
             min: self.center(t0) - Vec3::new(self.radius, self.radius, self.radius),
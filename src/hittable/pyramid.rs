@@ -4,6 +4,7 @@ use crate::{
     material::*,
 };
 
+/// Pyramid from its top and the 4 corners of its base, in order around it
 pub struct Pyramid {
     top: Point,
     base0: Point,
@@ -11,6 +12,7 @@ pub struct Pyramid {
     base2: Point,
     base3: Point,
     sides: HittableList,
+    id: u32,
 }
 
 impl Pyramid {
@@ -25,43 +27,23 @@ impl Pyramid {
         let mut sides = HittableList::new();
 
         // Base
-        sides.add(Box::new(Triangle {
-            v0: base0,
-            v1: base1,
-            v2: base2,
-            material: material.clone(),
-        }));
-        sides.add(Box::new(Triangle {
-            v0: base0,
-            v1: base2,
-            v2: base3,
-            material: material.clone(),
-        }));
+        sides.add(Box::new(Triangle::new(
+            base0,
+            base1,
+            base2,
+            material.clone(),
+        )));
+        sides.add(Box::new(Triangle::new(
+            base0,
+            base2,
+            base3,
+            material.clone(),
+        )));
 
-        sides.add(Box::new(Triangle {
-            v0: top,
-            v1: base0,
-            v2: base1,
-            material: material.clone(),
-        }));
-        sides.add(Box::new(Triangle {
-            v0: top,
-            v1: base1,
-            v2: base2,
-            material: material.clone(),
-        }));
-        sides.add(Box::new(Triangle {
-            v0: top,
-            v1: base2,
-            v2: base3,
-            material: material.clone(),
-        }));
-        sides.add(Box::new(Triangle {
-            v0: top,
-            v1: base3,
-            v2: base0,
-            material,
-        }));
+        sides.add(Box::new(Triangle::new(top, base0, base1, material.clone())));
+        sides.add(Box::new(Triangle::new(top, base1, base2, material.clone())));
+        sides.add(Box::new(Triangle::new(top, base2, base3, material.clone())));
+        sides.add(Box::new(Triangle::new(top, base3, base0, material)));
 
         Self {
             top,
@@ -70,13 +52,31 @@ impl Pyramid {
             base2,
             base3,
             sides,
+            id: 0,
         }
     }
 }
 
 impl Hittable for Pyramid {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
-        self.sides.hit(ray, taemin, t_max, hit_record)
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        if !self.sides.hit(ray, taemin, t_max, hit_record, sampler) {
+            return false;
+        }
+
+        // The sides are one object
+        hit_record.object_id = self.id;
+        true
+    }
+
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
@@ -124,11 +124,11 @@ impl Hittable for Pyramid {
         })
     }
 
-    fn pdf_value(&self, point: &Point, vector: &Vec3) -> f32 {
-        self.sides.pdf_value(point, vector)
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
+        self.sides.pdf_value(point, vector, sampler)
     }
 
-    fn random(&self, point: &Point) -> Vec3 {
-        self.sides.random(point)
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        self.sides.random(point, sampler)
     }
 }
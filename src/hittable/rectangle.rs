@@ -1,7 +1,7 @@
 use crate::{hittable::*, material::*};
-use rand::Rng;
 
-#[derive(Clone)]
+/// Plane a `Rect` is parallel to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Plane {
     XY,
     XZ,
@@ -19,6 +19,8 @@ impl Plane {
     }
 }
 
+/// Rectangle parallel to a plane, at `k` along the axis the plane doesn't have. `a` and `b`
+/// are the first and second axes of the plane, so x and z in `Plane::XZ`.
 pub struct Rect {
     pub a0: f32,
     pub a1: f32,
@@ -27,10 +29,42 @@ pub struct Rect {
     pub k: f32,
     pub material: Material,
     pub plane: Plane,
+    /// Tells it apart in the `object_id` AOV, set by `World::number_objects`
+    pub id: u32,
+}
+
+impl Rect {
+    pub fn new(
+        plane: Plane,
+        (a0, a1): (f32, f32),
+        (b0, b1): (f32, f32),
+        k: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            a0,
+            a1,
+            b0,
+            b1,
+            k,
+            material,
+            plane,
+            id: 0,
+        }
+    }
 }
 
 impl Hittable for Rect {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        count_intersection_test();
+
         let (k_index, a_index, b_index) = self.plane.get_index();
 
         let t = (self.k - ray.origin[k_index]) / ray.direction[k_index];
@@ -55,26 +89,39 @@ impl Hittable for Rect {
         hit_record.set_face_normal(ray, &normal);
 
         hit_record.material = self.material.clone();
+        hit_record.object_id = self.id;
         hit_record.point = ray.at(t);
 
         true
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
-        Some(AABB {
-            min: Point::new(self.a0, self.b0, self.k - 0.0001),
-            max: Point::new(self.a1, self.b1, self.k + 0.0001),
-        })
+        // Padded along the axis the rect doesn't have, so the box isn't flat
+        let (k_index, a_index, b_index) = self.plane.get_index();
+        let mut min = Point::zeros();
+        let mut max = Point::zeros();
+        min[a_index] = self.a0;
+        max[a_index] = self.a1;
+        min[b_index] = self.b0;
+        max[b_index] = self.b1;
+        min[k_index] = self.k - 0.0001;
+        max[k_index] = self.k + 0.0001;
+
+        Some(AABB { min, max })
     }
 
-    fn pdf_value(&self, point: &Point, vector: &Vec3) -> f32 {
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
         let mut hit_record = HitRecord::default();
         let ray = Ray {
             origin: *point,
             direction: *vector,
             time: 0.,
         };
-        if !self.hit(&ray, 0.001, f32::INFINITY, &mut hit_record) {
+        if !self.hit(&ray, 0.001, f32::INFINITY, &mut hit_record, sampler) {
             return 0.;
         }
 
@@ -88,13 +135,13 @@ impl Hittable for Rect {
         }
     }
 
-    fn random(&self, point: &Point) -> Vec3 {
-        let mut rng = rand::thread_rng();
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        let (a, b) = sampler.get_2d();
         let mut random_point = Vec3::zeros();
 
         let (k_axis, a_axis, b_axis) = self.plane.get_index();
-        random_point[a_axis] = rng.gen_range(&self.a0, &self.a1);
-        random_point[b_axis] = rng.gen_range(&self.b0, &self.b1);
+        random_point[a_axis] = self.a0 + a * (self.a1 - self.a0);
+        random_point[b_axis] = self.b0 + b * (self.b1 - self.b0);
         random_point[k_axis] = self.k;
 
         random_point - *point
@@ -1,12 +1,23 @@
 use crate::{hittable::*, material::*};
 use std::f32::consts::*;
 
+/// Object with the shape of a signed distance field, found by sphere tracing it
 pub struct TracedSDF {
     pub sdf: Box<dyn SDF>,
     pub material: Material,
+    /// Tells it apart in the `object_id` AOV, set by `World::number_objects`
+    pub id: u32,
 }
 
 impl TracedSDF {
+    pub fn new(sdf: Box<dyn SDF>, material: Material) -> Self {
+        Self {
+            sdf,
+            material,
+            id: 0,
+        }
+    }
+
     pub fn normal(&self, point: Vec3) -> Vec3 {
         let h = 0.0001;
 
@@ -24,7 +35,16 @@ impl TracedSDF {
 }
 
 impl Hittable for TracedSDF {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        count_intersection_test();
+
         // Start from a t in the bounding box, and not from taemin
         let bounding_box_tmin = self
             .bounding_box(0., 0.)
@@ -37,6 +57,10 @@ impl Hittable for TracedSDF {
         };
 
         for _ in 0..2000 {
+            if t > t_max {
+                break;
+            }
+
             let point = ray.at(t);
             let distance = self.sdf.dist(point);
 
@@ -47,13 +71,14 @@ impl Hittable for TracedSDF {
                     normal,
                     t,
                     material: self.material.clone(),
+                    object_id: self.id,
                     ..*hit_record
                 };
-                hit_record.set_face_normal(&ray, &normal);
+                hit_record.set_face_normal(ray, &normal);
 
                 return true;
             }
-            if distance > 10000.0 || t > t_max {
+            if distance > 10000.0 {
                 break;
             }
 
@@ -63,11 +88,16 @@ impl Hittable for TracedSDF {
         false
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         self.sdf.bounding_box(t0, t1)
     }
 }
 
+/// Shape given by the distance from any point to its surface, negative inside it
 pub trait SDF: Send + Sync {
     fn dist(&self, position: Vec3) -> f32;
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB>;
@@ -137,17 +167,15 @@ impl SDF for SDFDonut {
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        // The ring goes around the z axis
+        let extent = Vec3::new(
+            self.radius0 + self.radius1,
+            self.radius0 + self.radius1,
+            self.radius1,
+        );
         Some(AABB {
-            min: Point::new(
-                -self.radius0 - self.radius1,
-                -self.radius1,
-                -self.radius0 - self.radius1,
-            ),
-            max: Point::new(
-                self.radius0 + self.radius1,
-                self.radius1,
-                self.radius0 + self.radius1,
-            ),
+            min: self.center - extent,
+            max: self.center + extent,
         })
     }
 }
@@ -214,7 +242,7 @@ impl SDF for SDFMandelBulb {
                 break;
             }
         }
-        return 0.25 * m.ln() * m.sqrt() / dz;
+        0.25 * m.ln() * m.sqrt() / dz
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
@@ -457,7 +485,14 @@ pub fn cross(center: Point, dimension: f32) -> Box<dyn SDF> {
 }
 
 pub fn menger_sponge(iterations: usize) -> Box<TracedSDF> {
-    let size = 33.;
+    Box::new(TracedSDF::new(
+        menger_sponge_sdf(iterations, 33.),
+        Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
+    ))
+}
+
+/// Cube of side `2 * size` with the crosses of every iteration carved out of it
+pub fn menger_sponge_sdf(iterations: usize, size: f32) -> Box<dyn SDF> {
     let a = Box::new(SDFCube {
         center: Point::zeros(),
         dimensions: Vec3::from(size),
@@ -474,10 +509,7 @@ pub fn menger_sponge(iterations: usize) -> Box<TracedSDF> {
         });
     }
 
-    box TracedSDF {
-        sdf: Box::new(SDFSubstraction { a, b }),
-        material: Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
-    }
+    Box::new(SDFSubstraction { a, b })
 }
 
 #[allow(dead_code)]
@@ -6,10 +6,32 @@ pub struct Sphere {
     pub center: Point,
     pub radius: f32,
     pub material: Material,
+    /// Tells it apart in the `object_id` AOV, set by `World::number_objects`
+    pub id: u32,
+}
+
+impl Sphere {
+    pub fn new(center: Point, radius: f32, material: Material) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+            id: 0,
+        }
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        count_intersection_test();
+
         let oc = ray.origin - self.center;
         let a = ray.direction.length_squared();
         let half_b = oc.dot(&ray.direction);
@@ -24,8 +46,9 @@ impl Hittable for Sphere {
                 hit_record.t = temp;
                 hit_record.point = ray.at(temp);
                 let outward_normal = (hit_record.point - self.center) / self.radius;
-                hit_record.set_face_normal(&ray, &outward_normal);
+                hit_record.set_face_normal(ray, &outward_normal);
                 hit_record.material = self.material.clone();
+                hit_record.object_id = self.id;
                 let (u, v) = get_sphere_uv((hit_record.point - self.center) / self.radius);
                 hit_record.u = u;
                 hit_record.v = v;
@@ -38,8 +61,9 @@ impl Hittable for Sphere {
                 hit_record.t = temp;
                 hit_record.point = ray.at(temp);
                 let outward_normal = (hit_record.point - self.center) / self.radius;
-                hit_record.set_face_normal(&ray, &outward_normal);
+                hit_record.set_face_normal(ray, &outward_normal);
                 hit_record.material = self.material.clone();
+                hit_record.object_id = self.id;
                 let (u, v) = get_sphere_uv((hit_record.point - self.center) / self.radius);
                 hit_record.u = u;
                 hit_record.v = v;
@@ -51,6 +75,10 @@ impl Hittable for Sphere {
         false
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         Some(AABB {
             min: self.center - Vec3::new(self.radius, self.radius, self.radius),
@@ -58,14 +86,14 @@ impl Hittable for Sphere {
         })
     }
 
-    fn pdf_value(&self, point: &Point, vector: &Vec3) -> f32 {
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
         let mut hit_record = HitRecord::default();
         let ray = Ray {
             origin: *point,
             direction: *vector,
             time: 0.,
         };
-        if !self.hit(&ray, 0.001, f32::INFINITY, &mut hit_record) {
+        if !self.hit(&ray, 0.001, f32::INFINITY, &mut hit_record, sampler) {
             return 0.;
         }
 
@@ -75,11 +103,15 @@ impl Hittable for Sphere {
 
         1. / solid_angle
     }
-    fn random(&self, point: &Point) -> Vec3 {
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
         let direction = self.center - *point;
         let distance_squared = direction.length_squared();
         let uvw = ONB::build_from_w(direction);
-        uvw.local(Vec3::random_to_sphere(self.radius, distance_squared))
+        uvw.local(Vec3::to_sphere(
+            self.radius,
+            distance_squared,
+            sampler.get_2d(),
+        ))
     }
 }
 
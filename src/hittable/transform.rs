@@ -0,0 +1,133 @@
+use crate::{hittable::*, matrix::*};
+use std::sync::Arc;
+
+/// Moves, turns, scales or shears an object with an affine `Matrix`. Rays are taken into
+/// the space of the object, so it's hit as if it had been built already transformed.
+pub struct Transform {
+    /// Can be shared with other transforms, which place copies of it
+    pub hittable: Arc<dyn Hittable>,
+    matrix: Matrix,
+    inverse: Matrix,
+    /// Transpose of the inverse, which keeps normals perpendicular to the surface
+    normal_matrix: Matrix,
+}
+
+impl Transform {
+    /// Panics if the matrix squashes space flat, like a scale by 0
+    pub fn new(hittable: Box<dyn Hittable>, matrix: Matrix) -> Self {
+        Self::shared(hittable.into(), matrix)
+    }
+
+    /// Transform of an object that other transforms can also have, without copying it.
+    /// Panics like `new`.
+    pub fn shared(hittable: Arc<dyn Hittable>, matrix: Matrix) -> Self {
+        let inverse = matrix
+            .inverse()
+            .expect("Transform matrix can't be inverted");
+        Self {
+            hittable,
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+
+    /// The ray in the space of the object, with a unit direction, as sphere tracing needs
+    /// one. Also gives how much longer a step of t is in that space, to convert t back.
+    fn local_ray(&self, ray: &Ray) -> (Ray, f32) {
+        let direction = self.inverse.transform_vector(&ray.direction);
+        let length = direction.length();
+        let local_ray = Ray {
+            origin: self.inverse.transform_point(&ray.origin),
+            direction: direction / length,
+            time: ray.time,
+        };
+        (local_ray, length)
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
+        let (local_ray, length) = self.local_ray(ray);
+        if !self.hittable.hit(
+            &local_ray,
+            taemin * length,
+            t_max * length,
+            hit_record,
+            sampler,
+        ) {
+            return false;
+        }
+        // Clamped, as rounding could take it out of the interval by a hair
+        hit_record.t = (hit_record.t / length).clamp(taemin, t_max);
+
+        // The normal still faces the ray, as transforming both keeps the sign of their dot
+        // product
+        hit_record.point = self.matrix.transform_point(&hit_record.point);
+        hit_record.normal = self
+            .normal_matrix
+            .transform_vector(&hit_record.normal)
+            .normalize();
+        true
+    }
+
+    /// Box around the 8 corners of the box of the object, once they're transformed
+    /// An object shared with other transforms is left as it is, as they'd all number it
+    fn number_objects(&mut self, next: &mut u32) {
+        if let Some(hittable) = Arc::get_mut(&mut self.hittable) {
+            hittable.number_objects(next);
+        }
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        let aabb = self.hittable.bounding_box(t0, t1)?;
+        let mut corners = [Point::zeros(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let pick = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    aabb.min[axis]
+                } else {
+                    aabb.max[axis]
+                }
+            };
+            *corner = self
+                .matrix
+                .transform_point(&Point::new(pick(0), pick(1), pick(2)));
+        }
+        Some(AABB::surrounding_points(&corners))
+    }
+
+    /// The pdf of the object over the directions in its space, times how much the transform
+    /// squeezes the directions around `vector` together
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
+        let local_vector = self.inverse.transform_vector(vector);
+        let local_pdf =
+            self.hittable
+                .pdf_value(&self.inverse.transform_point(point), &local_vector, sampler);
+        if local_pdf == 0. {
+            return 0.;
+        }
+
+        // A unit direction d in the space of the object covers |det| / |M d|^3 times the
+        // solid angle once it's transformed
+        let stretch = vector.length() / local_vector.length();
+        local_pdf * stretch.powi(3) / self.matrix.determinant().abs()
+    }
+
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        let local_point = self.inverse.transform_point(point);
+        self.matrix
+            .transform_vector(&self.hittable.random(&local_point, sampler))
+    }
+}
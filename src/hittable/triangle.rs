@@ -6,10 +6,33 @@ pub struct Triangle {
     pub v1: Point,
     pub v2: Point,
     pub material: Material,
+    /// Tells it apart in the `object_id` AOV, set by `World::number_objects`
+    pub id: u32,
+}
+
+impl Triangle {
+    pub fn new(v0: Point, v1: Point, v2: Point, material: Material) -> Self {
+        Self {
+            v0,
+            v1,
+            v2,
+            material,
+            id: 0,
+        }
+    }
 }
 
 impl Hittable for Triangle {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        _sampler: &mut Sampler,
+    ) -> bool {
+        count_intersection_test();
+
         let v1v0 = self.v1 - self.v0;
         let v2v0 = self.v2 - self.v0;
         let rov0 = ray.origin - self.v0;
@@ -17,12 +40,16 @@ impl Hittable for Triangle {
         let normal = v1v0.cross(&v2v0);
         let q = rov0.cross(&ray.direction);
         let d_inv = ray.direction.dot(&normal);
+        // Parallel to the triangle
+        if d_inv == 0.0 {
+            return false;
+        }
 
         let u = (-q).dot(&v2v0) / d_inv;
         let v = q.dot(&v1v0) / d_inv;
         let t = (-normal).dot(&rov0) / d_inv;
 
-        if u < 0.0 || u > 1.0 || v < 0.0 || (u + v) > 1.0 {
+        if !(0.0..=1.0).contains(&u) || v < 0.0 || (u + v) > 1.0 {
             return false;
         }
 
@@ -33,26 +60,50 @@ impl Hittable for Triangle {
 
         hit_record.t = t;
         hit_record.point = ray.at(t);
-        hit_record.set_face_normal(&ray, &normal.normalize());
+        hit_record.set_face_normal(ray, &normal.normalize());
         hit_record.material = self.material.clone();
+        hit_record.object_id = self.id;
         hit_record.u = u;
         hit_record.v = v;
 
         true
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        self.id = take_object_id(next);
+    }
+
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
-        Some(AABB {
-            min: Point::new(
-                self.v0.x.min(self.v1.x).min(self.v2.x),
-                self.v0.y.min(self.v1.y).min(self.v2.y),
-                self.v0.z.min(self.v1.z).min(self.v2.z),
-            ),
-            max: Point::new(
-                self.v0.x.max(self.v1.x).max(self.v2.x),
-                self.v0.y.max(self.v1.y).max(self.v2.y),
-                self.v0.z.max(self.v1.z).max(self.v2.z),
-            ),
-        })
+        Some(AABB::surrounding_points(&[self.v0, self.v1, self.v2]))
+    }
+
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
+        let mut hit_record = HitRecord::default();
+        let ray = Ray {
+            origin: *point,
+            direction: *vector,
+            time: 0.,
+        };
+        if !self.hit(&ray, 0.001, f32::INFINITY, &mut hit_record, sampler) {
+            return 0.;
+        }
+
+        let area = 0.5 * (self.v1 - self.v0).cross(&(self.v2 - self.v0)).length();
+        let distance_squared = hit_record.t.powi(2) * vector.length_squared();
+        let cosine = vector.dot(&hit_record.normal).abs() / vector.length();
+        if cosine != 0.0 {
+            distance_squared / (cosine * area)
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        // Uniform over the area, the square root keeps the points from bunching up at v0
+        let (r1, r2) = sampler.get_2d();
+        let s = r1.sqrt();
+        let random_point = self.v0 * (1. - s) + self.v1 * (s * (1. - r2)) + self.v2 * (s * r2);
+
+        random_point - *point
     }
 }
@@ -1,11 +1,12 @@
-use crate::{aabb::*, hit_record::*, hittable::*, ray::*, vec3::*};
-use rand::seq::SliceRandom;
+use crate::{aabb::*, hit_record::*, hittable::*, ray::*, sampler::*, vec3::*};
 
+/// Group of objects, tested one after the other
+#[derive(Default)]
 pub struct HittableList {
     objects: Vec<Box<dyn Hittable>>,
 }
 
-impl<'a> HittableList {
+impl HittableList {
     pub fn new() -> Self {
         Self {
             objects: Vec::new(),
@@ -21,14 +22,21 @@ impl<'a> HittableList {
     }
 }
 
-impl<'a> Hittable for HittableList {
-    fn hit(&self, ray: &Ray, taemin: f32, t_max: f32, hit_record: &mut HitRecord) -> bool {
+impl Hittable for HittableList {
+    fn hit(
+        &self,
+        ray: &Ray,
+        taemin: f32,
+        t_max: f32,
+        hit_record: &mut HitRecord,
+        sampler: &mut Sampler,
+    ) -> bool {
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
 
         for object in &self.objects {
             let mut temp_rec = HitRecord::default();
-            if object.hit(&ray, taemin, closest_so_far, &mut temp_rec) {
+            if object.hit(ray, taemin, closest_so_far, &mut temp_rec, sampler) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
                 *hit_record = temp_rec;
@@ -38,6 +46,12 @@ impl<'a> Hittable for HittableList {
         hit_anything
     }
 
+    fn number_objects(&mut self, next: &mut u32) {
+        for object in &mut self.objects {
+            object.number_objects(next);
+        }
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         if self.objects.is_empty() {
             return None;
@@ -56,21 +70,19 @@ impl<'a> Hittable for HittableList {
         Some(temp_box)
     }
 
-    fn pdf_value(&self, point: &Point, vector: &Vec3) -> f32 {
+    fn pdf_value(&self, point: &Point, vector: &Vec3, sampler: &mut Sampler) -> f32 {
         let weight = 1.0 / self.objects.len() as f32;
         let mut sum = 0.0;
 
         for object in &self.objects {
-            sum += weight * object.pdf_value(point, vector);
+            sum += weight * object.pdf_value(point, vector, sampler);
         }
 
         sum
     }
 
-    fn random(&self, point: &Point) -> Vec3 {
-        self.objects
-            .choose(&mut rand::thread_rng())
-            .unwrap()
-            .random(point)
+    fn random(&self, point: &Point, sampler: &mut Sampler) -> Vec3 {
+        let index = (sampler.get_1d() * self.objects.len() as f32) as usize;
+        self.objects[index.min(self.objects.len() - 1)].random(point, sampler)
     }
 }
@@ -1,26 +1,126 @@
-#![feature(clamp, box_syntax)]
+//! Ray tracer from the Ray Tracing in One Weekend books, with signed distance fields,
+//! adaptive sampling and denoising. Scenes are built as a `World`, or loaded from scene files
+//! with `load_scene`, and rendered with `render_world`:
+//!
+//! ```no_run
+//! use sunray::{texture, CameraSettings, Color, Material, Point, Sphere, World};
+//!
+//! let ground = texture::checker(
+//!     texture::solid_color(Color::new(0.2, 0.3, 0.1)),
+//!     texture::solid_color(Color::from(0.9)),
+//! );
+//! let world = World::builder()
+//!     .add_object(Sphere::new(
+//!         Point::new(0., -1000., 0.),
+//!         1000.,
+//!         Material::LambertianTexture(ground),
+//!     ))
+//!     .add_object(Sphere::new(Point::new(0., 1., 0.), 1., Material::Dielectric(1.5)))
+//!     .camera(CameraSettings {
+//!         look_from: Point::new(13., 2., 3.),
+//!         look_at: Point::new(0., 1., 0.),
+//!         ..CameraSettings::default()
+//!     })
+//!     .background(Color::new(0.7, 0.8, 1.))
+//!     .samples_per_pixel(64)
+//!     .build();
+//!
+//! let output = sunray::render_world(&world);
+//! output.image.save("spheres.png").unwrap();
+//! ```
 
 mod aabb;
+mod adaptive;
+mod aov;
+mod bench;
 mod bvh;
 mod camera;
+#[cfg(test)]
+mod chi_square;
+mod compare;
+mod denoise;
+mod framebuffer;
+mod gltf_file;
 mod hit_record;
 mod hittable;
 mod hittable_list;
+mod low_discrepancy;
 mod material;
+mod matrix;
+mod mesh_file;
 mod onb;
 mod pdf;
 mod perlin;
+mod progressive;
 mod ray;
 mod renderer;
+mod sampler;
+mod scene_file;
 mod scenes;
-mod texture;
+mod session;
+mod stats;
+pub mod texture;
+mod tiles;
+mod tonemap;
 mod vec3;
+mod world_builder;
 
 #[cfg(target_arch = "wasm32")]
 pub use renderer::get_image_ppm_single_threaded;
 
-pub use renderer::get_image_ppm;
-pub use scenes::Scene;
+pub use aabb::AABB;
+pub use adaptive::AdaptiveSampling;
+pub use aov::AOVBuffers;
+pub use bench::{
+    bench_scene, BenchComparison, BenchReport, BenchResult, BenchSettings, BENCH_SCENES,
+};
+pub use bvh::BVHNode;
+pub use camera::{Camera, CameraSettings};
+pub use compare::{compare_images, difference_image, difference_scale, ImageMetrics, Metric};
+pub use denoise::Denoiser;
+pub use framebuffer::{ExrPixelType, Framebuffer, ImageFormat};
+pub use gltf_file::load_gltf;
+pub use hit_record::HitRecord;
+pub use hittable::{
+    cube::Cube,
+    cylinder::Cylinder,
+    flip_face::FlipFace,
+    instance::Instance,
+    medium::ConstantMedium,
+    mesh::{Mesh, MeshFace, TriangleMesh},
+    moving_sphere::MovingSphere,
+    pyramid::Pyramid,
+    rectangle::{Plane, Rect},
+    sdf::{self, TracedSDF, SDF},
+    sphere::Sphere,
+    transform::Transform,
+    triangle::Triangle,
+    Hittable,
+};
+pub use hittable_list::HittableList;
+pub use material::Material;
+pub use matrix::Matrix;
+pub use mesh_file::{load_mesh, load_obj, load_ply};
+pub use perlin::Perlin;
+pub use progressive::{Accumulation, Progressive};
+pub use ray::Ray;
+pub use renderer::{render, render_world, render_world_with, RenderOutput};
+pub use sampler::{Sampler, SamplerKind};
+pub use scene_file::{load_scene, parse_scene, SceneError};
+pub use scenes::{generate_world, Scene, World};
+pub use session::RenderSession;
+pub use stats::RenderStats;
+pub use texture::Texture;
+pub use tiles::{Tile, TileOrder};
+pub use tonemap::{DisplayTransform, ToneMap};
+pub use vec3::{Color, Point, Vec3};
+pub use world_builder::WorldBuilder;
+
+// Counts the memory allocated, for `sunny bench` to report it. Only with the `bench`
+// feature, as it makes every allocation slower.
+#[cfg(all(feature = "bench", not(feature = "wee_alloc")))]
+#[global_allocator]
+static ALLOCATOR: bench::CountingAllocator = bench::CountingAllocator;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
@@ -0,0 +1,188 @@
+//! Low-discrepancy sequences, and the hashes used to scramble them so that every pixel
+//! gets a different set of well distributed points. Mostly follows pbrt-v4.
+
+/// Bases of the Halton sequence, one per dimension
+pub const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// Largest f32 below 1
+pub const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+pub fn mix_bits(mut value: u64) -> u64 {
+    value ^= value >> 31;
+    value = value.wrapping_mul(0x7fb5_d329_728e_a185);
+    value ^= value >> 27;
+    value = value.wrapping_mul(0x81da_def4_bc2d_d44d);
+    value ^= value >> 33;
+    value
+}
+
+/// Element `index` of a random permutation of `0..count`, without having to build the
+/// permutation (Kensler, Correlated Multi-Jittered Sampling)
+pub fn permutation_element(mut index: u32, count: u32, seed: u32) -> u32 {
+    let mut mask = count.wrapping_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+
+        if index < count {
+            return index.wrapping_add(seed) % count;
+        }
+    }
+}
+
+/// Sample `index` of dimension `dimension` of the Halton sequence, with every digit
+/// randomly permuted depending on the ones before it (Owen scrambling)
+pub fn halton_sample(dimension: usize, mut index: u32, seed: u32) -> f32 {
+    let base = PRIMES[dimension];
+    // Enough digits to fill the mantissa of an f32
+    let digits = (24.0 / (base as f32).log2()).ceil() as u32;
+
+    let mut reversed_digits: u64 = 0;
+    let mut scale = 1.0_f64;
+    for _ in 0..digits {
+        let digit = index % base;
+        index /= base;
+
+        let digit_seed = mix_bits(seed as u64 ^ reversed_digits) as u32;
+        let digit = permutation_element(digit, base, digit_seed);
+        reversed_digits = reversed_digits * base as u64 + digit as u64;
+        scale /= base as f64;
+    }
+
+    ((reversed_digits as f64 * scale) as f32).min(ONE_MINUS_EPSILON)
+}
+
+/// Sample `index` of the first (0) or second (1) dimension of the Sobol sequence,
+/// Owen scrambled with `seed`. Every other dimension is made by shuffling these two.
+pub fn sobol_sample(mut index: u64, dimension: usize, seed: u32) -> f32 {
+    let mut value = 0;
+    let mut column = 1 << 31;
+    while index != 0 && column != 0 {
+        if index & 1 == 1 {
+            value ^= column;
+        }
+        column = if dimension == 0 {
+            column >> 1
+        } else {
+            column ^ (column >> 1)
+        };
+        index >>= 1;
+    }
+
+    (owen_scramble(value, seed) as f32 * 2f32.powi(-32)).min(ONE_MINUS_EPSILON)
+}
+
+/// Hash-based approximation of Owen scrambling: every bit gets flipped or not depending on the
+/// bits above it. Applied to the index of a sample, it shuffles the sequence while keeping
+/// every aligned power of two block of samples together.
+pub fn owen_scramble(mut value: u32, seed: u32) -> u32 {
+    value = value.reverse_bits();
+    value ^= value.wrapping_mul(0x3d20_adea);
+    value = value.wrapping_add(seed);
+    value = value.wrapping_mul((seed >> 16) | 1);
+    value ^= value.wrapping_mul(0x0552_6c56);
+    value ^= value.wrapping_mul(0x53a2_2864);
+    value.reverse_bits()
+}
+
+/// Interleaves the bits of `x` and `y`, so pixels that are close get close indices
+pub fn encode_morton(x: u32, y: u32) -> u64 {
+    fn spread_bits(value: u32) -> u64 {
+        let mut value = value as u64;
+        value = (value ^ (value << 16)) & 0x0000_ffff_0000_ffff;
+        value = (value ^ (value << 8)) & 0x00ff_00ff_00ff_00ff;
+        value = (value ^ (value << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+        value = (value ^ (value << 2)) & 0x3333_3333_3333_3333;
+        value = (value ^ (value << 1)) & 0x5555_5555_5555_5555;
+        value
+    }
+
+    (spread_bits(y) << 1) | spread_bits(x)
+}
+
+const PERMUTATIONS: [[u64; 4]; 24] = [
+    [0, 1, 2, 3],
+    [0, 1, 3, 2],
+    [0, 2, 1, 3],
+    [0, 2, 3, 1],
+    [0, 3, 2, 1],
+    [0, 3, 1, 2],
+    [1, 0, 2, 3],
+    [1, 0, 3, 2],
+    [1, 2, 0, 3],
+    [1, 2, 3, 0],
+    [1, 3, 2, 0],
+    [1, 3, 0, 2],
+    [2, 1, 0, 3],
+    [2, 1, 3, 0],
+    [2, 0, 1, 3],
+    [2, 0, 3, 1],
+    [2, 3, 0, 1],
+    [2, 3, 1, 0],
+    [3, 1, 2, 0],
+    [3, 1, 0, 2],
+    [3, 2, 1, 0],
+    [3, 2, 0, 1],
+    [3, 0, 2, 1],
+    [3, 0, 1, 2],
+];
+
+/// Index into the Sobol sequence of a sample of the pixel with Morton code `morton`
+/// (Ahmed and Wonka, Screen-Space Blue-Noise Diffusion of Monte Carlo Sampling Error via
+/// Hierarchical Ordering of Pixels).
+///
+/// Every pixel gets a consecutive block of the sequence, in Z order, and then the base 4
+/// digits of the index are shuffled depending on the dimension. Neighbouring pixels end up
+/// with samples that complement each other, so the error looks like blue noise.
+pub fn zsobol_index(morton: u64, sample: u32, log2_samples: u32, dimension: u32) -> u64 {
+    // Pixel coordinates of up to 16 bits
+    const PIXEL_DIGITS: u32 = 16;
+
+    let index = (morton << log2_samples) | sample as u64;
+    let odd_samples = log2_samples & 1 == 1;
+    let last_digit = if odd_samples { 1 } else { 0 };
+    let digits = PIXEL_DIGITS + log2_samples.div_ceil(2);
+
+    let mut result = 0;
+    for i in (last_digit..digits).rev() {
+        let shift = 2 * i - last_digit;
+        let digit = (index >> shift) & 3;
+        let higher_digits = index >> (shift + 2);
+        let permutation = (mix_bits(higher_digits ^ (0x5555_5555 * dimension as u64)) >> 24) % 24;
+        result |= PERMUTATIONS[permutation as usize][digit as usize] << shift;
+    }
+
+    if odd_samples {
+        let digit = index & 1;
+        result |= digit ^ (mix_bits((index >> 1) ^ (0x5555_5555 * dimension as u64)) & 1);
+    }
+
+    result
+}
@@ -1,32 +1,859 @@
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
-use std::time::Instant;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
-use sunray::{get_image_ppm, Scene};
+use argh::FromArgs;
+use sunray::{
+    bench_scene, compare_images, difference_image, difference_scale, generate_world, load_gltf,
+    load_scene, render_world_with, Accumulation, AdaptiveSampling, BenchReport, BenchSettings,
+    Denoiser, Framebuffer, ImageFormat, Metric, Progressive, RenderSession, SamplerKind, Scene,
+    Tile, TileOrder, ToneMap, World, BENCH_SCENES,
+};
+
+/// Renders scenes with sunray
+#[derive(FromArgs)]
+struct Sunny {
+    #[argh(subcommand)]
+    command: Command,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Render(RenderArgs),
+    Watch(WatchArgs),
+    Bench(BenchArgs),
+    Compare(CompareArgs),
+    Scenes(ScenesArgs),
+}
+
+/// Lists the scenes that can be rendered
+#[derive(FromArgs)]
+#[argh(subcommand, name = "scenes")]
+struct ScenesArgs {}
+
+/// Renders a scene. Anything that isn't set keeps the value the scene picks.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "render")]
+struct RenderArgs {
+    /// scene to render, one of the ones `sunny scenes` lists, a scene file or a glTF file
+    #[argh(positional)]
+    scene: SceneArg,
+
+    /// width of the image in pixels
+    #[argh(option, short = 'w')]
+    width: Option<u16>,
+    /// aspect ratio of the image, as a number or width:height
+    #[argh(option)]
+    aspect: Option<AspectRatio>,
+    /// samples per pixel, or the most on average with adaptive sampling
+    #[argh(option, short = 's')]
+    spp: Option<u16>,
+    /// most bounces of a ray
+    #[argh(option)]
+    max_depth: Option<u16>,
+    /// seed of the random numbers. Renders with the same seed are identical
+    #[argh(option)]
+    seed: Option<u64>,
+    /// how the samples are distributed: independent, stratified, halton, sobol or blue-noise
+    #[argh(option)]
+    sampler: Option<SamplerKind>,
+    /// threads to render with, every core by default
+    #[argh(option, short = 'j')]
+    threads: Option<usize>,
+
+    /// image to write, with the format given by the extension (png, ppm, pfm or exr). Can
+    /// be repeated, and defaults to image.png and image.exr
+    #[argh(option, short = 'o')]
+    output: Vec<PathBuf>,
+    /// format of the images, instead of the one given by their extension
+    #[argh(option)]
+    format: Option<ImageFormat>,
+    /// tone mapping operator: clamp, reinhard, extended-reinhard, aces or hable. The white
+    /// point of extended-reinhard goes after a colon, like extended-reinhard:8
+    #[argh(option)]
+    tonemap: Option<ToneMap>,
+    /// exposure compensation in stops
+    #[argh(option)]
+    exposure: Option<f32>,
+    /// also write the auxiliary buffers (albedo, normal, depth...) next to the image
+    #[argh(switch)]
+    aovs: bool,
+    /// filter the noise out of the image
+    #[argh(switch)]
+    denoise: bool,
+
+    /// stop sampling pixels once their relative error is under this threshold
+    #[argh(option)]
+    adaptive: Option<f32>,
+    /// render in passes of this many samples per pixel
+    #[argh(option)]
+    progressive: Option<u32>,
+    /// save the progressive render to this checkpoint, so it can be resumed
+    #[argh(option)]
+    checkpoint: Option<PathBuf>,
+    /// carry on from a checkpoint. With more samples, it adds them to a finished render
+    #[argh(option)]
+    resume: Option<PathBuf>,
+    /// side of the tiles the image is rendered in, in pixels
+    #[argh(option)]
+    tile_size: Option<u16>,
+    /// order of the tiles: spiral, hilbert or scanline
+    #[argh(option)]
+    tile_order: Option<TileOrder>,
+    /// write the tiles to this image as they finish, to watch the render
+    #[argh(option)]
+    preview: Option<PathBuf>,
+    /// stop the render after this many seconds, keeping what's done
+    #[argh(option)]
+    time_limit: Option<f32>,
+
+    /// print the settings without rendering
+    #[argh(switch)]
+    dry_run: bool,
+}
+
+/// Renders a quick preview of a scene file, and renders it again every time the file changes
+#[derive(FromArgs)]
+#[argh(subcommand, name = "watch")]
+struct WatchArgs {
+    /// scene file or glTF file to watch
+    #[argh(positional)]
+    scene: PathBuf,
+
+    /// width of the preview in pixels
+    #[argh(option, short = 'w', default = "400")]
+    width: u16,
+    /// samples per pixel the preview stops at
+    #[argh(option, short = 's', default = "16")]
+    spp: u16,
+    /// samples per pixel added by every pass. The preview is written after each of them
+    #[argh(option, default = "1")]
+    pass_samples: u32,
+    /// image to write the preview to
+    #[argh(option, short = 'o', default = "PathBuf::from(\"preview.png\")")]
+    output: PathBuf,
+    /// seconds between checks of the scene file
+    #[argh(option, default = "0.5")]
+    interval: f32,
+}
+
+/// Renders a fixed set of scenes with fixed settings, and reports how long they take and
+/// the work they do
+#[derive(FromArgs)]
+#[argh(subcommand, name = "bench")]
+struct BenchArgs {
+    /// scenes to render, instead of the default set
+    #[argh(positional)]
+    scenes: Vec<Scene>,
+
+    /// width of the images in pixels
+    #[argh(option, short = 'w', default = "BenchSettings::default().width")]
+    width: u16,
+    /// samples per pixel
+    #[argh(
+        option,
+        short = 's',
+        default = "BenchSettings::default().samples_per_pixel"
+    )]
+    spp: u16,
+    /// seed of the random numbers
+    #[argh(option, default = "BenchSettings::default().seed")]
+    seed: u64,
+    /// times each scene is rendered, keeping the fastest
+    #[argh(option, default = "BenchSettings::default().runs")]
+    runs: u32,
+    /// threads to render with, every core by default
+    #[argh(option, short = 'j')]
+    threads: Option<usize>,
+
+    /// write the report to this CSV file
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+    /// report of an earlier run to compare with
+    #[argh(option)]
+    baseline: Option<PathBuf>,
+    /// percentage a scene can get slower than the baseline before it counts as a
+    /// regression, which makes the command fail
+    #[argh(option, default = "5.")]
+    tolerance: f64,
+}
+
+/// Compares an image with a reference, printing how different they are
+#[derive(FromArgs)]
+#[argh(subcommand, name = "compare")]
+struct CompareArgs {
+    /// image to check
+    #[argh(positional)]
+    image: PathBuf,
+    /// reference image
+    #[argh(positional)]
+    reference: PathBuf,
+
+    /// write an image with the difference of every pixel in false color
+    #[argh(option)]
+    diff: Option<PathBuf>,
+    /// difference that gets the brightest color in the diff image. By default it's the one
+    /// 99% of the pixels are under
+    #[argh(option)]
+    diff_scale: Option<f32>,
+    /// metric the threshold applies to: rmse, relmse, psnr or ssim
+    #[argh(option, default = "Metric::RelMse")]
+    metric: Metric,
+    /// fail if the metric is worse than this. Errors have to be at most, PSNR and SSIM at
+    /// least this
+    #[argh(option)]
+    threshold: Option<f64>,
+}
+
+/// Built-in scene, or the path of a scene file or a glTF file
+enum SceneArg {
+    BuiltIn(Scene),
+    File(PathBuf),
+}
+
+impl FromStr for SceneArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = PathBuf::from(s);
+        if path
+            .extension()
+            .is_some_and(|extension| ["scene", "gltf", "glb"].iter().any(|e| extension == *e))
+            || path.is_file()
+        {
+            return Ok(Self::File(path));
+        }
+        Scene::from_str(s).map(Self::BuiltIn)
+    }
+}
+
+impl SceneArg {
+    fn name(&self) -> String {
+        match self {
+            Self::BuiltIn(scene) => scene.name().to_string(),
+            Self::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+/// Aspect ratio, as `1.5` or `3:2`
+struct AspectRatio(f32);
+
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("aspect ratio has to be a number or width:height, got {}", s);
+        let ratio = match s.split_once(':') {
+            Some((width, height)) => {
+                let width: f32 = width.parse().map_err(|_| error())?;
+                let height: f32 = height.parse().map_err(|_| error())?;
+                width / height
+            }
+            None => s.parse().map_err(|_| error())?,
+        };
+
+        if ratio.is_finite() && ratio > 0. {
+            Ok(Self(ratio))
+        } else {
+            Err(error())
+        }
+    }
+}
+
+impl RenderArgs {
+    /// The scene's world, with the settings from the arguments
+    fn world(&self) -> Result<World, String> {
+        let mut world = match &self.scene {
+            SceneArg::BuiltIn(scene) => generate_world(*scene),
+            SceneArg::File(path) => {
+                load_world(path).map_err(|why| format!("couldn't load the scene {}", why))?
+            }
+        };
+
+        if let Some(width) = self.width {
+            world.image_width = width;
+        }
+        if let Some(AspectRatio(aspect_ratio)) = self.aspect {
+            world.set_aspect_ratio(aspect_ratio);
+        }
+        if let Some(max_depth) = self.max_depth {
+            world.max_depth = max_depth;
+        }
+        if let Some(seed) = self.seed {
+            world.seed = seed;
+        }
+        if let Some(sampler) = self.sampler {
+            world.sampler = sampler;
+        }
+        if let Some(tone_map) = self.tonemap {
+            world.display.tone_map = tone_map;
+        }
+        if let Some(exposure) = self.exposure {
+            world.display.exposure = exposure;
+        }
+        if let Some(tile_size) = self.tile_size {
+            world.tile_size = tile_size;
+        }
+        if let Some(tile_order) = self.tile_order {
+            world.tile_order = tile_order;
+        }
+        world.aovs |= self.aovs;
+        if self.denoise {
+            world.denoise = Some(Denoiser::default());
+        }
+
+        if let Some(spp) = self.spp {
+            world.samples_per_pixel = spp;
+        }
+        if let Some(noise_threshold) = self.adaptive {
+            let mut adaptive = AdaptiveSampling {
+                noise_threshold,
+                ..AdaptiveSampling::default()
+            };
+            adaptive.min_samples = adaptive.min_samples.min(world.samples_per_pixel);
+            world.adaptive = Some(adaptive);
+        }
+
+        Ok(world)
+    }
+
+    /// Images to write, and their format
+    fn outputs(&self) -> Result<Vec<(PathBuf, ImageFormat)>, String> {
+        let paths = if self.output.is_empty() {
+            // PNG for viewing, EXR keeps the linear HDR values for compositing
+            vec![PathBuf::from("image.png"), PathBuf::from("image.exr")]
+        } else {
+            self.output.clone()
+        };
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let format = self
+                    .format
+                    .or_else(|| ImageFormat::from_path(&path))
+                    .ok_or_else(|| {
+                        format!(
+                            "can't tell the format of {} from its extension, use --format",
+                            path.display()
+                        )
+                    })?;
+                Ok((path, format))
+            })
+            .collect()
+    }
+
+    fn progressive(&self) -> Option<Progressive> {
+        if self.progressive.is_none() && self.checkpoint.is_none() && self.resume.is_none() {
+            return None;
+        }
+
+        let mut progressive = Progressive {
+            checkpoint: self.checkpoint.clone(),
+            ..Progressive::default()
+        };
+        if let Some(pass_samples) = self.progressive {
+            progressive.pass_samples = pass_samples;
+        }
+        Some(progressive)
+    }
+}
 
 fn main() {
+    let sunny: Sunny = argh::from_env();
+
+    let result = match sunny.command {
+        Command::Render(args) => render(&args),
+        Command::Watch(args) => watch(&args),
+        Command::Bench(args) => bench(&args),
+        Command::Compare(args) => compare(&args),
+        Command::Scenes(_) => {
+            for scene in &Scene::ALL {
+                println!("{}", scene.name());
+            }
+            Ok(())
+        }
+    };
+
+    if let Err(why) = result {
+        eprintln!("error: {}", why);
+        std::process::exit(1);
+    }
+}
+
+fn render(args: &RenderArgs) -> Result<(), String> {
     let start = Instant::now();
 
-    let path = Path::new("image.ppm");
-    let display = path.display();
+    set_threads(args.threads)?;
+
+    let world = args.world()?;
+    let outputs = args.outputs()?;
+    print_settings(args, &world, &outputs);
+    if args.dry_run {
+        return Ok(());
+    }
 
-    let result = get_image_ppm(Scene::CornellMandelBox);
+    // The preview gets written at most twice a second, so the threads don't wait on it
+    let preview_image = Mutex::new((
+        Framebuffer::new(world.image_width as usize, world.image_height()),
+        Instant::now(),
+    ));
+    let on_tile = |tile: &Tile, pixels: &Framebuffer| {
+        let path = match &args.preview {
+            Some(path) => path,
+            None => return,
+        };
 
-    // Open a file in write-only mode, returns `io::Result<File>`
-    let mut file = match File::create(&path) {
-        Err(why) => panic!("couldn't create {}: {}", display, why),
-        Ok(file) => file,
+        let (image, last_save) = &mut *preview_image.lock().unwrap();
+        image.display = pixels.display;
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                image.set(tile.x + x, tile.y + y, pixels.get(x, y));
+            }
+        }
+
+        if last_save.elapsed() >= Duration::from_millis(500) {
+            if let Err(why) = image.save(path) {
+                eprintln!("couldn't write the preview {}: {}", path.display(), why);
+            }
+            *last_save = Instant::now();
+        }
     };
 
-    match file.write_all(result.as_bytes()) {
-        Err(why) => panic!("couldn't write to {}: {}", display, why),
-        Ok(_) => {}
+    // Before rendering, as the render only stops for errors once it's done
+    let checkpoint =
+        match &args.resume {
+            Some(path) => Some(Accumulation::load(path).map_err(|why| {
+                format!("couldn't load the checkpoint {}: {}", path.display(), why)
+            })?),
+            None => None,
+        };
+
+    let time_limit = args.time_limit.map(Duration::from_secs_f32);
+    let session = RenderSession::new();
+    let (render_done, render_finished) = mpsc::channel::<()>();
+    let output = thread::scope(|scope| {
+        let session = &session;
+
+        // Reports the progress every second, until the render is done
+        scope.spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) =
+                render_finished.recv_timeout(Duration::from_secs(1))
+            {
+                if let Some(eta) = session.eta() {
+                    println!(
+                        "{:.1}% done, {:.0?} left, {:.0} samples/s",
+                        session.progress() * 100.,
+                        eta,
+                        session.samples_per_second()
+                    );
+                }
+                if time_limit.is_some_and(|limit| session.elapsed() >= limit) {
+                    session.cancel();
+                }
+            }
+        });
+
+        let output = if let Some(progressive) = args.progressive() {
+            let mut accumulation = checkpoint.unwrap_or_else(|| Accumulation::new(&world));
+            println!(
+                "Rendering {}x{} progressively, starting from {:.1} samples per pixel",
+                accumulation.width,
+                accumulation.height,
+                accumulation.average_samples()
+            );
+            let mut pass = 0;
+            let on_pass = |accumulation: &Accumulation| {
+                pass += 1;
+                println!(
+                    "Pass {}: {:.1} samples per pixel",
+                    pass,
+                    accumulation.average_samples()
+                );
+            };
+            progressive
+                .render_with_passes(&world, &mut accumulation, session, on_tile, on_pass)
+                .map_err(|why| format!("progressive render failed: {}", why))
+        } else {
+            match &world.adaptive {
+                Some(adaptive) => println!(
+                    "Rendering {}x{} with {} samples on average at most, {} to {} per pixel",
+                    world.image_width,
+                    world.image_height(),
+                    world.samples_per_pixel,
+                    adaptive.min_samples,
+                    adaptive.max_samples
+                ),
+                None => println!(
+                    "Rendering {}x{} with {} samples",
+                    world.image_width,
+                    world.image_height(),
+                    world.samples_per_pixel
+                ),
+            }
+            Ok(render_world_with(&world, session, on_tile))
+        };
+        drop(render_done);
+        output
+    })?;
+    if session.is_cancelled() {
+        println!(
+            "Cancelled at {:.1}% with {:.1} samples on average",
+            session.progress() * 100.,
+            output.average_samples
+        );
+    } else {
+        println!(
+            "Rendered {}x{} with {:.1} samples on average",
+            output.image.width, output.image.height, output.average_samples
+        );
     }
+    println!("{}", session.stats());
 
-    // Yeah it's a bit dumb to open the image again
-    let img = image::open("image.ppm").unwrap();
-    img.save("image.png").unwrap();
+    for (path, format) in &outputs {
+        output
+            .image
+            .save_as(path, *format)
+            .map_err(|why| format!("couldn't write {}: {}", path.display(), why))?;
+    }
+    // The AOVs keep their values, so they always go in EXR files
+    if let Some(aovs) = &output.aovs {
+        aovs.save(outputs[0].0.with_extension("exr"))
+            .map_err(|why| format!("couldn't write the AOVs: {}", why))?;
+    }
 
     println!("{:.2?} seconds to run.", start.elapsed());
+    Ok(())
+}
+
+fn set_threads(threads: Option<usize>) -> Result<(), String> {
+    if let Some(threads) = threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|why| format!("couldn't start {} threads: {}", threads, why))?;
+    }
+    Ok(())
+}
+
+fn watch(args: &WatchArgs) -> Result<(), String> {
+    let interval = Duration::from_secs_f32(args.interval);
+    let progressive = Progressive {
+        pass_samples: args.pass_samples,
+        ..Progressive::default()
+    };
+
+    let mut last_modified = None;
+    loop {
+        last_modified = Some(wait_for_change(&args.scene, last_modified, interval));
+
+        // A scene with errors keeps the last image, until it's fixed
+        let mut world = match load_world(&args.scene) {
+            Ok(world) => world,
+            Err(why) => {
+                eprintln!("{}", why);
+                println!("Keeping the last preview, waiting for the scene to change");
+                continue;
+            }
+        };
+        world.image_width = args.width;
+        world.samples_per_pixel = args.spp;
+        world.adaptive = None;
+
+        let session = RenderSession::new();
+        let (render_done, render_finished) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            let session = &session;
+
+            // Stops the render as soon as the scene changes, to start over with the new one
+            scope.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = render_finished.recv_timeout(interval) {
+                    if modified(&args.scene) != last_modified {
+                        session.cancel();
+                    }
+                }
+            });
+
+            let save = |accumulation: &Accumulation| {
+                if let Err(why) = accumulation.output(&world).image.save(&args.output) {
+                    eprintln!("couldn't write {}: {}", args.output.display(), why);
+                }
+            };
+            let mut accumulation = Accumulation::new(&world);
+            let rendered = progressive.render_with_passes(
+                &world,
+                &mut accumulation,
+                session,
+                |_: &Tile, _: &Framebuffer| {},
+                save,
+            );
+            drop(render_done);
+            rendered
+        })
+        .map_err(|why| format!("progressive render failed: {}", why))?;
+
+        if !session.is_cancelled() {
+            println!(
+                "Wrote {}, waiting for the scene to change",
+                args.output.display()
+            );
+        }
+    }
+}
+
+/// Loads a glTF file by its extension, or a scene file, and prints its warnings
+fn load_world(path: &Path) -> std::io::Result<World> {
+    let world = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("gltf") | Some("glb") => load_gltf(path)?,
+        _ => load_scene(path)?,
+    };
+    for warning in &world.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    Ok(world)
+}
+
+/// When the file was last modified, if it can be read
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Waits until the file has a different modification time than `last_modified`, and returns it
+fn wait_for_change(
+    path: &Path,
+    last_modified: Option<SystemTime>,
+    interval: Duration,
+) -> SystemTime {
+    loop {
+        match modified(path) {
+            Some(modified) if Some(modified) != last_modified => return modified,
+            _ => thread::sleep(interval),
+        }
+    }
+}
+
+fn bench(args: &BenchArgs) -> Result<(), String> {
+    set_threads(args.threads)?;
+    if !cfg!(feature = "bench") {
+        println!("The memory isn't counted, build with `--features bench` to count it");
+    }
+
+    // Read first, so a wrong path doesn't waste a whole benchmark
+    let baseline = match &args.baseline {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .map_err(|why| why.to_string())
+                .and_then(|report| report.parse::<BenchReport>())
+                .map_err(|why| format!("couldn't read the baseline {}: {}", path.display(), why))?,
+        ),
+        None => None,
+    };
+
+    let settings = BenchSettings {
+        width: args.width,
+        samples_per_pixel: args.spp,
+        seed: args.seed,
+        runs: args.runs,
+    };
+    let scenes = if args.scenes.is_empty() {
+        BENCH_SCENES.to_vec()
+    } else {
+        args.scenes.clone()
+    };
+
+    let mut report = BenchReport::default();
+    for scene in scenes {
+        println!("Benchmarking {}", scene.name());
+        let result = bench_scene(scene, &settings);
+        println!("{}", result.stats);
+        println!(
+            "Built in {:.2?}, {:.1} MiB at most",
+            result.build_time,
+            mebibytes(result.peak_memory)
+        );
+        report.results.push(result);
+    }
+
+    println!();
+    println!(
+        "{:<20} {:>10} {:>14} {:>16} {:>10}",
+        "scene", "time", "rays/s", "intersections", "memory"
+    );
+    for result in &report.results {
+        println!(
+            "{:<20} {:>10.2?} {:>14.0} {:>16} {:>6.1} MiB",
+            result.scene,
+            result.stats.elapsed,
+            result.stats.rays_per_second(),
+            result.stats.intersection_tests,
+            mebibytes(result.peak_memory)
+        );
+    }
+
+    if let Some(path) = &args.output {
+        fs::write(path, report.to_string())
+            .map_err(|why| format!("couldn't write {}: {}", path.display(), why))?;
+        println!("Wrote the report to {}", path.display());
+    }
+
+    if let Some(baseline) = &baseline {
+        let mut regressions = 0;
+        println!();
+        println!("Compared with the baseline:");
+        for comparison in report.compare(baseline) {
+            let time_change = comparison.time_change() * 100.;
+            println!(
+                "{:<20} {:>10.2?} -> {:>10.2?} ({:+.1}%), memory {:+.1}%",
+                comparison.result.scene,
+                comparison.baseline.stats.elapsed,
+                comparison.result.stats.elapsed,
+                time_change,
+                comparison.memory_change() * 100.
+            );
+            if !comparison.same_settings() {
+                println!("    rendered with different settings, the times can't be compared");
+            } else {
+                if comparison.different_work() {
+                    println!("    traced different rays or tested different objects");
+                }
+                if comparison.regressed(args.tolerance) {
+                    println!("    slower than the baseline");
+                    regressions += 1;
+                }
+            }
+        }
+
+        if regressions > 0 {
+            return Err(format!(
+                "{} scenes are more than {}% slower than the baseline",
+                regressions, args.tolerance
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn compare(args: &CompareArgs) -> Result<(), String> {
+    let load = |path: &PathBuf| {
+        Framebuffer::load(path).map_err(|why| format!("couldn't load {}: {}", path.display(), why))
+    };
+    let image = load(&args.image)?;
+    let reference = load(&args.reference)?;
+    if (image.width, image.height) != (reference.width, reference.height) {
+        return Err(format!(
+            "the image is {}x{} and the reference {}x{}",
+            image.width, image.height, reference.width, reference.height
+        ));
+    }
+
+    let metrics = compare_images(&image, &reference);
+    println!("{}", metrics);
+
+    if let Some(path) = &args.diff {
+        let scale = args
+            .diff_scale
+            .unwrap_or_else(|| difference_scale(&image, &reference));
+        difference_image(&image, &reference, scale)
+            .save(path)
+            .map_err(|why| format!("couldn't write {}: {}", path.display(), why))?;
+        println!(
+            "Wrote the difference to {}, white is {}",
+            path.display(),
+            scale
+        );
+    }
+
+    if let Some(threshold) = args.threshold {
+        if !args.metric.passes(&metrics, threshold) {
+            return Err(format!(
+                "{:?} is {}, over the threshold of {}",
+                args.metric,
+                args.metric.value(&metrics),
+                threshold
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn mebibytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024. * 1024.)
+}
+
+fn print_settings(args: &RenderArgs, world: &World, outputs: &[(PathBuf, ImageFormat)]) {
+    let setting = |name: &str, value: String| println!("{:<14}{}", name, value);
+
+    setting("Scene", args.scene.name());
+    setting(
+        "Resolution",
+        format!(
+            "{}x{} (aspect ratio {:.3})",
+            world.image_width,
+            world.image_height(),
+            world.aspect_ratio
+        ),
+    );
+    setting(
+        "Samples",
+        match &world.adaptive {
+            Some(adaptive) => format!(
+                "{} per pixel on average at most, {} to {} per pixel, until the relative error \
+                 is under {}",
+                world.samples_per_pixel,
+                adaptive.min_samples,
+                adaptive.max_samples,
+                adaptive.noise_threshold
+            ),
+            None => format!("{} per pixel", world.samples_per_pixel),
+        },
+    );
+    setting("Max depth", world.max_depth.to_string());
+    setting("Seed", world.seed.to_string());
+    setting("Sampler", format!("{:?}", world.sampler));
+    setting(
+        "Tiles",
+        format!(
+            "{}x{} pixels, {:?} order",
+            world.tile_size, world.tile_size, world.tile_order
+        ),
+    );
+    setting("Threads", rayon::current_num_threads().to_string());
+    setting(
+        "Display",
+        format!(
+            "{:?} tone mapping, {:+} stops",
+            world.display.tone_map, world.display.exposure
+        ),
+    );
+    setting("AOVs", if world.aovs { "yes" } else { "no" }.to_string());
+    setting(
+        "Denoise",
+        if world.denoise.is_some() { "yes" } else { "no" }.to_string(),
+    );
+    if let Some(progressive) = args.progressive() {
+        setting(
+            "Progressive",
+            format!("{} samples per pass", progressive.pass_samples),
+        );
+    }
+    let outputs: Vec<String> = outputs
+        .iter()
+        .map(|(path, format)| format!("{} ({:?})", path.display(), format))
+        .collect();
+    setting("Output", outputs.join(", "));
 }
@@ -1,17 +1,23 @@
-use crate::{hit_record::*, onb::*, pdf::*, ray::*, texture::*, vec3::*};
-use rand::*;
+use crate::{hit_record::*, onb::*, pdf::*, ray::*, sampler::*, texture::*, vec3::*};
 use std::f32::consts::PI;
 
+/// How a surface reflects, refracts or emits light
 #[derive(Clone)]
 #[allow(dead_code)]
 pub enum Material {
+    /// Shows the normals as colors, for debugging
     Normal,
+    /// Matte surface with a color
     Lambertian(Color),
     LambertianTexture(Texture),
+    /// Mirror with a color, and a fuzz from 0 (polished) to 1 that blurs the reflections
     Metal(Color, f32),
+    /// Glass or water, with its index of refraction
     Dielectric(f32),
+    /// Emits light of a color, which can be brighter than 1
     DiffuseLight(Color),
     DiffuseLightTexture(Texture),
+    /// Scatters light in any direction, used inside of media
     Isotropic(Color),
 }
 
@@ -36,7 +42,12 @@ impl Material {
         }
     }
 
-    pub fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+    pub fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sampler: &mut Sampler,
+    ) -> Option<ScatterRecord<'_>> {
         match self {
             Self::Lambertian(albedo) => Some(ScatterRecord::Scatter {
                 attenuation: *albedo,
@@ -48,15 +59,15 @@ impl Material {
             }),
             Self::Metal(albedo, fuzz) => {
                 let reflected = ray_in.direction.reflect(&hit_record.normal);
+                // A broken normal absorbs the ray, instead of spreading NaNs over the image
                 if reflected.x.is_nan() {
-                    dbg!(ray_in.direction, hit_record.normal);
-                    println!("mat");
-                    std::process::exit(1);
+                    return None;
                 }
                 Some(ScatterRecord::Specular {
                     specular_ray: Ray {
                         origin: hit_record.point,
-                        direction: (reflected + *fuzz * Vec3::random_in_unit_sphere()).normalize(),
+                        direction: (reflected + *fuzz * Vec3::random_in_unit_sphere(sampler))
+                            .normalize(),
                         time: 0.,
                     },
                     attenuation: *albedo,
@@ -84,7 +95,7 @@ impl Material {
                     });
                 }
                 let reflect_prob = schlick(cos_theta, eta_over_etai);
-                if rand::thread_rng().gen::<f32>() < reflect_prob {
+                if sampler.get_1d() < reflect_prob {
                     return Some(ScatterRecord::Specular {
                         specular_ray: Ray {
                             origin: hit_record.point,
@@ -107,7 +118,7 @@ impl Material {
             Self::Isotropic(albedo) => Some(ScatterRecord::Specular {
                 specular_ray: Ray {
                     origin: hit_record.point,
-                    direction: Vec3::random_in_unit_sphere(),
+                    direction: Vec3::on_unit_sphere(sampler.get_2d()),
                     time: ray_in.time,
                 },
                 attenuation: *albedo,
@@ -116,9 +127,23 @@ impl Material {
         }
     }
 
+    /// Base color of the surface, used for the albedo AOV
+    pub fn albedo(&self, hit_record: &HitRecord) -> Color {
+        match self {
+            Self::Normal => hit_record.normal,
+            Self::Lambertian(albedo) | Self::Metal(albedo, _) | Self::Isotropic(albedo) => *albedo,
+            Self::LambertianTexture(albedo) => albedo(hit_record.u, hit_record.v, hit_record.point),
+            Self::Dielectric(_) => Color::ones(),
+            Self::DiffuseLight(emit) => emit.clamp(0.0, 1.0),
+            Self::DiffuseLightTexture(emit) => {
+                emit(hit_record.u, hit_record.v, hit_record.point).clamp(0.0, 1.0)
+            }
+        }
+    }
+
     pub fn scattering_pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f32 {
         match self {
-            Self::Lambertian(_albedo) => {
+            Self::Lambertian(_) | Self::LambertianTexture(_) => {
                 let cosine = hit_record.normal.dot(&scattered.direction.normalize());
                 if cosine < 0. {
                     0.
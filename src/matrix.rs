@@ -0,0 +1,144 @@
+use crate::vec3::*;
+use std::ops::Mul;
+
+/// 4x4 matrix of an affine transform, which moves, turns, scales or shears points and
+/// vectors. `a * b` is the transform that does `b` and then `a`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix {
+    /// Row by row. The last one is always `[0, 0, 0, 1]`.
+    pub rows: [[f32; 4]; 4],
+}
+
+impl Default for Matrix {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Matrix {
+    pub fn identity() -> Self {
+        Self::from_rows([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]])
+    }
+
+    /// Linear part of the transform, without moving
+    fn from_rows(rows: [[f32; 3]; 3]) -> Self {
+        let row = |[x, y, z]: [f32; 3]| [x, y, z, 0.];
+        Self {
+            rows: [row(rows[0]), row(rows[1]), row(rows[2]), [0., 0., 0., 1.]],
+        }
+    }
+
+    /// From the columns, like glTF and OpenGL keep their matrices
+    pub fn from_columns(columns: [[f32; 4]; 4]) -> Self {
+        Self {
+            rows: Self { rows: columns }.transpose().rows,
+        }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut matrix = Self::identity();
+        matrix.rows[0][3] = offset.x;
+        matrix.rows[1][3] = offset.y;
+        matrix.rows[2][3] = offset.z;
+        matrix
+    }
+
+    /// Rotation around an axis through the origin, by an angle in degrees. It's
+    /// counterclockwise when the axis points towards the viewer.
+    pub fn rotation(axis: Vec3, angle: f32) -> Self {
+        let Vec3 { x, y, z } = axis.normalize();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let k = 1. - cos;
+
+        Self::from_rows([
+            [cos + x * x * k, x * y * k - z * sin, x * z * k + y * sin],
+            [y * x * k + z * sin, cos + y * y * k, y * z * k - x * sin],
+            [z * x * k - y * sin, z * y * k + x * sin, cos + z * z * k],
+        ])
+    }
+
+    /// Scales each axis by a factor, which mirrors it if it's negative
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::from_rows([
+            [factors.x, 0., 0.],
+            [0., factors.y, 0.],
+            [0., 0., factors.z],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.rows[j][i];
+            }
+        }
+        Self { rows }
+    }
+
+    /// Column of the linear part, which is where the axis ends up
+    fn axis(&self, index: usize) -> Vec3 {
+        Vec3::new(
+            self.rows[0][index],
+            self.rows[1][index],
+            self.rows[2][index],
+        )
+    }
+
+    /// Of the linear part, which is how much volumes get scaled by, and negative if the
+    /// transform mirrors them
+    pub fn determinant(&self) -> f32 {
+        self.axis(0).dot(&self.axis(1).cross(&self.axis(2)))
+    }
+
+    /// `None` if the transform squashes space flat, and can't be undone
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant == 0. || !determinant.is_finite() {
+            return None;
+        }
+
+        // The rows of the inverse of the linear part are the cross products of its columns
+        let (a, b, c) = (self.axis(0), self.axis(1), self.axis(2));
+        let rows = [b.cross(&c), c.cross(&a), a.cross(&b)];
+        let mut inverse = Self::from_rows([
+            [rows[0].x, rows[0].y, rows[0].z],
+            [rows[1].x, rows[1].y, rows[1].z],
+            [rows[2].x, rows[2].y, rows[2].z],
+        ]);
+        for row in &mut inverse.rows[..3] {
+            for value in &mut row[..3] {
+                *value /= determinant;
+            }
+        }
+
+        let offset = inverse.transform_vector(&self.axis(3));
+        inverse.rows[0][3] = -offset.x;
+        inverse.rows[1][3] = -offset.y;
+        inverse.rows[2][3] = -offset.z;
+        Some(inverse)
+    }
+
+    pub fn transform_point(&self, point: &Point) -> Point {
+        self.transform_vector(point) + self.axis(3)
+    }
+
+    /// Vectors are only turned and scaled, and not moved
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        self.axis(0) * vector.x + self.axis(1) * vector.y + self.axis(2) * vector.z
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let mut rows = [[0.; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * other.rows[k][j]).sum();
+            }
+        }
+        Self { rows }
+    }
+}
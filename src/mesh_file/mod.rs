@@ -0,0 +1,68 @@
+//! Loaders for meshes made in other programs, which give a `TriangleMesh`. The faces that
+//! don't say which material they have get the one passed to the loader.
+
+mod obj;
+mod ply;
+
+pub use self::{obj::load_obj, ply::load_ply};
+use crate::{hittable::mesh::*, material::*};
+use std::{fmt, io, path::Path};
+
+/// Loads a mesh, with the format given by the extension of the file
+pub fn load_mesh(path: impl AsRef<Path>, material: Material) -> io::Result<TriangleMesh> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    match extension.as_deref() {
+        Some("obj") => load_obj(path, material),
+        Some("ply") => load_ply(path, material),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{}: unknown kind of mesh, expected an .obj or a .ply",
+                path.display()
+            ),
+        )),
+    }
+}
+
+/// Error in a mesh file, with the line it's in
+#[derive(Clone, Debug, PartialEq)]
+struct MeshError {
+    line: usize,
+    message: String,
+}
+
+impl MeshError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// As an `io::Error` that starts with the path of the file and the line
+    fn at(self, path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}:{}", path.display(), self),
+        )
+    }
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+/// Error in a binary file, where there are no lines
+fn invalid_data(path: &Path, message: impl fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), message),
+    )
+}
@@ -0,0 +1,436 @@
+//! Wavefront OBJ meshes, with their materials from MTL files
+
+use super::MeshError;
+use crate::{
+    hittable::mesh::*,
+    material::*,
+    texture::{self, Texture},
+    vec3::*,
+};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Loads an OBJ file, and the MTL files it uses, relative to it. Polygons are split into
+/// triangles, and the faces in a smoothing group that don't have normals get smooth ones,
+/// from the faces around each vertex.
+pub fn load_obj(path: impl AsRef<Path>, material: Material) -> io::Result<TriangleMesh> {
+    let path = path.as_ref();
+    let source = read(path)?;
+
+    let mut parser = ObjParser::new(path, material);
+    for (index, line) in source.lines().enumerate() {
+        parser.line(index + 1, line)?;
+    }
+
+    Ok(TriangleMesh::new(parser.finish()))
+}
+
+fn read(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path)
+        .map_err(|why| io::Error::new(why.kind(), format!("{}: {}", path.display(), why)))
+}
+
+/// Keyword and values of a line, without its comment. `None` for empty lines.
+fn words(line: &str) -> Option<(&str, Vec<&str>)> {
+    let line = line.split('#').next().unwrap_or("");
+    let mut words = line.split_whitespace();
+    let keyword = words.next()?;
+    Some((keyword, words.collect()))
+}
+
+fn numbers(values: &[&str], line: usize) -> Result<Vec<f32>, MeshError> {
+    values
+        .iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| MeshError::new(line, format!("expected a number, got `{}`", value)))
+        })
+        .collect()
+}
+
+/// Indices of the position, texture coordinates and normal of a vertex of a face
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct ObjParser<'a> {
+    path: &'a Path,
+    mesh: Mesh,
+    /// Materials of the MTL files, by name
+    library: HashMap<String, Material>,
+    /// Index in the materials of the mesh of the ones faces use
+    used_materials: HashMap<String, usize>,
+    /// Whether an MTL file couldn't be loaded, so its materials can't be found either
+    missing_library: bool,
+    material: usize,
+    smoothing_group: u32,
+    /// Faces in a smoothing group that don't have normals, and their group
+    smooth_faces: Vec<(usize, u32)>,
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(path: &'a Path, material: Material) -> Self {
+        Self {
+            path,
+            mesh: Mesh {
+                materials: vec![material],
+                ..Mesh::default()
+            },
+            library: HashMap::new(),
+            used_materials: HashMap::new(),
+            missing_library: false,
+            material: 0,
+            smoothing_group: 0,
+            smooth_faces: Vec::new(),
+        }
+    }
+
+    fn directory(&self) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .to_path_buf()
+    }
+
+    fn line(&mut self, line: usize, text: &str) -> io::Result<()> {
+        let (keyword, values) = match words(text) {
+            Some(words) => words,
+            None => return Ok(()),
+        };
+
+        let result = match keyword {
+            "v" => self.vector(&values, line).map(|position| {
+                self.mesh.positions.push(position);
+            }),
+            "vn" => self.vector(&values, line).map(|normal| {
+                self.mesh.normals.push(normal);
+            }),
+            "vt" => self.uv(&values, line),
+            "f" => self.face(&values, line),
+            "s" => self.smoothing_group(&values, line),
+            "mtllib" => {
+                // Names can have spaces
+                let path = self.directory().join(values.join(" "));
+                match load_mtl(&path) {
+                    Ok(library) => self.library.extend(library),
+                    // The faces of its materials get the default one instead
+                    Err(why) => {
+                        self.missing_library = true;
+                        self.mesh.warnings.push(format!(
+                            "{}:{}: using the default material, couldn't load {}",
+                            self.path.display(),
+                            line,
+                            why
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            "usemtl" => self.use_material(&values.join(" "), line),
+            // Lines, points, groups and the rest don't change the triangles
+            _ => Ok(()),
+        };
+        result.map_err(|error| error.at(self.path))
+    }
+
+    fn vector(&self, values: &[&str], line: usize) -> Result<Vec3, MeshError> {
+        // Positions can have a weight or a color after them, which are ignored
+        match numbers(values, line)?.as_slice() {
+            [x, y, z, ..] => Ok(Vec3::new(*x, *y, *z)),
+            _ => Err(MeshError::new(line, "expected 3 numbers")),
+        }
+    }
+
+    fn uv(&mut self, values: &[&str], line: usize) -> Result<(), MeshError> {
+        let uv = match numbers(values, line)?.as_slice() {
+            [u] => (*u, 0.),
+            [u, v] | [u, v, _] => (*u, *v),
+            _ => return Err(MeshError::new(line, "`vt` takes 1 to 3 numbers")),
+        };
+        self.mesh.uvs.push(uv);
+        Ok(())
+    }
+
+    fn smoothing_group(&mut self, values: &[&str], line: usize) -> Result<(), MeshError> {
+        self.smoothing_group = match values {
+            ["off"] => 0,
+            ["on"] => 1,
+            [group] => group.parse().map_err(|_| {
+                MeshError::new(line, format!("unknown smoothing group `{}`", group))
+            })?,
+            _ => return Err(MeshError::new(line, "`s` takes a smoothing group, or off")),
+        };
+        Ok(())
+    }
+
+    fn use_material(&mut self, name: &str, line: usize) -> Result<(), MeshError> {
+        if let Some(index) = self.used_materials.get(name) {
+            self.material = *index;
+            return Ok(());
+        }
+
+        match self.library.get(name) {
+            Some(material) => {
+                self.mesh.materials.push(material.clone());
+                self.material = self.mesh.materials.len() - 1;
+            }
+            // It could be in the library that's missing, which already has a warning
+            None if self.missing_library => self.material = 0,
+            None => return Err(MeshError::new(line, format!("unknown material `{}`", name))),
+        }
+        self.used_materials.insert(name.to_string(), self.material);
+        Ok(())
+    }
+
+    /// `f v/vt/vn ...`, where the texture coordinates and normals are optional, and polygons
+    /// are split into a fan of triangles
+    fn face(&mut self, values: &[&str], line: usize) -> Result<(), MeshError> {
+        if values.len() < 3 {
+            return Err(MeshError::new(line, "faces need at least 3 vertices"));
+        }
+
+        let mut corners: Vec<Corner> = Vec::with_capacity(values.len());
+        for value in values {
+            let mut indices = value.split('/');
+            let position = indices.next().unwrap_or("");
+            let uv = indices.next().filter(|uv| !uv.is_empty());
+            let normal = indices.next().filter(|normal| !normal.is_empty());
+            if indices.next().is_some() {
+                return Err(MeshError::new(line, format!("unknown vertex `{}`", value)));
+            }
+
+            corners.push((
+                index(position, self.mesh.positions.len(), line)?,
+                uv.map(|uv| index(uv, self.mesh.uvs.len(), line))
+                    .transpose()?,
+                normal
+                    .map(|normal| index(normal, self.mesh.normals.len(), line))
+                    .transpose()?,
+            ));
+        }
+
+        // Only used if every corner has them
+        let has_uvs = corners.iter().all(|corner| corner.1.is_some());
+        let has_normals = corners.iter().all(|corner| corner.2.is_some());
+        for i in 1..corners.len() - 1 {
+            let triangle = [corners[0], corners[i], corners[i + 1]];
+            let indices = |index: fn(&Corner) -> Option<usize>| {
+                Some([
+                    index(&triangle[0])?,
+                    index(&triangle[1])?,
+                    index(&triangle[2])?,
+                ])
+            };
+
+            if !has_normals && self.smoothing_group != 0 {
+                self.smooth_faces
+                    .push((self.mesh.faces.len(), self.smoothing_group));
+            }
+            self.mesh.faces.push(MeshFace {
+                positions: [triangle[0].0, triangle[1].0, triangle[2].0],
+                normals: indices(|corner| corner.2).filter(|_| has_normals),
+                uvs: indices(|corner| corner.1).filter(|_| has_uvs),
+                colors: None,
+                material: self.material,
+            });
+        }
+        Ok(())
+    }
+
+    /// Gives the smooth faces the average of the normals of the faces in their group around
+    /// each vertex, weighted by their area
+    fn finish(mut self) -> Mesh {
+        let mesh = &mut self.mesh;
+        let mut vertex_normals: HashMap<(usize, u32), Vec3> = HashMap::new();
+        for &(face, group) in &self.smooth_faces {
+            let [a, b, c] = mesh.faces[face].positions;
+            let normal = (mesh.positions[b] - mesh.positions[a])
+                .cross(&(mesh.positions[c] - mesh.positions[a]));
+            for &position in &[a, b, c] {
+                *vertex_normals
+                    .entry((position, group))
+                    .or_insert_with(Vec3::zeros) += normal;
+            }
+        }
+
+        let mut normal_indices = HashMap::new();
+        for &(face, group) in &self.smooth_faces {
+            let positions = mesh.faces[face].positions;
+            let mut normals = [0; 3];
+            for (normal, &position) in normals.iter_mut().zip(&positions) {
+                *normal = *normal_indices.entry((position, group)).or_insert_with(|| {
+                    mesh.normals
+                        .push(vertex_normals[&(position, group)].normalize());
+                    mesh.normals.len() - 1
+                });
+            }
+            mesh.faces[face].normals = Some(normals);
+        }
+
+        self.mesh
+    }
+}
+
+/// Index of a vertex, starting from 1, or from -1 for the last one
+fn index(text: &str, count: usize, line: usize) -> Result<usize, MeshError> {
+    let index: isize = text
+        .parse()
+        .map_err(|_| MeshError::new(line, format!("expected an index, got `{}`", text)))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as isize + index
+    };
+    if index == 0 || resolved < 0 || resolved >= count as isize {
+        return Err(MeshError::new(
+            line,
+            format!("index {} is out of the {} there are", index, count),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// Properties of an MTL material, that get turned into the closest `Material`
+struct MtlMaterial {
+    diffuse: Color,
+    diffuse_map: Option<Texture>,
+    specular: Color,
+    specular_exponent: f32,
+    emission: Color,
+    emission_map: Option<Texture>,
+    refraction_index: Option<f32>,
+    dissolve: f32,
+    illumination: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Color::from(0.8),
+            diffuse_map: None,
+            specular: Color::zeros(),
+            specular_exponent: 0.,
+            emission: Color::zeros(),
+            emission_map: None,
+            refraction_index: None,
+            dissolve: 1.,
+            illumination: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn property(
+        &mut self,
+        keyword: &str,
+        values: &[&str],
+        line: usize,
+        directory: &Path,
+    ) -> Result<(), MeshError> {
+        let color = || match numbers(values, line)?.as_slice() {
+            [value] => Ok(Color::from(*value)),
+            [r, g, b] => Ok(Color::new(*r, *g, *b)),
+            _ => Err(MeshError::new(line, "expected a color, as 3 numbers or 1")),
+        };
+        let number = || match numbers(values, line)?.as_slice() {
+            [value] => Ok(*value),
+            _ => Err(MeshError::new(line, "expected a number")),
+        };
+        // Maps can have options before the image, which is always last
+        let image = || {
+            let name = values
+                .last()
+                .ok_or_else(|| MeshError::new(line, "expected the path of an image"))?;
+            let path = directory.join(name);
+            texture::load_image(&path).map_err(|why| {
+                MeshError::new(line, format!("couldn't load {}: {}", path.display(), why))
+            })
+        };
+
+        match keyword {
+            "Kd" => self.diffuse = color()?,
+            "Ks" => self.specular = color()?,
+            "Ke" => self.emission = color()?,
+            "Ns" => self.specular_exponent = number()?,
+            "Ni" => self.refraction_index = Some(number()?),
+            "d" => self.dissolve = number()?,
+            "Tr" => self.dissolve = 1. - number()?,
+            "illum" => self.illumination = number()? as u32,
+            "map_Kd" => self.diffuse_map = Some(image()?),
+            "map_Ke" => self.emission_map = Some(image()?),
+            // Ambient color, bump maps and the rest aren't used
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Emissive materials are lights, transparent ones glass, and the ones with reflections
+    /// and no diffuse color metal. The rest are diffuse.
+    fn material(&self) -> Material {
+        if let Some(emission) = &self.emission_map {
+            return Material::DiffuseLightTexture(emission.clone());
+        }
+        if !is_black(self.emission) {
+            return Material::DiffuseLight(self.emission);
+        }
+
+        let transparent = self.dissolve < 1. || matches!(self.illumination, 4 | 6 | 7 | 9);
+        if transparent {
+            return Material::Dielectric(self.refraction_index.filter(|n| *n > 1.).unwrap_or(1.5));
+        }
+
+        let reflective = matches!(self.illumination, 3 | 5)
+            || (is_black(self.diffuse) && self.diffuse_map.is_none());
+        if reflective && !is_black(self.specular) {
+            // Roughness that matches the Phong exponent
+            let fuzz = (2. / (self.specular_exponent.max(0.) + 2.)).sqrt();
+            return Material::Metal(self.specular, fuzz.clamp(0., 1.));
+        }
+
+        match &self.diffuse_map {
+            Some(diffuse) => Material::LambertianTexture(diffuse.clone()),
+            None => Material::Lambertian(self.diffuse),
+        }
+    }
+}
+
+fn is_black(color: Color) -> bool {
+    color.length_squared() == 0.
+}
+
+fn load_mtl(path: &Path) -> io::Result<HashMap<String, Material>> {
+    let source = read(path)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let (keyword, values) = match words(text) {
+            Some(words) => words,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material.material());
+            }
+            current = Some((values.join(" "), MtlMaterial::default()));
+            continue;
+        }
+
+        match &mut current {
+            Some((_, material)) => material.property(keyword, &values, line, directory),
+            None => Err(MeshError::new(line, "expected `newmtl` first")),
+        }
+        .map_err(|error| error.at(path))?;
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material.material());
+    }
+    Ok(materials)
+}
@@ -0,0 +1,482 @@
+//! Stanford PLY meshes, in ASCII or binary, which is what scanners usually give
+
+use super::{invalid_data, MeshError};
+use crate::{hittable::mesh::*, material::*, vec3::*};
+use std::{convert::TryInto, fs, io, path::Path};
+
+/// Loads a PLY file, with the normals, colors and texture coordinates its vertices have.
+/// Polygons are split into triangles, and colors replace the albedo of the material when
+/// it's lambertian.
+pub fn load_ply(path: impl AsRef<Path>, material: Material) -> io::Result<TriangleMesh> {
+    let path = path.as_ref();
+    let data = fs::read(path)
+        .map_err(|why| io::Error::new(why.kind(), format!("{}: {}", path.display(), why)))?;
+
+    let header = Header::parse(&data).map_err(|error| error.at(path))?;
+    let mut body = match header.format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(&data[header.body..])
+                .map_err(|_| invalid_data(path, "the ASCII data isn't text"))?;
+            Body::Ascii {
+                text,
+                position: 0,
+                line: header.lines + 1,
+            }
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Body::Binary {
+            data: &data[header.body..],
+            position: 0,
+            big_endian: header.format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut mesh = Mesh {
+        materials: vec![material],
+        ..Mesh::default()
+    };
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, &mut body, &mut mesh),
+            "face" => read_faces(element, &mut body, &mut mesh),
+            // Edges, materials and the rest are skipped
+            _ => (0..element.count).try_for_each(|i| {
+                for property in &element.properties {
+                    body.property(property)
+                        .map_err(|why| format!("{} {}: {}", element.name, i, why))?;
+                }
+                Ok(())
+            }),
+        }
+        .map_err(|message| body.error(path, message))?;
+    }
+
+    mesh.validate().map_err(|why| invalid_data(path, why))?;
+    Ok(TriangleMesh::new(mesh))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::Int8),
+            "uchar" | "uint8" => Some(Self::UInt8),
+            "short" | "int16" => Some(Self::Int16),
+            "ushort" | "uint16" => Some(Self::UInt16),
+            "int" | "int32" => Some(Self::Int32),
+            "uint" | "uint32" => Some(Self::UInt32),
+            "float" | "float32" => Some(Self::Float32),
+            "double" | "float64" => Some(Self::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    /// Value that means full intensity for a color of this type
+    fn color_scale(self) -> f64 {
+        match self {
+            Self::Float32 | Self::Float64 => 1.,
+            Self::UInt16 => 65535.,
+            _ => 255.,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PropertyKind {
+    Scalar(Scalar),
+    /// A count, and that many items
+    List {
+        count: Scalar,
+        item: Scalar,
+    },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name.as_str()))
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    /// Lines of the header, for the lines of errors in ASCII data
+    lines: usize,
+    /// Where the data starts, after the header
+    body: usize,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, MeshError> {
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        let mut position = 0;
+        let mut line = 0;
+
+        loop {
+            line += 1;
+            let end = match data[position..].iter().position(|byte| *byte == b'\n') {
+                Some(end) => position + end,
+                None => {
+                    // At the last line when the file ends with a newline
+                    let last_line = if position == data.len() {
+                        line - 1
+                    } else {
+                        line
+                    };
+                    return Err(MeshError::new(
+                        last_line,
+                        "the header doesn't have an end_header",
+                    ));
+                }
+            };
+            let text = std::str::from_utf8(&data[position..end])
+                .map_err(|_| MeshError::new(line, "the header isn't text"))?;
+            position = end + 1;
+
+            let words: Vec<&str> = text.split_whitespace().collect();
+            if line == 1 {
+                if words != ["ply"] {
+                    return Err(MeshError::new(
+                        line,
+                        "not a PLY file, it doesn't start with ply",
+                    ));
+                }
+                continue;
+            }
+
+            match words.as_slice() {
+                ["format", kind, _version] => {
+                    format = Some(match *kind {
+                        "ascii" => Format::Ascii,
+                        "binary_little_endian" => Format::BinaryLittleEndian,
+                        "binary_big_endian" => Format::BinaryBigEndian,
+                        _ => {
+                            return Err(MeshError::new(line, format!("unknown format `{}`", kind)))
+                        }
+                    })
+                }
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| {
+                        MeshError::new(
+                            line,
+                            format!("expected a count of {}, got `{}`", name, count),
+                        )
+                    })?,
+                    properties: Vec::new(),
+                }),
+                ["property", rest @ ..] => {
+                    let element = elements.last_mut().ok_or_else(|| {
+                        MeshError::new(line, "properties have to be after their element")
+                    })?;
+                    let scalar = |name: &str| {
+                        Scalar::parse(name)
+                            .ok_or_else(|| MeshError::new(line, format!("unknown type `{}`", name)))
+                    };
+                    let (kind, name) = match rest {
+                        ["list", count, item, name] => {
+                            let count = scalar(count)?;
+                            if matches!(count, Scalar::Float32 | Scalar::Float64) {
+                                return Err(MeshError::new(line, "list counts have to be integers"));
+                            }
+                            (
+                                PropertyKind::List {
+                                    count,
+                                    item: scalar(item)?,
+                                },
+                                name,
+                            )
+                        }
+                        [kind, name] => (PropertyKind::Scalar(scalar(kind)?), name),
+                        _ => {
+                            return Err(MeshError::new(
+                                line,
+                                "expected `property <type> <name>` or `property list <count type> <type> <name>`",
+                            ))
+                        }
+                    };
+                    element.properties.push(Property {
+                        name: name.to_string(),
+                        kind,
+                    });
+                }
+                ["end_header"] => break,
+                _ => {
+                    return Err(MeshError::new(
+                        line,
+                        format!("unknown header line `{}`", text.trim()),
+                    ))
+                }
+            }
+        }
+
+        Ok(Self {
+            format: format
+                .ok_or_else(|| MeshError::new(line, "the header doesn't have a format"))?,
+            elements,
+            lines: line,
+            body: position,
+        })
+    }
+}
+
+/// Data after the header, read one value at a time
+enum Body<'a> {
+    /// Numbers separated by whitespace
+    Ascii {
+        text: &'a str,
+        position: usize,
+        line: usize,
+    },
+    Binary {
+        data: &'a [u8],
+        position: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Self::Ascii {
+                text,
+                position,
+                line,
+            } => {
+                let bytes = text.as_bytes();
+                while *position < bytes.len() && bytes[*position].is_ascii_whitespace() {
+                    if bytes[*position] == b'\n' {
+                        *line += 1;
+                    }
+                    *position += 1;
+                }
+                let start = *position;
+                while *position < bytes.len() && !bytes[*position].is_ascii_whitespace() {
+                    *position += 1;
+                }
+
+                let word = &text[start..*position];
+                if word.is_empty() {
+                    return Err("the file ends before all the data".to_string());
+                }
+                word.parse()
+                    .map_err(|_| format!("expected a number, got `{}`", word))
+            }
+            Self::Binary {
+                data,
+                position,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                let bytes = data
+                    .get(*position..*position + size)
+                    .ok_or_else(|| "the file ends before all the data".to_string())?;
+                *position += size;
+
+                let mut little_endian = [0; 8];
+                little_endian[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    little_endian[..size].reverse();
+                }
+                let value = match scalar {
+                    Scalar::Int8 => little_endian[0] as i8 as f64,
+                    Scalar::UInt8 => little_endian[0] as f64,
+                    Scalar::Int16 => {
+                        i16::from_le_bytes([little_endian[0], little_endian[1]]) as f64
+                    }
+                    Scalar::UInt16 => {
+                        u16::from_le_bytes([little_endian[0], little_endian[1]]) as f64
+                    }
+                    Scalar::Int32 => {
+                        i32::from_le_bytes(little_endian[..4].try_into().unwrap()) as f64
+                    }
+                    Scalar::UInt32 => {
+                        u32::from_le_bytes(little_endian[..4].try_into().unwrap()) as f64
+                    }
+                    Scalar::Float32 => {
+                        f32::from_le_bytes(little_endian[..4].try_into().unwrap()) as f64
+                    }
+                    Scalar::Float64 => f64::from_le_bytes(little_endian),
+                };
+                Ok(value)
+            }
+        }
+    }
+
+    /// Reads a scalar property, or the items of a list
+    fn property(&mut self, property: &Property) -> Result<Vec<f64>, String> {
+        match property.kind {
+            PropertyKind::Scalar(scalar) => Ok(vec![self.read(scalar)?]),
+            PropertyKind::List { count, item } => {
+                let count = self.read(count)?;
+                if count < 0. || count.fract() != 0. {
+                    return Err(format!("a list can't have {} items", count));
+                }
+                (0..count as usize).map(|_| self.read(item)).collect()
+            }
+        }
+    }
+
+    /// Error from reading, with the line in ASCII files
+    fn error(&self, path: &Path, message: String) -> io::Error {
+        match self {
+            Self::Ascii { line, .. } => MeshError::new(*line, message).at(path),
+            Self::Binary { .. } => invalid_data(path, message),
+        }
+    }
+}
+
+fn read_vertices(element: &Element, body: &mut Body, mesh: &mut Mesh) -> Result<(), String> {
+    let find = |names: [&[&str]; 3]| -> Option<[usize; 3]> {
+        Some([
+            element.property(names[0])?,
+            element.property(names[1])?,
+            element.property(names[2])?,
+        ])
+    };
+    let positions =
+        find([&["x"], &["y"], &["z"]]).ok_or_else(|| "vertices need x, y and z".to_string())?;
+    let normals = find([&["nx"], &["ny"], &["nz"]]);
+    let colors = find([
+        &["red", "r", "diffuse_red"],
+        &["green", "g", "diffuse_green"],
+        &["blue", "b", "diffuse_blue"],
+    ]);
+    let uvs = element
+        .property(&["u", "s", "texture_u", "texture_s"])
+        .zip(element.property(&["v", "t", "texture_v", "texture_t"]));
+    let color_scale = colors.map(|colors| match element.properties[colors[0]].kind {
+        PropertyKind::Scalar(scalar) => scalar.color_scale(),
+        PropertyKind::List { .. } => 1.,
+    });
+
+    let mut values = vec![0.; element.properties.len()];
+    for i in 0..element.count {
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            // Lists aren't used in vertices
+            *value = body
+                .property(property)
+                .map_err(|why| format!("vertex {}: {}", i, why))?
+                .first()
+                .copied()
+                .unwrap_or(0.);
+        }
+
+        let vector =
+            |[x, y, z]: [usize; 3]| Vec3::new(values[x] as f32, values[y] as f32, values[z] as f32);
+        mesh.positions.push(vector(positions));
+        if let Some(normals) = normals {
+            mesh.normals.push(vector(normals));
+        }
+        if let (Some(colors), Some(scale)) = (colors, color_scale) {
+            mesh.colors.push(vector(colors) / scale as f32);
+        }
+        if let Some((u, v)) = uvs {
+            mesh.uvs.push((values[u] as f32, values[v] as f32));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(element: &Element, body: &mut Body, mesh: &mut Mesh) -> Result<(), String> {
+    let indices = element
+        .property(&["vertex_indices", "vertex_index"])
+        .filter(|index| matches!(element.properties[*index].kind, PropertyKind::List { .. }))
+        .ok_or_else(|| "faces need a vertex_indices list".to_string())?;
+
+    for i in 0..element.count {
+        let mut polygon = Vec::new();
+        for (index, property) in element.properties.iter().enumerate() {
+            let values = body
+                .property(property)
+                .map_err(|why| format!("face {}: {}", i, why))?;
+            if index == indices {
+                polygon = values;
+            }
+        }
+
+        if polygon.len() < 3 {
+            return Err(format!(
+                "face {} has {} vertices, it needs at least 3",
+                i,
+                polygon.len()
+            ));
+        }
+        if let Some(index) = polygon
+            .iter()
+            .find(|index| **index < 0. || index.fract() != 0.)
+        {
+            return Err(format!("face {} has the vertex {}", i, index));
+        }
+
+        // In a fan, as polygons are convex
+        for corner in 1..polygon.len() - 1 {
+            let triangle = [
+                polygon[0] as usize,
+                polygon[corner] as usize,
+                polygon[corner + 1] as usize,
+            ];
+            mesh.faces.push(MeshFace {
+                positions: triangle,
+                normals: per_vertex(&mesh.normals, triangle),
+                uvs: per_vertex(&mesh.uvs, triangle),
+                colors: per_vertex(&mesh.colors, triangle),
+                material: 0,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The normals, colors and texture coordinates of a PLY are per vertex, so they use the
+/// indices of the positions when there are any
+fn per_vertex<T>(buffer: &[T], triangle: [usize; 3]) -> Option<[usize; 3]> {
+    if buffer.is_empty() {
+        None
+    } else {
+        Some(triangle)
+    }
+}
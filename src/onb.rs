@@ -1,5 +1,6 @@
 use crate::vec3::*;
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct ONB {
     pub u: Vec3,
     pub v: Vec3,
@@ -1,7 +1,7 @@
-use crate::{hittable::*, onb::*, vec3::*};
-use rand::Rng;
+use crate::{hittable::*, onb::*, sampler::*, vec3::*};
 use std::f32::consts::PI;
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum PDF<'a> {
     Cosine(ONB),
     Hittable {
@@ -15,7 +15,7 @@ pub enum PDF<'a> {
 }
 
 impl<'a> PDF<'a> {
-    pub fn value(&self, direction: Vec3) -> f32 {
+    pub fn value(&self, direction: Vec3, sampler: &mut Sampler) -> f32 {
         match self {
             Self::Cosine(uvw) => {
                 let cosine = direction.normalize().dot(&uvw.w);
@@ -25,22 +25,183 @@ impl<'a> PDF<'a> {
                     cosine / PI
                 }
             }
-            Self::Hittable { hittable, origin } => hittable.pdf_value(origin, &direction),
-            Self::Mixture { p, q } => 0.5 * p.value(direction) + 0.5 * q.value(direction),
+            Self::Hittable { hittable, origin } => hittable.pdf_value(origin, &direction, sampler),
+            Self::Mixture { p, q } => {
+                0.5 * p.value(direction, sampler) + 0.5 * q.value(direction, sampler)
+            }
         }
     }
 
-    pub fn generate(&self) -> Vec3 {
+    pub fn generate(&self, sampler: &mut Sampler) -> Vec3 {
         match self {
-            Self::Cosine(uvw) => uvw.local(Vec3::random_cosine_direction()),
-            Self::Hittable { hittable, origin } => hittable.random(origin),
+            Self::Cosine(uvw) => uvw.local(Vec3::cosine_direction(sampler.get_2d())),
+            Self::Hittable { hittable, origin } => hittable.random(origin, sampler),
             Self::Mixture { p, q } => {
-                if rand::thread_rng().gen::<f32>() < 0.5 {
-                    p.generate()
+                if sampler.get_1d() < 0.5 {
+                    p.generate(sampler)
                 } else {
-                    q.generate()
+                    q.generate(sampler)
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chi_square::*,
+        hittable::{
+            cube::*, cylinder::*, flip_face::*, pyramid::*, rectangle::*, sphere::*, transform::*,
+            triangle::*,
+        },
+        hittable_list::*,
+        material::*,
+        matrix::*,
+    };
+
+    fn material() -> Material {
+        Material::Lambertian(Color::from(0.5))
+    }
+
+    /// Checks that the PDF integrates to 1 and generates directions that follow it
+    fn check(pdf: &PDF) {
+        let test = ChiSquareTest::default();
+
+        let integral = test.integral(|direction, sampler| pdf.value(direction, sampler));
+        assert!(
+            (integral - 1.).abs() < 0.02,
+            "the PDF integrates to {} over the sphere",
+            integral
+        );
+
+        test.run(
+            |sampler| pdf.generate(sampler),
+            |direction, sampler| pdf.value(direction, sampler),
+        )
+        .unwrap();
+    }
+
+    fn check_hittable(hittable: &dyn Hittable) {
+        check(&PDF::Hittable {
+            hittable,
+            origin: Point::new(0.1, -0.2, 0.3),
+        });
+    }
+
+    #[test]
+    fn cosine() {
+        check(&PDF::Cosine(ONB::build_from_w(Vec3::new(0.3, 1., -0.2))));
+    }
+
+    #[test]
+    fn sphere() {
+        check_hittable(&Sphere::new(Point::new(0.5, 1., -2.), 1.2, material()));
+    }
+
+    #[test]
+    fn rectangles() {
+        for plane in &[Plane::XY, Plane::XZ, Plane::YZ] {
+            check_hittable(&Rect::new(*plane, (-1., 1.5), (-0.5, 1.), 1.5, material()));
+        }
+    }
+
+    #[test]
+    fn flipped_rectangle() {
+        let rect = Rect::new(Plane::XZ, (-1., 1.), (-1., 1.), 1., material());
+        check_hittable(&FlipFace::new(Box::new(rect)));
+    }
+
+    #[test]
+    fn cube() {
+        check_hittable(&Cube::new(
+            Point::new(-1., 1., -1.),
+            Point::new(1., 2., 0.5),
+            material(),
+        ));
+    }
+
+    #[test]
+    fn triangle() {
+        check_hittable(&Triangle::new(
+            Point::new(-1., 1., -1.),
+            Point::new(2., 1.5, -0.5),
+            Point::new(0., 1., 1.5),
+            material(),
+        ));
+    }
+
+    #[test]
+    fn pyramid() {
+        check_hittable(&Pyramid::new(
+            Point::new(0., 3., 0.),
+            Point::new(-1., 1., -1.),
+            Point::new(1., 1., -1.),
+            Point::new(1., 1., 1.),
+            Point::new(-1., 1., 1.),
+            material(),
+        ));
+    }
+
+    #[test]
+    fn cylinder() {
+        let cylinder = Cylinder::new(
+            Point::new(0., -1., -3.),
+            Point::new(1., 1., -2.5),
+            1.5,
+            material(),
+        );
+        check_hittable(&cylinder);
+        check_hittable(&Cylinder {
+            caps: false,
+            ..cylinder
+        });
+    }
+
+    #[test]
+    fn transform() {
+        // Stretching changes the solid angle the directions cover, unlike moving and turning
+        let matrix = Matrix::translation(Vec3::new(0.5, 1.5, -2.))
+            * Matrix::rotation(Vec3::new(1., 1., 0.), 40.)
+            * Matrix::scaling(Vec3::new(1.8, 0.6, 1.));
+        check_hittable(&Transform::new(
+            Box::new(Sphere::new(Point::zeros(), 1., material())),
+            matrix,
+        ));
+        check_hittable(&Transform::new(
+            Box::new(Rect::new(Plane::XZ, (-1., 1.), (-1., 1.), 0., material())),
+            matrix,
+        ));
+    }
+
+    #[test]
+    fn hittable_list() {
+        let mut list = HittableList::new();
+        list.add(Box::new(Sphere::new(
+            Point::new(0., 2., 0.),
+            0.8,
+            material(),
+        )));
+        list.add(Box::new(Rect::new(
+            Plane::XY,
+            (-1., 1.),
+            (-1., 1.),
+            -1.5,
+            material(),
+        )));
+        check_hittable(&list);
+    }
+
+    #[test]
+    fn mixture() {
+        let sphere = Sphere::new(Point::new(0., 2., 0.), 0.8, material());
+        check(&PDF::Mixture {
+            p: Box::new(PDF::Cosine(ONB::build_from_w(Vec3::new(0., 1., 0.)))),
+            q: Box::new(PDF::Hittable {
+                hittable: &sphere,
+                origin: Point::zeros(),
+            }),
+        });
+    }
+}
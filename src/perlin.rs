@@ -1,7 +1,9 @@
-use crate::vec3::*;
+use crate::{sampler::*, vec3::*};
 use rand::Rng;
 
 const POINT_COUNT: usize = 256;
+
+/// Smooth random noise, for the noise, turbulence and marble textures
 pub struct Perlin {
     rand_vec: Vec<Vec3>,
     perm_x: Vec<usize>,
@@ -10,19 +12,24 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new() -> Perlin {
+    pub fn new<R: Rng + ?Sized>(rng: &mut R) -> Perlin {
         let rand_vec: Vec<Vec3> = (0..POINT_COUNT)
-            .map(|_| Vec3::random_in_unit_sphere())
+            .map(|_| Vec3::random_in_unit_sphere(rng))
             .collect();
 
         Perlin {
             rand_vec,
-            perm_x: perlin_generate_perm(),
-            perm_y: perlin_generate_perm(),
-            perm_z: perlin_generate_perm(),
+            perm_x: perlin_generate_perm(rng),
+            perm_y: perlin_generate_perm(rng),
+            perm_z: perlin_generate_perm(rng),
         }
     }
 
+    /// Noise made from its own random numbers, so it's the same every time
+    pub fn with_seed(seed: u64) -> Perlin {
+        Perlin::new(&mut Sampler::new(seed, 0))
+    }
+
     pub fn noise(&self, point: Point) -> f32 {
         let ijk = Point {
             x: point.x.floor(),
@@ -39,14 +46,13 @@ impl Perlin {
         for di in 0..2 {
             for dj in 0..2 {
                 for dk in 0..2 {
-                    let x_index = ((ijk.x as i16 + di as i16) & 255) as usize;
-                    let y_index = ((ijk.y as i16 + dj as i16) & 255) as usize;
-                    let z_index = ((ijk.z as i16 + dk as i16) & 255) as usize;
-
-                    corners[di][dj][dk] = self.rand_vec[(self.perm_x[x_index]
-                        ^ self.perm_y[y_index]
-                        ^ self.perm_z[z_index])
-                        as usize];
+                    // Far away points saturate the cast, and wrap around like in release
+                    let x_index = ((ijk.x as i16).wrapping_add(di as i16) & 255) as usize;
+                    let y_index = ((ijk.y as i16).wrapping_add(dj as i16) & 255) as usize;
+                    let z_index = ((ijk.z as i16).wrapping_add(dk as i16) & 255) as usize;
+
+                    corners[di][dj][dk] = self.rand_vec
+                        [self.perm_x[x_index] ^ self.perm_y[y_index] ^ self.perm_z[z_index]];
                 }
             }
         }
@@ -69,10 +75,9 @@ impl Perlin {
     }
 }
 
-fn perlin_generate_perm() -> Vec<usize> {
+fn perlin_generate_perm<R: Rng + ?Sized>(rng: &mut R) -> Vec<usize> {
     let mut vec: Vec<usize> = (0..POINT_COUNT).collect();
 
-    let mut rng = rand::thread_rng();
     for i in (1..POINT_COUNT).rev() {
         vec.swap(i, rng.gen_range(0, i));
     }
@@ -93,7 +98,7 @@ fn trilinear_interpolation(corners: &Corners, uvw: Vec3) -> f32 {
                 let one_minus_ijk = Vec3::ones() - ijk;
 
                 accum += (ijk * uvw + one_minus_ijk * one_minus_uvw).multiply_components()
-                    * (uvw - ijk).dot(&corner);
+                    * (uvw - ijk).dot(corner);
             }
         }
     }
@@ -0,0 +1,476 @@
+use crate::{
+    adaptive::*,
+    aov::*,
+    framebuffer::*,
+    renderer::{render_pass, RenderOutput},
+    sampler::*,
+    scenes::*,
+    session::*,
+    tiles::*,
+    vec3::*,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Everything that has been rendered so far in a pixel
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PixelState {
+    /// Sum of the colors of every sample
+    pub sum: [f64; 3],
+    pub samples: u32,
+    pub stats: PixelStats,
+    /// Sum of the AOVs of every sample
+    pub aov: AOVSample,
+}
+
+impl PixelState {
+    pub fn add(&mut self, color: Color) {
+        self.sum[0] += color.x as f64;
+        self.sum[1] += color.y as f64;
+        self.sum[2] += color.z as f64;
+        self.samples += 1;
+    }
+
+    /// Whether the pixel can't take more samples, as it has converged or has the most it
+    /// can have
+    pub fn done(&self, world: &World) -> bool {
+        let converged = match &world.adaptive {
+            Some(adaptive) => adaptive.converged(&self.stats),
+            None => false,
+        };
+        converged || self.samples >= world.max_samples()
+    }
+
+    pub fn color(&self) -> Color {
+        if self.samples == 0 {
+            return Color::zeros();
+        }
+        let samples = self.samples as f64;
+        Color::new(
+            (self.sum[0] / samples) as f32,
+            (self.sum[1] / samples) as f32,
+            (self.sum[2] / samples) as f32,
+        )
+    }
+}
+
+/// Samples accumulated over the passes of a render. It has everything needed to carry on
+/// rendering, so it can be saved to a checkpoint and resumed later, giving the same image
+/// as if the render had never stopped.
+pub struct Accumulation {
+    pub width: usize,
+    pub height: usize,
+    /// Seed and sampler of the render, which have to stay the same for the samples to follow
+    /// the same sequence after resuming
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    /// Samples per pixel of the world it was rendered for. Resuming can ask for more, to add
+    /// samples to a finished render, but not for fewer.
+    pub samples_per_pixel: u16,
+    /// Whether the AOVs are being accumulated too
+    pub aovs: bool,
+    /// Rows go from top to bottom
+    pub pixels: Vec<PixelState>,
+}
+
+impl Accumulation {
+    pub fn new(world: &World) -> Self {
+        let width = world.image_width as usize;
+        let height = world.image_height();
+
+        Self {
+            width,
+            height,
+            seed: world.seed,
+            sampler: world.sampler,
+            samples_per_pixel: world.samples_per_pixel,
+            aovs: world.needs_aovs(),
+            pixels: vec![PixelState::default(); width * height],
+        }
+    }
+
+    /// Samples taken in every pixel so far
+    pub fn samples(&self) -> u64 {
+        self.pixels.iter().map(|pixel| pixel.samples as u64).sum()
+    }
+
+    pub fn average_samples(&self) -> f32 {
+        self.samples() as f32 / self.pixels.len() as f32
+    }
+
+    /// Most samples the world takes in the whole image, `samples_per_pixel` for every pixel.
+    /// With adaptive sampling, the ones that converged pixels don't take go to the noisy ones.
+    pub fn total_samples(&self, world: &World) -> u64 {
+        self.pixels.len() as u64 * world.samples_per_pixel as u64
+    }
+
+    /// Samples every pixel that isn't done can take in the next pass, up to `most`, so the
+    /// image doesn't go over `total_samples`. It's 0 once there's nothing left to render.
+    pub fn pass_samples(&self, world: &World, most: u32) -> u32 {
+        let left = self.total_samples(world).saturating_sub(self.samples());
+        let open = self
+            .pixels
+            .iter()
+            .filter(|pixel| !pixel.done(world))
+            .count() as u64;
+        if open == 0 {
+            return 0;
+        }
+        (left / open).min(most as u64) as u32
+    }
+
+    /// Copy of the pixels of a tile, row by row from the top
+    pub fn tile_pixels(&self, tile: &Tile) -> Vec<PixelState> {
+        tile.pixel_indices(self.width)
+            .map(|index| self.pixels[index])
+            .collect()
+    }
+
+    pub fn set_tile_pixels(&mut self, tile: &Tile, pixels: &[PixelState]) {
+        for (index, pixel) in tile.pixel_indices(self.width).zip(pixels) {
+            self.pixels[index] = *pixel;
+        }
+    }
+
+    /// Checks that the world can carry on rendering into this accumulation
+    pub fn check(&self, world: &World) -> io::Result<()> {
+        let width = world.image_width as usize;
+        let height = world.image_height();
+        if (self.width, self.height) != (width, height) {
+            return Err(invalid_data(format!(
+                "the checkpoint is {}x{}, but the world renders at {}x{}",
+                self.width, self.height, width, height
+            )));
+        }
+        if (self.seed, self.sampler) != (world.seed, world.sampler) {
+            return Err(invalid_data(format!(
+                "the checkpoint was rendered with seed {} and the {:?} sampler, but the world \
+                 has seed {} and the {:?} sampler",
+                self.seed, self.sampler, world.seed, world.sampler
+            )));
+        }
+        if world.samples_per_pixel < self.samples_per_pixel {
+            return Err(invalid_data(format!(
+                "the checkpoint was rendered with {} samples per pixel, but the world only \
+                 takes {}",
+                self.samples_per_pixel, world.samples_per_pixel
+            )));
+        }
+        if world.needs_aovs() && !self.aovs {
+            return Err(invalid_data(
+                "the world needs AOVs, but the checkpoint didn't keep them".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the image, and the AOVs and denoising if the world asks for them
+    pub fn output(&self, world: &World) -> RenderOutput {
+        let colors = self.pixels.iter().map(PixelState::color).collect();
+        let mut image = Framebuffer::from_pixels(self.width, self.height, colors);
+        image.display = world.display;
+
+        let aovs = if self.aovs {
+            let samples: Vec<AOVSample> = self
+                .pixels
+                .iter()
+                .map(|pixel| pixel.aov.average(pixel.samples.max(1)))
+                .collect();
+            Some(AOVBuffers::from_samples(self.width, self.height, &samples))
+        } else {
+            None
+        };
+
+        if let (Some(denoiser), Some(aovs)) = (&world.denoise, &aovs) {
+            image = denoiser.denoise(&image, aovs);
+        }
+
+        RenderOutput {
+            image,
+            aovs: aovs.filter(|_| world.aovs),
+            average_samples: self.average_samples(),
+        }
+    }
+
+    /// Writes a checkpoint. It goes to a temporary file that then replaces `path`,
+    /// so a crash while saving doesn't lose the previous checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&temporary, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.width as u32).to_le_bytes())?;
+        writer.write_all(&(self.height as u32).to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.samples_per_pixel.to_le_bytes())?;
+        writer.write_all(&[sampler_to_byte(self.sampler), self.aovs as u8])?;
+
+        for pixel in &self.pixels {
+            writer.write_all(&pixel.samples.to_le_bytes())?;
+            for sum in &pixel.sum {
+                writer.write_all(&sum.to_le_bytes())?;
+            }
+            writer.write_all(&pixel.stats.count.to_le_bytes())?;
+            writer.write_all(&pixel.stats.mean.to_le_bytes())?;
+            writer.write_all(&pixel.stats.m2.to_le_bytes())?;
+
+            if self.aovs {
+                let aov = &pixel.aov;
+                for vector in &[aov.albedo, aov.normal, aov.position] {
+                    for value in &[vector.x, vector.y, vector.z] {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                }
+                for value in &[aov.depth, aov.u, aov.v] {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                writer.write_all(&aov.object_id.to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a sunray checkpoint".to_string()));
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "checkpoint version {} isn't supported, expected {}",
+                version, VERSION
+            )));
+        }
+
+        let width = read_u32(reader)? as usize;
+        let height = read_u32(reader)? as usize;
+        // The size comes from the file, so it's checked before allocating anything for it
+        let pixel_count = checked_pixel_count(width, height).ok_or_else(|| {
+            invalid_data(format!("the checkpoint is {}x{}, too big", width, height))
+        })?;
+        let seed = read_u64(reader)?;
+        let samples_per_pixel = read_u16(reader)?;
+        let mut flags = [0; 2];
+        reader.read_exact(&mut flags)?;
+        let sampler = sampler_from_byte(flags[0])?;
+        let aovs = flags[1] != 0;
+
+        // A truncated file runs out before the pixels do, so it doesn't get all of them
+        // allocated up front either
+        let mut pixels = Vec::with_capacity(pixel_count.min(1 << 20));
+        for _ in 0..pixel_count {
+            let mut pixel = PixelState {
+                samples: read_u32(reader)?,
+                ..PixelState::default()
+            };
+            for sum in &mut pixel.sum {
+                *sum = f64::from_bits(read_u64(reader)?);
+            }
+            pixel.stats.count = read_u32(reader)?;
+            pixel.stats.mean = read_f32(reader)?;
+            pixel.stats.m2 = read_f32(reader)?;
+
+            if aovs {
+                let aov = &mut pixel.aov;
+                for vector in &mut [&mut aov.albedo, &mut aov.normal, &mut aov.position] {
+                    vector.x = read_f32(reader)?;
+                    vector.y = read_f32(reader)?;
+                    vector.z = read_f32(reader)?;
+                }
+                aov.depth = read_f32(reader)?;
+                aov.u = read_f32(reader)?;
+                aov.v = read_f32(reader)?;
+                aov.object_id = read_u32(reader)?;
+            }
+
+            pixels.push(pixel);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            seed,
+            sampler,
+            samples_per_pixel,
+            aovs,
+            pixels,
+        })
+    }
+}
+
+const MAGIC: &[u8; 8] = b"SUNRAYCK";
+const VERSION: u32 = 2;
+
+fn sampler_to_byte(sampler: SamplerKind) -> u8 {
+    match sampler {
+        SamplerKind::Independent => 0,
+        SamplerKind::Stratified => 1,
+        SamplerKind::Halton => 2,
+        SamplerKind::Sobol => 3,
+        SamplerKind::BlueNoise => 4,
+    }
+}
+
+fn sampler_from_byte(byte: u8) -> io::Result<SamplerKind> {
+    match byte {
+        0 => Ok(SamplerKind::Independent),
+        1 => Ok(SamplerKind::Stratified),
+        2 => Ok(SamplerKind::Halton),
+        3 => Ok(SamplerKind::Sobol),
+        4 => Ok(SamplerKind::BlueNoise),
+        _ => Err(invalid_data(format!(
+            "unknown sampler {} in checkpoint",
+            byte
+        ))),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
+
+/// Renders in passes of a few samples per pixel, until the world's sample count is reached,
+/// saving checkpoints along the way
+pub struct Progressive {
+    /// Samples per pixel added by every pass
+    pub pass_samples: u32,
+    /// Where to save the checkpoints, if anywhere
+    pub checkpoint: Option<PathBuf>,
+    /// Minimum time between checkpoints. There's always one after the last pass.
+    pub checkpoint_interval: Duration,
+}
+
+impl Default for Progressive {
+    fn default() -> Self {
+        Self {
+            pass_samples: 16,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Progressive {
+    /// Carries on rendering into `accumulation`, which can be a new one or one loaded from a
+    /// checkpoint. Raising the world's samples per pixel adds samples to a finished render,
+    /// although with the stratified and blue noise samplers, which spread the samples
+    /// depending on how many there are, that's not the same as rendering them all at once.
+    pub fn render(
+        &self,
+        world: &World,
+        accumulation: &mut Accumulation,
+    ) -> io::Result<RenderOutput> {
+        let session = RenderSession::new();
+        self.render_with(
+            world,
+            accumulation,
+            &session,
+            |_: &Tile, _: &Framebuffer| {},
+        )
+    }
+
+    /// Like `render`, calling `on_tile` with the pixels of every tile as soon as a pass
+    /// finishes it. If `session` gets cancelled, it stops after the current tiles, and the
+    /// checkpoint has everything up to there.
+    pub fn render_with<F>(
+        &self,
+        world: &World,
+        accumulation: &mut Accumulation,
+        session: &RenderSession,
+        on_tile: F,
+    ) -> io::Result<RenderOutput>
+    where
+        F: Fn(&Tile, &Framebuffer) + Sync,
+    {
+        self.render_with_passes(world, accumulation, session, on_tile, |_: &Accumulation| {})
+    }
+
+    /// Like `render_with`, also calling `on_pass` with everything rendered so far after
+    /// every pass, to show the image as it gets better
+    pub fn render_with_passes<F, P>(
+        &self,
+        world: &World,
+        accumulation: &mut Accumulation,
+        session: &RenderSession,
+        on_tile: F,
+        mut on_pass: P,
+    ) -> io::Result<RenderOutput>
+    where
+        F: Fn(&Tile, &Framebuffer) + Sync,
+        P: FnMut(&Accumulation),
+    {
+        accumulation.check(world)?;
+        accumulation.samples_per_pixel = world.samples_per_pixel;
+        session.start(accumulation.total_samples(world), accumulation.samples());
+        let mut last_checkpoint = Instant::now();
+        loop {
+            let samples = accumulation.pass_samples(world, self.pass_samples.max(1));
+            if session.is_cancelled()
+                || samples == 0
+                || render_pass(world, accumulation, samples, session, &on_tile) == 0
+            {
+                break;
+            }
+
+            on_pass(accumulation);
+
+            if let Some(path) = &self.checkpoint {
+                if last_checkpoint.elapsed() >= self.checkpoint_interval {
+                    accumulation.save(path)?;
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+        session.finish();
+
+        if let Some(path) = &self.checkpoint {
+            accumulation.save(path)?;
+        }
+
+        Ok(accumulation.output(world))
+    }
+}
@@ -1,78 +1,238 @@
-use rand::Rng;
 use rayon::prelude::*;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
 
 use crate::{
+    aov::*,
+    framebuffer::*,
     hit_record::*,
     hittable::*,
-    hittable_list::*,
     material::ScatterRecord,
     pdf::*,
+    progressive::*,
     ray::*,
+    sampler::*,
     scenes::{self, *},
+    session::*,
+    stats::*,
+    tiles::*,
     vec3::*,
 };
 
-pub fn get_image_ppm(scene: Scene) -> String {
-    // World
-    let World {
-        hittables,
-        camera,
-        lights,
-        background_color_top,
-        background_color_bottom,
-        samples_per_pixel,
-        image_width,
-        aspect_ratio,
-        max_depth,
-    } = scenes::generate_world(scene);
+/// Sample dimensions used by the camera ray: position in the pixel (2), lens (2) and time (1)
+const CAMERA_DIMENSIONS: u32 = 5;
+/// Sample dimensions that every bounce has, to choose between the lights and the material (1),
+/// choose a light (1), the direction (2) and whether to reflect or refract (1)
+const BOUNCE_DIMENSIONS: u32 = 5;
 
-    let image_height = (image_width as f32 / aspect_ratio) as u16;
+pub struct RenderOutput {
+    pub image: Framebuffer,
+    /// Only there if the world asked for them
+    pub aovs: Option<AOVBuffers>,
+    /// Samples per pixel that were actually taken, which with adaptive sampling
+    /// can be different from the world's `samples_per_pixel`
+    pub average_samples: f32,
+}
 
-    println!(
-        "Rendering {}x{} with {} samples",
-        image_width, image_height, samples_per_pixel
+pub fn render(scene: Scene) -> RenderOutput {
+    let world = scenes::generate_world(scene);
+    render_world(&world)
+}
+
+pub fn render_world(world: &World) -> RenderOutput {
+    render_world_with(world, &RenderSession::new(), |_: &Tile, _: &Framebuffer| {})
+}
+
+/// Renders the world, calling `on_tile` with the pixels of every tile as soon as it's
+/// finished, so they can be shown or sent somewhere before the whole image is done.
+/// It's called from the rendering threads, as the tiles finish in roughly the world's
+/// tile order.
+///
+/// `session` keeps count of the progress, and can cancel the render from another thread.
+pub fn render_world_with<F>(world: &World, session: &RenderSession, on_tile: F) -> RenderOutput
+where
+    F: Fn(&Tile, &Framebuffer) + Sync,
+{
+    let mut accumulation = Accumulation::new(world);
+    session.start(accumulation.total_samples(world), 0);
+    // With adaptive sampling, every pixel first takes the least samples it needs to know if
+    // it's converged. Then the pixels that aren't get passes of twice as many samples, for
+    // as long as there are samples left in the budget of the image.
+    let mut pass_samples = match &world.adaptive {
+        Some(adaptive) => (adaptive.min_samples as u32).max(1),
+        None => world.max_samples(),
+    };
+    loop {
+        let samples = accumulation.pass_samples(world, pass_samples);
+        if session.is_cancelled() || samples == 0 {
+            break;
+        }
+        render_pass(world, &mut accumulation, samples, session, &on_tile);
+        pass_samples = pass_samples.saturating_mul(2);
+    }
+    session.finish();
+    accumulation.output(world)
+}
+
+/// Adds up to `samples` samples to every pixel that isn't done yet, tile by tile, and
+/// returns how many were taken in total. It stops early if the session gets cancelled.
+pub fn render_pass<F>(
+    world: &World,
+    accumulation: &mut Accumulation,
+    samples: u32,
+    session: &RenderSession,
+    on_tile: &F,
+) -> u64
+where
+    F: Fn(&Tile, &Framebuffer) + Sync,
+{
+    let tiles = tiles(
+        accumulation.width,
+        accumulation.height,
+        world.tile_size as usize,
+        world.tile_order,
     );
+    // Every tile renders into its own copy of its pixels, which go back once they're all done
+    let tile_pixels: Vec<Mutex<Vec<PixelState>>> = tiles
+        .iter()
+        .map(|tile| Mutex::new(accumulation.tile_pixels(tile)))
+        .collect();
+
+    // Rayon would split the tiles in halves, so instead every thread takes the next one,
+    // which starts them in order
+    let next_tile = AtomicUsize::new(0);
+    let taken = {
+        let accumulation = &*accumulation;
+        (0..rayon::current_num_threads())
+            .into_par_iter()
+            .map(|_| {
+                let mut taken = 0;
+                loop {
+                    if session.is_cancelled() {
+                        break taken;
+                    }
+                    let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    let tile = match tiles.get(index) {
+                        Some(tile) => tile,
+                        None => break taken,
+                    };
 
-    let string = (0..image_height)
-        .into_par_iter()
-        .rev()
-        .map(|j| {
-            println!("Remaining scanlines: {}", j);
-            (0..image_width)
-                .into_par_iter()
-                .map(|i| {
-                    let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-
-                    let mut rng = rand::thread_rng();
-                    for _s in 0..samples_per_pixel {
-                        let u = (i as f32 + rng.gen::<f32>()) / (image_width - 1) as f32;
-                        let v = (j as f32 + rng.gen::<f32>()) / (image_height - 1) as f32;
-
-                        let ray = camera.ray(u, v);
-                        pixel_color += ray_color(
-                            &ray,
-                            background_color_top,
-                            background_color_bottom,
-                            &hittables,
-                            &lights,
-                            max_depth,
-                        );
+                    let mut pixels = tile_pixels[index].lock().unwrap();
+                    let tile_taken =
+                        render_tile(world, accumulation, tile, &mut pixels, samples, session);
+                    // Tiles that were already done haven't changed
+                    if tile_taken > 0 {
+                        on_tile(tile, &tile_framebuffer(world, tile, &pixels));
                     }
+                    taken += tile_taken;
+                }
+            })
+            .sum()
+    };
 
-                    pixel_color.write_color(samples_per_pixel)
-                })
-                .collect()
-        })
-        .map(|array: Vec<String>| array.join(""))
-        .collect::<Vec<String>>()
-        .join("");
-
-    println!(
-        "Rendered {}x{} with {} samples",
-        image_width, image_height, samples_per_pixel
-    );
+    for (tile, pixels) in tiles.iter().zip(tile_pixels) {
+        accumulation.set_tile_pixels(tile, &pixels.into_inner().unwrap());
+    }
 
-    format!("P3\n{} {}\n255\n{}", image_width, image_height, string)
+    taken
+}
+
+/// Adds up to `samples` samples to the `pixels` of a tile, taken out of `accumulation`,
+/// and counts the work in the session
+fn render_tile(
+    world: &World,
+    accumulation: &Accumulation,
+    tile: &Tile,
+    pixels: &mut [PixelState],
+    samples: u32,
+    session: &RenderSession,
+) -> u64 {
+    // Whatever the thread counted before isn't part of the tile
+    take_thread_stats();
+
+    let mut taken = 0;
+    for (row, row_pixels) in pixels.chunks_mut(tile.width).enumerate() {
+        // Rows go from top to bottom, but the camera's v goes up
+        let j = accumulation.height - 1 - (tile.y + row);
+
+        let mut row_taken = 0;
+        for (column, pixel) in row_pixels.iter_mut().enumerate() {
+            if session.is_cancelled() {
+                break;
+            }
+
+            let mut sampler = Sampler::for_pixel(
+                accumulation.sampler,
+                accumulation.seed,
+                tile.x + column,
+                j,
+                world.max_samples(),
+            );
+            row_taken += render_pixel(world, &mut sampler, pixel, samples, accumulation.aovs);
+        }
+
+        // The session gets the counts every row, so the progress moves along with high
+        // sample counts too
+        let stats = RenderStats {
+            samples: row_taken as u64,
+            ..take_thread_stats()
+        };
+        session.add(row_taken as u64, &stats);
+        taken += row_taken as u64;
+    }
+
+    taken
+}
+
+/// What the pixels of a tile look like so far
+fn tile_framebuffer(world: &World, tile: &Tile, pixels: &[PixelState]) -> Framebuffer {
+    let colors = pixels.iter().map(PixelState::color).collect();
+    let mut framebuffer = Framebuffer::from_pixels(tile.width, tile.height, colors);
+    framebuffer.display = world.display;
+    framebuffer
+}
+
+/// Adds up to `samples` samples to the pixel of the sampler, stopping early if it's done
+fn render_pixel(
+    world: &World,
+    sampler: &mut Sampler,
+    pixel: &mut PixelState,
+    samples: u32,
+    aovs: bool,
+) -> u32 {
+    let image_width = world.image_width as usize;
+    let image_height = world.image_height();
+    let (i, j) = sampler.pixel();
+    let end = (pixel.samples + samples).min(world.max_samples());
+
+    let mut taken = 0;
+    while pixel.samples < end {
+        if let Some(adaptive) = &world.adaptive {
+            if adaptive.converged(&pixel.stats) {
+                break;
+            }
+        }
+
+        sampler.start_sample(pixel.samples);
+        let (jitter_u, jitter_v) = sampler.get_2d();
+        // At least 1, so images 1 pixel wide or high still cover the whole view
+        let u = (i as f32 + jitter_u) / (image_width.max(2) - 1) as f32;
+        let v = (j as f32 + jitter_v) / (image_height.max(2) - 1) as f32;
+
+        let ray = world.camera.ray(u, v, sampler);
+        let aov = if aovs { Some(&mut pixel.aov) } else { None };
+        let color = camera_ray_color(&ray, world, aov, sampler).nan_to_zero();
+
+        pixel.add(color);
+        if world.adaptive.is_some() {
+            pixel.stats.add(color);
+        }
+        taken += 1;
+    }
+
+    taken
 }
 
 use wasm_bindgen::prelude::*;
@@ -95,112 +255,141 @@ pub fn get_image_ppm_single_threaded(
 ) -> String {
     log("getting started");
 
-    // World
-    let World {
-        hittables,
-        camera,
-        lights,
-        background_color_top: _,
-        background_color_bottom: _,
-        samples_per_pixel: _,
-        image_width: _,
-        aspect_ratio: _,
-        max_depth,
-    } = scenes::generate_world(scene);
-
-    let background_color_top = Color::from_array(color_top);
-    let background_color_bottom = Color::from_array(color_bottom);
-
-    let image_height = (image_width as f32 / aspect_ratio) as u16;
-
-    let string = (0..image_height)
-        .into_iter()
-        .rev()
-        .map(|j| {
-            log(&*format!("Remaining scanlines: {}", j));
-            (0..image_width)
-                .into_iter()
-                .map(|i| {
-                    let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-
-                    let mut rng = rand::thread_rng();
-                    for _s in 0..samples_per_pixel {
-                        let u = (i as f32 + rng.gen::<f32>()) / (image_width - 1) as f32;
-                        let v = (j as f32 + rng.gen::<f32>()) / (image_height - 1) as f32;
-
-                        let ray = camera.ray(u, v);
-                        pixel_color += ray_color(
-                            &ray,
-                            background_color_top,
-                            background_color_bottom,
-                            &hittables,
-                            &lights,
-                            max_depth,
-                        );
-                    }
+    let world = World {
+        background_color_top: Color::from_array(color_top),
+        background_color_bottom: Color::from_array(color_bottom),
+        samples_per_pixel,
+        image_width: image_width as u16,
+        aspect_ratio,
+        aovs: false,
+        ..scenes::generate_world(scene)
+    };
+
+    let mut accumulation = Accumulation::new(&world);
+    let tiles = tiles(
+        accumulation.width,
+        accumulation.height,
+        world.tile_size as usize,
+        world.tile_order,
+    );
+    let max_samples = world.max_samples();
+    let session = RenderSession::new();
+
+    for (index, tile) in tiles.iter().enumerate() {
+        let mut pixels = accumulation.tile_pixels(tile);
+        render_tile(
+            &world,
+            &accumulation,
+            tile,
+            &mut pixels,
+            max_samples,
+            &session,
+        );
+        accumulation.set_tile_pixels(tile, &pixels);
+        log(&*format!("Rendered tile {} of {}", index + 1, tiles.len()));
+    }
 
-                    pixel_color.write_color(samples_per_pixel)
-                })
-                .collect()
-        })
-        .map(|array: Vec<String>| array.join(""))
-        .collect::<Vec<String>>()
-        .join("");
+    accumulation.output(&world).image.to_ppm_ascii()
+}
 
-    format!("P3\n{} {}\n255\n{}", image_width, image_height, string)
+fn background(ray: &Ray, world: &World) -> Color {
+    let t = 0.5 * (ray.direction.normalize().y + 1.0);
+    (1.0 - t) * world.background_color_bottom + t * world.background_color_top
 }
 
-fn ray_color(
+/// Traces a ray from the camera, and stores what it hits first in the AOVs
+fn camera_ray_color(
     ray: &Ray,
-    background_color_top: Color,
-    background_color_bottom: Color,
-    hittables: &dyn Hittable,
-    lights: &HittableList,
-    depth: u16,
+    world: &World,
+    aov: Option<&mut AOVSample>,
+    sampler: &mut Sampler,
 ) -> Color {
+    let aov = match aov {
+        Some(aov) => aov,
+        None => return ray_color(ray, world, world.max_depth, sampler),
+    };
+
+    if world.max_depth == 0 {
+        return Color::zeros();
+    }
+
+    count_ray();
+    let mut hit_record = HitRecord::default();
+    if world
+        .hittables
+        .hit(ray, 0.001, f32::INFINITY, &mut hit_record, sampler)
+    {
+        let albedo = hit_record.material.albedo(&hit_record);
+        aov.accumulate(&AOVSample::hit(&hit_record, albedo));
+
+        shade(ray, &hit_record, world, world.max_depth, sampler)
+    } else {
+        let background = background(ray, world);
+        aov.accumulate(&AOVSample::miss(background));
+
+        background
+    }
+}
+
+fn ray_color(ray: &Ray, world: &World, depth: u16, sampler: &mut Sampler) -> Color {
     // If we've exceeded the ray bounce limit, no more light is gathered.
     if depth == 0 {
         return Color::zeros();
     }
 
+    count_ray();
     let mut hit_record = HitRecord::default();
-    if !hittables.hit(&ray, 0.001, f32::INFINITY, &mut hit_record) {
-        let t = 0.5 * (ray.direction.normalize().y + 1.0);
-        // return (1.0 - t) * Color::new(0.05, 0.05, 0.2) + t * Color::zeros();
-        return (1.0 - t) * background_color_bottom + t * background_color_top;
+    if !world
+        .hittables
+        .hit(ray, 0.001, f32::INFINITY, &mut hit_record, sampler)
+    {
+        return background(ray, world);
     }
 
+    shade(ray, &hit_record, world, depth, sampler)
+}
+
+fn shade(
+    ray: &Ray,
+    hit_record: &HitRecord,
+    world: &World,
+    depth: u16,
+    sampler: &mut Sampler,
+) -> Color {
+    let bounce = (world.max_depth - depth) as u32;
+    sampler.set_dimension(CAMERA_DIMENSIONS + bounce * BOUNCE_DIMENSIONS);
+
     let emitted = hit_record.material.emitted(
         ray,
-        &hit_record,
+        hit_record,
         hit_record.u,
         hit_record.v,
         hit_record.point,
     );
 
-    if let Some(srec) = hit_record.material.scatter(ray, &hit_record) {
+    if let Some(srec) = hit_record.material.scatter(ray, hit_record, sampler) {
         match srec {
             ScatterRecord::Scatter { pdf, attenuation } => {
                 // If there are no lights, we have a normal pdf
-                let p = if lights.is_empty() {
+                let p = if world.lights.is_empty() {
                     pdf
                 } else {
                     let light_pdf = PDF::Hittable {
-                        hittable: lights,
+                        hittable: &world.lights,
                         origin: hit_record.point,
                     };
                     PDF::Mixture {
-                        p: box light_pdf,
-                        q: box pdf,
+                        p: Box::new(light_pdf),
+                        q: Box::new(pdf),
                     }
                 };
 
                 let scattered = Ray {
                     origin: hit_record.point,
-                    direction: p.generate(),
+                    direction: p.generate(sampler),
                     time: ray.time,
                 };
-                let pdf_val = p.value(scattered.direction);
+                let pdf_val = p.value(scattered.direction, sampler);
 
                 // Early return to skip a ray bounce
                 if attenuation.x == 0. && attenuation.y == 0. && attenuation.z == 0. {
@@ -211,15 +400,8 @@ fn ray_color(
                     + attenuation
                         * hit_record
                             .material
-                            .scattering_pdf(ray, &hit_record, &scattered)
-                        * ray_color(
-                            &scattered,
-                            background_color_top,
-                            background_color_bottom,
-                            hittables,
-                            lights,
-                            depth - 1,
-                        )
+                            .scattering_pdf(ray, hit_record, &scattered)
+                        * ray_color(&scattered, world, depth - 1, sampler)
                         / pdf_val
             }
             ScatterRecord::Specular {
@@ -231,15 +413,7 @@ fn ray_color(
                     return emitted;
                 }
 
-                attenuation
-                    * ray_color(
-                        &specular_ray,
-                        background_color_top,
-                        background_color_bottom,
-                        hittables,
-                        lights,
-                        depth - 1,
-                    )
+                attenuation * ray_color(&specular_ray, world, depth - 1, sampler)
             }
         }
     } else {
@@ -0,0 +1,288 @@
+use crate::low_discrepancy::*;
+use rand::{Error, Rng, RngCore};
+use std::str::FromStr;
+
+/// How the samples of a pixel are placed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    /// Every sample is independent of the others, like plain `rand`
+    Independent,
+    /// The domain of every dimension is split in as many strata as samples, and each
+    /// sample goes in a different one
+    Stratified,
+    /// Owen scrambled Halton sequence, with a different prime per dimension
+    Halton,
+    /// Owen scrambled Sobol sequence. Dimensions are made from shuffled copies of the
+    /// first two (Burley, Practical Hash-based Owen Scrambling), so there's no limit to them,
+    /// and every power of two of samples is well distributed.
+    #[default]
+    Sobol,
+    /// Sobol sequence where neighbouring pixels share the samples, so the remaining noise is
+    /// blue (high frequency), which is less visible and easier to denoise. Works best with
+    /// a power of two of samples.
+    BlueNoise,
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "independent" | "random" => Ok(Self::Independent),
+            "stratified" => Ok(Self::Stratified),
+            "halton" => Ok(Self::Halton),
+            "sobol" => Ok(Self::Sobol),
+            "blue-noise" | "bluenoise" | "zsobol" => Ok(Self::BlueNoise),
+            _ => Err(format!(
+                "unknown sampler {}, expected independent, stratified, halton, sobol or blue-noise",
+                s
+            )),
+        }
+    }
+}
+
+/// Random numbers for the renderer.
+///
+/// `get_1d` and `get_2d` give the samples of the current dimension, from the sequence of
+/// the sampler's kind, so that the samples of a pixel cover the domain evenly. Anything else,
+/// like rejection sampling, uses it as a regular `Rng`, which is a PCG32 generator.
+///
+/// Every sample of every pixel is seeded from the render seed, the pixel and the sample
+/// index, so images don't depend on how the work is split between threads.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    kind: SamplerKind,
+    rng: Pcg32,
+    seed: u64,
+    pixel: (u32, u32),
+    /// Samples that are going to be taken in the pixel
+    samples: u32,
+    sample: u32,
+    dimension: u32,
+}
+
+impl Sampler {
+    /// Independent sampler that isn't tied to a pixel
+    pub fn new(seed: u64, stream: u64) -> Self {
+        Self {
+            kind: SamplerKind::Independent,
+            rng: Pcg32::new(seed, stream),
+            seed,
+            pixel: (0, 0),
+            samples: 1,
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    pub fn for_pixel(kind: SamplerKind, seed: u64, x: usize, y: usize, samples: u32) -> Self {
+        let mut sampler = Self {
+            kind,
+            rng: Pcg32::new(seed, 0),
+            seed,
+            pixel: (x as u32, y as u32),
+            samples: samples.max(1),
+            sample: 0,
+            dimension: 0,
+        };
+        sampler.start_sample(0);
+        sampler
+    }
+
+    pub fn pixel(&self) -> (usize, usize) {
+        (self.pixel.0 as usize, self.pixel.1 as usize)
+    }
+
+    /// Goes to sample `sample` of the pixel, starting from the first dimension
+    pub fn start_sample(&mut self, sample: u32) {
+        let stream = splitmix64(self.pixel_hash() ^ splitmix64(sample as u64));
+        self.rng = Pcg32::new(splitmix64(self.seed ^ stream), stream);
+        self.sample = sample;
+        self.dimension = 0;
+    }
+
+    /// Jumps to a dimension. Every part of the renderer gets its own dimensions, so that
+    /// the samples that decide the same thing come from the same sequence.
+    pub fn set_dimension(&mut self, dimension: u32) {
+        self.dimension = dimension;
+    }
+
+    pub fn get_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let hash = self.hash(dimension);
+
+        match self.kind {
+            SamplerKind::Independent => self.rng.gen(),
+            SamplerKind::Stratified => {
+                let stratum = permutation_element(self.sample, self.samples, hash as u32);
+                (stratum as f32 + self.rng.gen::<f32>()) / self.samples as f32
+            }
+            SamplerKind::Halton if (dimension as usize) < PRIMES.len() => {
+                halton_sample(dimension as usize, self.sample, hash as u32)
+            }
+            SamplerKind::Halton => self.rng.gen(),
+            SamplerKind::Sobol => {
+                let index = owen_scramble(self.sample, hash as u32);
+                sobol_sample(index as u64, 0, (hash >> 32) as u32)
+            }
+            SamplerKind::BlueNoise => sobol_sample(self.zsobol_index(dimension), 0, hash as u32),
+        }
+    }
+
+    pub fn get_2d(&mut self) -> (f32, f32) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+        let hash = self.hash(dimension);
+
+        match self.kind {
+            SamplerKind::Independent => (self.rng.gen(), self.rng.gen()),
+            SamplerKind::Stratified => {
+                let columns = ((self.samples as f32).sqrt() as u32).max(1);
+                let rows = self.samples.div_ceil(columns);
+                let stratum = permutation_element(self.sample, columns * rows, hash as u32);
+
+                (
+                    ((stratum % columns) as f32 + self.rng.gen::<f32>()) / columns as f32,
+                    ((stratum / columns) as f32 + self.rng.gen::<f32>()) / rows as f32,
+                )
+            }
+            SamplerKind::Halton if (dimension as usize + 1) < PRIMES.len() => (
+                halton_sample(dimension as usize, self.sample, hash as u32),
+                halton_sample(dimension as usize + 1, self.sample, (hash >> 32) as u32),
+            ),
+            SamplerKind::Halton => (self.rng.gen(), self.rng.gen()),
+            SamplerKind::Sobol => {
+                let index = owen_scramble(self.sample, hash as u32) as u64;
+                let seed = mix_bits(hash);
+                (
+                    sobol_sample(index, 0, seed as u32),
+                    sobol_sample(index, 1, (seed >> 32) as u32),
+                )
+            }
+            SamplerKind::BlueNoise => {
+                let index = self.zsobol_index(dimension);
+                (
+                    sobol_sample(index, 0, hash as u32),
+                    sobol_sample(index, 1, (hash >> 32) as u32),
+                )
+            }
+        }
+    }
+
+    fn pixel_hash(&self) -> u64 {
+        splitmix64(((self.pixel.1 as u64) << 32) | self.pixel.0 as u64)
+    }
+
+    /// Seed of the scrambling of a dimension. The blue noise sampler uses the same one
+    /// for every pixel, as they all share the sequence.
+    fn hash(&self, dimension: u32) -> u64 {
+        let pixel = match self.kind {
+            SamplerKind::BlueNoise => 0,
+            _ => self.pixel_hash(),
+        };
+        mix_bits(self.seed ^ pixel ^ splitmix64(dimension as u64))
+    }
+
+    fn zsobol_index(&self, dimension: u32) -> u64 {
+        let log2_samples = 32 - (self.samples - 1).leading_zeros();
+        zsobol_index(
+            encode_morton(self.pixel.0, self.pixel.1),
+            self.sample,
+            log2_samples,
+            dimension,
+        )
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl RngCore for Sampler {
+    #[inline(always)]
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// PCG32, which is small, fast and reproducible: the same seed always gives the same
+/// numbers on every platform
+#[derive(Clone, Debug)]
+struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+impl Pcg32 {
+    fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    #[inline(always)]
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    #[inline(always)]
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Mixes the bits of a number, so that close inputs give unrelated outputs
+pub fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,1032 @@
+//! Text format for scenes, so they can be written without recompiling. A scene file is
+//! a list of statements, one per line (or separated by `;`), each a keyword followed by
+//! its values and optionally a block of statements between braces. `#` starts a comment.
+//!
+//! ```text
+//! camera { look-from 278 278 -800; look-at 278 278 0; fov 40 }
+//! render { width 600; spp 100; tonemap aces }
+//! background 0.7 0.8 1
+//!
+//! texture marble marble 4
+//! material white lambertian 0.73
+//!
+//! rect { plane yz; a 0 555; b 0 555; k 555; material lambertian 0.12 0.45 0.15 }
+//! translate {
+//!     offset 265 0 295
+//!     rotate-y { angle 15; cube { min 0 0 0; max 165 330 165; material white } }
+//! }
+//! light { flip-face { rect { plane xz; a 213 343; b 227 332; k 550; material diffuse-light 15 } } }
+//! ```
+//!
+//! Vectors and colors are 3 numbers, or 1 for all the components, and numbers can be
+//! written as fractions like `225/255`. Textures and materials have to be defined before
+//! they are used, and materials can also be written in place, like `material metal 0.8 0`.
+//!
+//! The objects are `sphere`, `moving-sphere`, `rect`, `cube`, `pyramid`, `triangle`,
+//! `cylinder`, `mesh`, which loads an .obj or .ply file like
+//! `mesh { file bunny.obj; material white }`, and `sdf`, which has a tree of shapes
+//! (`sphere`, `box`, `donut`, `mandelbox`...) and operations (`union`, `subtraction`,
+//! `intersection`, `repetition`, `scale`).
+//! `translate`, `rotate-y`, `rotate` (around any `axis`), `scale`, `flip-face` and `medium`
+//! wrap the objects in their block, `group` and `bvh` join them, and the ones in a `light`
+//! block are also sampled like lights.
+//!
+//! Objects that are repeated many times can be defined once, as a prototype, and placed
+//! with instances, which share its memory. An instance has transforms, done in the order
+//! they're written, and a material that replaces the ones of the prototype:
+//!
+//! ```text
+//! prototype tree { mesh { file tree.obj; material leaves } }
+//! instance tree { scale 2; rotate-y 30; translate 10 0 -5 }
+//! instance tree { rotate 1 0 0 10; translate 20 0 3; material lambertian 0.8 0.5 0.1 }
+//! ```
+//! Every built-in scene is also in `scenes/`, written in this format.
+
+mod syntax;
+
+use self::syntax::*;
+use crate::{
+    adaptive::*,
+    bvh::*,
+    camera::*,
+    denoise::*,
+    hittable::{
+        cube::*, cylinder::*, flip_face::*, instance::*, medium::*, mesh::*, moving_sphere::*,
+        pyramid::*, rectangle::*, sdf::*, sphere::*, transform::*, triangle::*, *,
+    },
+    hittable_list::*,
+    material::*,
+    matrix::*,
+    mesh_file::*,
+    perlin::*,
+    scenes::World,
+    texture::{self, Texture},
+    vec3::*,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Error in a scene file, with the line it's in
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl SceneError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// Builds the world described by a scene. Images and meshes are loaded relative to the
+/// current directory.
+pub fn parse_scene(source: &str) -> Result<World, SceneError> {
+    SceneBuilder::new(PathBuf::new()).build(source)
+}
+
+/// Loads a scene file. Images and meshes are loaded relative to the folder the file is in,
+/// and errors start with the path of the file.
+pub fn load_scene(path: impl AsRef<Path>) -> io::Result<World> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|why| io::Error::new(why.kind(), format!("{}: {}", path.display(), why)))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+
+    SceneBuilder::new(directory)
+        .build(&source)
+        .map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path.display(), error.line, error.message),
+            )
+        })
+}
+
+struct SceneBuilder {
+    /// Images and meshes are relative to it
+    directory: PathBuf,
+    world: World,
+    camera: CameraSettings,
+    textures: HashMap<String, Texture>,
+    materials: HashMap<String, Material>,
+    prototypes: HashMap<String, Arc<dyn Hittable>>,
+    /// Objects are built through `&self`, so their warnings go in a cell
+    warnings: RefCell<Vec<String>>,
+}
+
+impl SceneBuilder {
+    fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            world: World::default(),
+            camera: CameraSettings::default(),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            prototypes: HashMap::new(),
+            warnings: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn build(mut self, source: &str) -> Result<World, SceneError> {
+        // The camera goes first wherever it's written, as BVHs are built over its shutter time
+        let statements = parse(source)?;
+        let (cameras, others): (Vec<_>, Vec<_>) = statements
+            .iter()
+            .partition(|statement| statement.keyword == "camera");
+        for statement in cameras.into_iter().chain(others) {
+            self.statement(statement)?;
+        }
+
+        // The camera is built once the whole file is read, as it needs the aspect ratio
+        self.world.camera = self.camera.build(self.world.aspect_ratio);
+        self.world.number_objects();
+        self.world.warnings = self.warnings.into_inner();
+
+        Ok(self.world)
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), SceneError> {
+        match statement.keyword.as_str() {
+            "camera" => self.camera(statement),
+            "render" => self.render_settings(statement),
+            "background" => self.background(statement),
+            "texture" => {
+                let (name, texture) = self.texture_definition(statement)?;
+                define(&mut self.textures, name, texture, statement)
+            }
+            "material" => {
+                let arguments = &statement.arguments;
+                if arguments.len() < 2 || statement.block.is_some() {
+                    return Err(statement.error("expected `material <name> <kind> <values>`"));
+                }
+                let material = self.material(statement, &arguments[1..])?;
+                define(&mut self.materials, &arguments[0], material, statement)
+            }
+            // Objects that aren't in the world, only copied into it by instances
+            "prototype" => {
+                let name = match statement.arguments.as_slice() {
+                    [name] => name,
+                    _ => return Err(statement.error("expected `prototype <name> { <objects> }`")),
+                };
+                let objects = self.objects(statement)?;
+                if objects.is_empty() {
+                    return Err(statement.error("`prototype` needs at least one object"));
+                }
+                let prototype: Arc<dyn Hittable> = Arc::new(BVHNode::new(
+                    objects,
+                    self.camera.time.0,
+                    self.camera.time.1,
+                ));
+                define(&mut self.prototypes, name, prototype, statement)
+            }
+            // Objects in a light block are also sampled directly, like the lights of the world.
+            // They're built once, and placed in both as instances.
+            "light" => {
+                for child in statement.block()? {
+                    let light: Arc<dyn Hittable> = self.object(child)?.into();
+                    let instance = || Box::new(Instance::new(&light, Matrix::identity()));
+                    self.world.hittables.add(instance());
+                    self.world.lights.add(instance());
+                }
+                Ok(())
+            }
+            _ => {
+                let object = self.object(statement)?;
+                self.world.hittables.add(object);
+                Ok(())
+            }
+        }
+    }
+
+    fn camera(&mut self, statement: &Statement) -> Result<(), SceneError> {
+        let camera = &mut self.camera;
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "look-from" => camera.look_from = property.vector()?,
+                "look-at" => camera.look_at = property.vector()?,
+                "up" => camera.up = property.vector()?,
+                "fov" => camera.fov = property.number()?,
+                "aperture" => camera.aperture = property.number()?,
+                "focus-distance" => camera.focus_distance = Some(property.number()?),
+                "time" => {
+                    let time = property.numbers(2)?;
+                    camera.time = (time[0], time[1]);
+                }
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+        Ok(())
+    }
+
+    fn render_settings(&mut self, statement: &Statement) -> Result<(), SceneError> {
+        let world = &mut self.world;
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "width" => world.image_width = property.value()?,
+                "aspect" => {
+                    // As a number, or width:height
+                    let text = property.arguments(1, 1)?[0].replace(':', "/");
+                    world.aspect_ratio = parse_number(&text)
+                        .filter(|ratio| ratio.is_finite() && *ratio > 0.)
+                        .ok_or_else(|| property.error("`aspect` takes a number or width:height"))?;
+                }
+                "spp" => world.samples_per_pixel = property.value()?,
+                "max-depth" => world.max_depth = property.value()?,
+                "seed" => world.seed = property.value()?,
+                "sampler" => world.sampler = property.value()?,
+                "tonemap" => world.display.tone_map = property.value()?,
+                "exposure" => world.display.exposure = property.number()?,
+                // adaptive <noise threshold> [<min samples> <max samples>]
+                "adaptive" => {
+                    let mut adaptive = AdaptiveSampling::default();
+                    if property.arguments(1, 3)?.len() == 2 {
+                        return Err(property.error(
+                            "`adaptive` takes the noise threshold, and optionally the least and most samples",
+                        ));
+                    }
+                    adaptive.noise_threshold = property.number_at(0)?;
+                    if property.arguments.len() == 3 {
+                        adaptive.min_samples = integer(property, &property.arguments[1])?;
+                        adaptive.max_samples = integer(property, &property.arguments[2])?;
+                    }
+                    world.adaptive = Some(adaptive);
+                }
+                "tile-size" => world.tile_size = property.value()?,
+                "tile-order" => world.tile_order = property.value()?,
+                "aovs" => {
+                    property.flag()?;
+                    world.aovs = true;
+                }
+                "denoise" => {
+                    property.flag()?;
+                    world.denoise = Some(Denoiser::default());
+                }
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+        Ok(())
+    }
+
+    /// `background <color>`, or a block with the `top` and `bottom` of a gradient
+    fn background(&mut self, statement: &Statement) -> Result<(), SceneError> {
+        if statement.block.is_none() {
+            let color = statement.vector()?;
+            self.world.background_color_top = color;
+            self.world.background_color_bottom = color;
+            return Ok(());
+        }
+
+        if !statement.arguments.is_empty() {
+            return Err(statement.error("`background` takes a color or a block, not both"));
+        }
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "top" => self.world.background_color_top = property.vector()?,
+                "bottom" => self.world.background_color_bottom = property.vector()?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+        Ok(())
+    }
+
+    /// `texture <name> <kind> <values>`
+    fn texture_definition<'a>(
+        &self,
+        statement: &'a Statement,
+    ) -> Result<(&'a String, Texture), SceneError> {
+        let arguments = &statement.arguments;
+        if arguments.len() < 2 || statement.block.is_some() {
+            return Err(statement.error("expected `texture <name> <kind> <values>`"));
+        }
+        let (name, kind, values) = (&arguments[0], &arguments[1], &arguments[2..]);
+
+        // noise, turbulence and marble take a scale, and the seed of their Perlin noise
+        let noise = || -> Result<(Perlin, f32), SceneError> {
+            let (scale, seed) = match values {
+                [scale] => (scale, 0),
+                [scale, seed] => (scale, integer(statement, seed)?),
+                _ => {
+                    return Err(statement.error(format!(
+                        "`{}` textures take a scale, and optionally a seed",
+                        kind
+                    )))
+                }
+            };
+            let scale = numbers(statement, std::slice::from_ref(scale))?[0];
+            Ok((Perlin::with_seed(seed), scale))
+        };
+
+        let texture = match kind.as_str() {
+            "solid" => texture::solid_color(color(statement, &numbers(statement, values)?)?),
+            "checker" => {
+                if values.len() != 2 {
+                    return Err(statement.error("`checker` textures take the even and odd textures"));
+                }
+                texture::checker(
+                    self.texture(statement, &values[0])?,
+                    self.texture(statement, &values[1])?,
+                )
+            }
+            "noise" => {
+                let (perlin, scale) = noise()?;
+                texture::noise(perlin, scale)
+            }
+            "turbulence" => {
+                let (perlin, scale) = noise()?;
+                texture::turbulent_noise(perlin, scale)
+            }
+            "marble" => {
+                let (perlin, scale) = noise()?;
+                texture::marble(perlin, scale)
+            }
+            "image" => {
+                if values.len() != 1 {
+                    return Err(statement.error("`image` textures take the path of the image"));
+                }
+                let path = self.directory.join(&values[0]);
+                texture::load_image(&path).map_err(|why| {
+                    statement.error(format!("couldn't load {}: {}", path.display(), why))
+                })?
+            }
+            _ => {
+                return Err(statement.error(format!(
+                    "unknown kind of texture `{}`, expected solid, checker, noise, turbulence, marble or image",
+                    kind
+                )))
+            }
+        };
+
+        Ok((name, texture))
+    }
+
+    fn texture(&self, statement: &Statement, name: &str) -> Result<Texture, SceneError> {
+        self.textures
+            .get(name)
+            .cloned()
+            .ok_or_else(|| statement.error(format!("unknown texture `{}`", name)))
+    }
+
+    /// A material defined before, from its name, or a new one from its kind and values
+    fn material(&self, statement: &Statement, words: &[String]) -> Result<Material, SceneError> {
+        let (kind, values) = match words.split_first() {
+            Some(split) => split,
+            None => return Err(statement.error("expected a material")),
+        };
+        if values.is_empty() {
+            if let Some(material) = self.materials.get(kind) {
+                return Ok(material.clone());
+            }
+        }
+
+        // Colors can also be the name of a texture
+        let color_or_texture =
+            |color_material: fn(Color) -> Material, texture_material: fn(Texture) -> Material| {
+                match values {
+                    [name] if parse_number(name).is_none() => {
+                        Ok(texture_material(self.texture(statement, name)?))
+                    }
+                    _ => Ok(color_material(color(
+                        statement,
+                        &numbers(statement, values)?,
+                    )?)),
+                }
+            };
+        let count = |count: usize| {
+            if values.len() != count {
+                return Err(statement.error(format!(
+                    "`{}` materials take {} values, got {}",
+                    kind,
+                    count,
+                    values.len()
+                )));
+            }
+            numbers(statement, values)
+        };
+
+        match kind.as_str() {
+            "normal" => count(0).map(|_| Material::Normal),
+            "lambertian" => color_or_texture(Material::Lambertian, Material::LambertianTexture),
+            "metal" => {
+                // metal <color> <fuzz>, with the color as 1 or 3 numbers
+                let numbers = numbers(statement, values)?;
+                match numbers.split_last() {
+                    Some((&fuzz, color_values)) if !color_values.is_empty() => {
+                        Ok(Material::Metal(color(statement, color_values)?, fuzz))
+                    }
+                    _ => Err(statement.error("`metal` materials take a color and a fuzz")),
+                }
+            }
+            "dielectric" => count(1).map(|values| Material::Dielectric(values[0])),
+            "diffuse-light" => {
+                color_or_texture(Material::DiffuseLight, Material::DiffuseLightTexture)
+            }
+            "isotropic" => Ok(Material::Isotropic(color(
+                statement,
+                &numbers(statement, values)?,
+            )?)),
+            _ if values.is_empty() => Err(statement.error(format!("unknown material `{}`", kind))),
+            _ => Err(statement.error(format!(
+                "unknown kind of material `{}`, expected normal, lambertian, metal, dielectric, diffuse-light or isotropic",
+                kind
+            ))),
+        }
+    }
+
+    fn object(&self, statement: &Statement) -> Result<Box<dyn Hittable>, SceneError> {
+        let object: Box<dyn Hittable> = match statement.keyword.as_str() {
+            "sphere" => Box::new(self.sphere(statement)?),
+            "moving-sphere" => Box::new(self.moving_sphere(statement)?),
+            "rect" => Box::new(self.rect(statement)?),
+            "cube" => Box::new(self.cube(statement)?),
+            "pyramid" => Box::new(self.pyramid(statement)?),
+            "triangle" => Box::new(self.triangle(statement)?),
+            "cylinder" => Box::new(self.cylinder(statement)?),
+            "mesh" => Box::new(self.mesh(statement)?),
+            "sdf" => Box::new(self.traced_sdf(statement)?),
+            "instance" => Box::new(self.instance(statement)?),
+            "translate" => {
+                let mut offset = None;
+                let child =
+                    self.children(statement, |property| match property.keyword.as_str() {
+                        "offset" => set(&mut offset, property.vector()?, property).map(|_| true),
+                        _ => Ok(false),
+                    })?;
+                Box::new(Transform::new(
+                    child,
+                    Matrix::translation(required(offset, statement, "offset")?),
+                ))
+            }
+            "rotate-y" => {
+                let mut angle = None;
+                let child =
+                    self.children(statement, |property| match property.keyword.as_str() {
+                        "angle" => set(&mut angle, property.number()?, property).map(|_| true),
+                        _ => Ok(false),
+                    })?;
+                Box::new(Transform::new(
+                    child,
+                    Matrix::rotation(Vec3::new(0., 1., 0.), required(angle, statement, "angle")?),
+                ))
+            }
+            "rotate" => {
+                let (mut axis, mut angle) = (None, None);
+                let child =
+                    self.children(statement, |property| match property.keyword.as_str() {
+                        "axis" => set(&mut axis, property.vector()?, property).map(|_| true),
+                        "angle" => set(&mut angle, property.number()?, property).map(|_| true),
+                        _ => Ok(false),
+                    })?;
+                let axis = required(axis, statement, "axis")?;
+                if axis.length_squared() == 0. {
+                    return Err(statement.error("`axis` can't be 0"));
+                }
+                Box::new(Transform::new(
+                    child,
+                    Matrix::rotation(axis, required(angle, statement, "angle")?),
+                ))
+            }
+            "scale" => {
+                let mut factor = None;
+                let child =
+                    self.children(statement, |property| match property.keyword.as_str() {
+                        "factor" => set(&mut factor, property.vector()?, property).map(|_| true),
+                        _ => Ok(false),
+                    })?;
+                let factor = required(factor, statement, "factor")?;
+                if factor.x * factor.y * factor.z == 0. {
+                    return Err(statement.error("`factor` can't be 0 along any axis"));
+                }
+                Box::new(Transform::new(child, Matrix::scaling(factor)))
+            }
+            "flip-face" => Box::new(FlipFace {
+                hittable: self.children(statement, |_| Ok(false))?,
+            }),
+            "medium" => {
+                let (mut density, mut color) = (None, None);
+                let child =
+                    self.children(statement, |property| match property.keyword.as_str() {
+                        "density" => set(&mut density, property.number()?, property).map(|_| true),
+                        "color" => set(&mut color, property.vector()?, property).map(|_| true),
+                        _ => Ok(false),
+                    })?;
+                Box::new(ConstantMedium::new(
+                    child,
+                    required(density, statement, "density")?,
+                    required(color, statement, "color")?,
+                ))
+            }
+            "group" => {
+                let mut group = HittableList::new();
+                for object in self.objects(statement)? {
+                    group.add(object);
+                }
+                Box::new(group)
+            }
+            "bvh" => {
+                let objects = self.objects(statement)?;
+                if objects.is_empty() {
+                    return Err(statement.error("`bvh` needs at least one object"));
+                }
+                Box::new(BVHNode::new(
+                    objects,
+                    self.camera.time.0,
+                    self.camera.time.1,
+                ))
+            }
+            "light" => return Err(statement.error("`light` can only be at the top of the scene")),
+            keyword => return Err(statement.error(format!("unknown statement `{}`", keyword))),
+        };
+
+        Ok(object)
+    }
+
+    /// Objects of a block without properties
+    fn objects(&self, statement: &Statement) -> Result<Vec<Box<dyn Hittable>>, SceneError> {
+        statement
+            .block()?
+            .iter()
+            .map(|child| self.object(child))
+            .collect()
+    }
+
+    /// The object inside a block, after `property` takes the properties it knows. If
+    /// there are several objects they get grouped.
+    fn children(
+        &self,
+        statement: &Statement,
+        mut property: impl FnMut(&Statement) -> Result<bool, SceneError>,
+    ) -> Result<Box<dyn Hittable>, SceneError> {
+        let mut objects = Vec::new();
+        for child in statement.block()? {
+            if !property(child)? {
+                objects.push(self.object(child)?);
+            }
+        }
+
+        match objects.len() {
+            0 => Err(statement.error(format!("`{}` needs an object", statement.keyword))),
+            1 => Ok(objects.pop().unwrap()),
+            _ => {
+                let mut group = HittableList::new();
+                for object in objects {
+                    group.add(object);
+                }
+                Ok(Box::new(group))
+            }
+        }
+    }
+
+    fn sphere(&self, statement: &Statement) -> Result<Sphere, SceneError> {
+        let (mut center, mut radius, mut material) = (None, None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "center" => set(&mut center, property.vector()?, property)?,
+                "radius" => set(&mut radius, property.number()?, property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+
+        Ok(Sphere::new(
+            required(center, statement, "center")?,
+            required(radius, statement, "radius")?,
+            required(material, statement, "material")?,
+        ))
+    }
+
+    fn moving_sphere(&self, statement: &Statement) -> Result<MovingSphere, SceneError> {
+        let (mut center0, mut center1, mut time, mut radius, mut material) =
+            (None, None, None, None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "center0" => set(&mut center0, property.vector()?, property)?,
+                "center1" => set(&mut center1, property.vector()?, property)?,
+                "time" => set(&mut time, property.numbers(2)?, property)?,
+                "radius" => set(&mut radius, property.number()?, property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+        let time = time.unwrap_or_else(|| vec![0., 1.]);
+
+        Ok(MovingSphere::new(
+            required(center0, statement, "center0")?,
+            required(center1, statement, "center1")?,
+            time[0],
+            time[1],
+            required(radius, statement, "radius")?,
+            required(material, statement, "material")?,
+        ))
+    }
+
+    fn rect(&self, statement: &Statement) -> Result<Rect, SceneError> {
+        let (mut plane, mut a, mut b, mut k, mut material) = (None, None, None, None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "plane" => {
+                    let value = match property.arguments(1, 1)?[0].as_str() {
+                        "xy" => Plane::XY,
+                        "xz" => Plane::XZ,
+                        "yz" => Plane::YZ,
+                        other => {
+                            return Err(property.error(format!(
+                                "unknown plane `{}`, expected xy, xz or yz",
+                                other
+                            )))
+                        }
+                    };
+                    set(&mut plane, value, property)?
+                }
+                "a" => set(&mut a, property.numbers(2)?, property)?,
+                "b" => set(&mut b, property.numbers(2)?, property)?,
+                "k" => set(&mut k, property.number()?, property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+        let a = required(a, statement, "a")?;
+        let b = required(b, statement, "b")?;
+
+        let k = required(k, statement, "k")?;
+        let material = required(material, statement, "material")?;
+        let plane = required(plane, statement, "plane")?;
+
+        Ok(Rect::new(plane, (a[0], a[1]), (b[0], b[1]), k, material))
+    }
+
+    fn cube(&self, statement: &Statement) -> Result<Cube, SceneError> {
+        let (mut min, mut max, mut material) = (None, None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "min" => set(&mut min, property.vector()?, property)?,
+                "max" => set(&mut max, property.vector()?, property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+
+        Ok(Cube::new(
+            required(min, statement, "min")?,
+            required(max, statement, "max")?,
+            required(material, statement, "material")?,
+        ))
+    }
+
+    fn pyramid(&self, statement: &Statement) -> Result<Pyramid, SceneError> {
+        let (mut top, mut base, mut material) = (None, None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "top" => set(&mut top, property.vector()?, property)?,
+                // The 4 corners, one after the other
+                "base" => set(&mut base, property.numbers(12)?, property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+        let base = required(base, statement, "base")?;
+        let corner = |i: usize| Point::new(base[3 * i], base[3 * i + 1], base[3 * i + 2]);
+
+        Ok(Pyramid::new(
+            required(top, statement, "top")?,
+            corner(0),
+            corner(1),
+            corner(2),
+            corner(3),
+            required(material, statement, "material")?,
+        ))
+    }
+
+    fn triangle(&self, statement: &Statement) -> Result<Triangle, SceneError> {
+        let (mut v0, mut v1, mut v2, mut material) = (None, None, None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "v0" => set(&mut v0, property.vector()?, property)?,
+                "v1" => set(&mut v1, property.vector()?, property)?,
+                "v2" => set(&mut v2, property.vector()?, property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+
+        Ok(Triangle::new(
+            required(v0, statement, "v0")?,
+            required(v1, statement, "v1")?,
+            required(v2, statement, "v2")?,
+            required(material, statement, "material")?,
+        ))
+    }
+
+    /// `cylinder { start <point>; end <point>; radius <r>; material <material> }`, with
+    /// `open` to leave out the caps
+    fn cylinder(&self, statement: &Statement) -> Result<Cylinder, SceneError> {
+        let (mut start, mut end, mut radius, mut material) = (None, None, None, None);
+        let mut open = None;
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "start" => set(&mut start, property.vector()?, property)?,
+                "end" => set(&mut end, property.vector()?, property)?,
+                "radius" => set(&mut radius, property.number()?, property)?,
+                "open" => set(&mut open, property.flag()?, property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+
+        Ok(Cylinder {
+            caps: open.is_none(),
+            ..Cylinder::new(
+                required(start, statement, "start")?,
+                required(end, statement, "end")?,
+                required(radius, statement, "radius")?,
+                required(material, statement, "material")?,
+            )
+        })
+    }
+
+    /// `mesh { file <path>; material <material> }`, where the material is for the faces
+    /// the file doesn't give one, gray if it isn't set
+    fn mesh(&self, statement: &Statement) -> Result<TriangleMesh, SceneError> {
+        let (mut file, mut material) = (None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "file" => set(&mut file, property.arguments(1, 1)?[0].clone(), property)?,
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => return Err(unknown_property(property, statement)),
+            }
+        }
+
+        let path = self.directory.join(required(file, statement, "file")?);
+        let material = material.unwrap_or_else(|| Material::Lambertian(Color::from(0.8)));
+        let mesh = load_mesh(&path, material).map_err(|why| statement.error(why.to_string()))?;
+        self.warnings
+            .borrow_mut()
+            .extend(mesh.mesh().warnings.iter().cloned());
+        Ok(mesh)
+    }
+
+    /// `sdf { material <material>; <sdf> }`, with the SDF as a tree of shapes and operations
+    fn traced_sdf(&self, statement: &Statement) -> Result<TracedSDF, SceneError> {
+        let (mut sdf, mut material) = (None, None);
+        for property in statement.block()? {
+            match property.keyword.as_str() {
+                "material" => set(&mut material, self.material_property(property)?, property)?,
+                _ => {
+                    if sdf.is_some() {
+                        return Err(
+                            property.error("an `sdf` has a single shape, join them with `union`")
+                        );
+                    }
+                    sdf = Some(sdf_node(property)?);
+                }
+            }
+        }
+
+        Ok(TracedSDF::new(
+            sdf.ok_or_else(|| statement.error("`sdf` needs a shape"))?,
+            required(material, statement, "material")?,
+        ))
+    }
+
+    /// `instance <prototype> { ... }`, with its transforms done in the order they're written
+    fn instance(&self, statement: &Statement) -> Result<Instance, SceneError> {
+        let prototype = match statement.arguments.as_slice() {
+            [name] => self
+                .prototypes
+                .get(name)
+                .ok_or_else(|| statement.error(format!("unknown prototype `{}`", name)))?,
+            _ => return Err(statement.error("expected `instance <prototype> { <properties> }`")),
+        };
+
+        let (mut matrix, mut material) = (Matrix::identity(), None);
+        for property in statement.block()? {
+            let transform = match property.keyword.as_str() {
+                "translate" => Matrix::translation(property.vector()?),
+                "rotate-y" => Matrix::rotation(Vec3::new(0., 1., 0.), property.number()?),
+                // rotate <axis> <angle>
+                "rotate" => {
+                    let values = property.numbers(4)?;
+                    let axis = Vec3::new(values[0], values[1], values[2]);
+                    if axis.length_squared() == 0. {
+                        return Err(property.error("the axis of `rotate` can't be 0"));
+                    }
+                    Matrix::rotation(axis, values[3])
+                }
+                "scale" => {
+                    let factor = property.vector()?;
+                    if factor.x * factor.y * factor.z == 0. {
+                        return Err(property.error("`scale` can't be 0 along any axis"));
+                    }
+                    Matrix::scaling(factor)
+                }
+                "material" => {
+                    set(&mut material, self.material_property(property)?, property)?;
+                    continue;
+                }
+                _ => return Err(unknown_property(property, statement)),
+            };
+            matrix = transform * matrix;
+        }
+
+        let instance = Instance::new(prototype, matrix);
+        Ok(match material {
+            Some(material) => instance.with_material(material),
+            None => instance,
+        })
+    }
+
+    fn material_property(&self, property: &Statement) -> Result<Material, SceneError> {
+        property.arguments(1, usize::MAX)?;
+        self.material(property, &property.arguments)
+    }
+}
+
+/// Joins two shapes into one, like `union`
+type Operation = fn(Box<dyn SDF>, Box<dyn SDF>) -> Box<dyn SDF>;
+
+fn sdf_node(statement: &Statement) -> Result<Box<dyn SDF>, SceneError> {
+    // Statements with a block are shapes, the rest are properties
+    let mut properties = HashMap::new();
+    let mut children = Vec::new();
+    for child in statement.block()? {
+        if child.block.is_some() {
+            children.push(child);
+        } else if properties.insert(child.keyword.as_str(), child).is_some() {
+            return Err(child.error(format!("`{}` is set twice", child.keyword)));
+        }
+    }
+
+    let used = RefCell::new(Vec::new());
+    let property = |name: &'static str| {
+        used.borrow_mut().push(name);
+        properties.get(name).copied().ok_or_else(|| {
+            statement.error(format!("`{}` is missing `{}`", statement.keyword, name))
+        })
+    };
+    let number = |name| property(name)?.number();
+    let vector = |name| property(name)?.vector();
+    // Shapes are centered at the origin if they don't have a center
+    let center = || {
+        used.borrow_mut().push("center");
+        match properties.get("center") {
+            Some(property) => property.vector(),
+            None => Ok(Point::zeros()),
+        }
+    };
+    let child = || match children.as_slice() {
+        [child] => sdf_node(child),
+        _ => Err(statement.error(format!("`{}` takes a single shape", statement.keyword))),
+    };
+    // Operations are applied from the first shape to the last
+    let fold = |operation: Operation| {
+        if children.len() < 2 {
+            return Err(
+                statement.error(format!("`{}` takes two shapes or more", statement.keyword))
+            );
+        }
+        let mut sdf = sdf_node(children[0])?;
+        for child in &children[1..] {
+            sdf = operation(sdf, sdf_node(child)?);
+        }
+        Ok(sdf)
+    };
+
+    let sdf: Box<dyn SDF> = match statement.keyword.as_str() {
+        "sphere" => Box::new(SDFSphere {
+            center: center()?,
+            radius: number("radius")?,
+        }),
+        "cylinder" => Box::new(SDFCilinder {
+            center: center()?,
+            radius: number("radius")?,
+        }),
+        "plane" => Box::new(SDFPlane {
+            normal: vector("normal")?,
+            h: number("h")?,
+        }),
+        "donut" => Box::new(SDFDonut {
+            center: center()?,
+            radius0: number("radius0")?,
+            radius1: number("radius1")?,
+        }),
+        "box" => Box::new(SDFCube {
+            center: center()?,
+            dimensions: vector("dimensions")?,
+        }),
+        "octahedron" => Box::new(SDFOctahedron {
+            center: center()?,
+            size: number("size")?,
+        }),
+        "mandelbulb" => Box::new(SDFMandelBulb { center: center()? }),
+        "mandelbox" => Box::new(SDFMandelBox {
+            center: center()?,
+            scale: number("scale")?,
+        }),
+        "knot" => Box::new(SDFKnot {
+            center: center()?,
+            k: number("k")?,
+        }),
+        "cross" => sdf::cross(center()?, number("size")?),
+        "menger-sponge" => {
+            sdf::menger_sponge_sdf(property("iterations")?.value()?, number("size")?)
+        }
+        "union" => fold(|a, b| Box::new(SDFUnion { a, b }))?,
+        "subtraction" => fold(|a, b| Box::new(SDFSubstraction { a, b }))?,
+        "intersection" => fold(|a, b| Box::new(SDFIntersection { a, b }))?,
+        "repetition" => Box::new(SDFRepetition {
+            a: child()?,
+            repetition: vector("period")?,
+        }),
+        "scale" => Box::new(SDFScale {
+            a: child()?,
+            scale: number("scale")?,
+        }),
+        keyword => return Err(statement.error(format!("unknown shape `{}`", keyword))),
+    };
+
+    let used = used.borrow();
+    if let Some(unused) = properties
+        .values()
+        .find(|p| !used.contains(&p.keyword.as_str()))
+    {
+        return Err(unknown_property(unused, statement));
+    }
+    let is_operation = matches!(
+        statement.keyword.as_str(),
+        "union" | "subtraction" | "intersection" | "repetition" | "scale"
+    );
+    if let (false, Some(child)) = (is_operation, children.first()) {
+        return Err(child.error(format!(
+            "`{}` is a shape, it can't have shapes inside",
+            statement.keyword
+        )));
+    }
+
+    Ok(sdf)
+}
+
+fn define<T>(
+    names: &mut HashMap<String, T>,
+    name: &str,
+    value: T,
+    statement: &Statement,
+) -> Result<(), SceneError> {
+    if names.insert(name.to_string(), value).is_some() {
+        return Err(statement.error(format!(
+            "there's already a {} called `{}`",
+            statement.keyword, name
+        )));
+    }
+    Ok(())
+}
+
+fn numbers(statement: &Statement, values: &[String]) -> Result<Vec<f32>, SceneError> {
+    values
+        .iter()
+        .map(|value| {
+            parse_number(value)
+                .ok_or_else(|| statement.error(format!("expected a number, got `{}`", value)))
+        })
+        .collect()
+}
+
+/// A color from 3 numbers, or 1 for the three channels
+fn color(statement: &Statement, values: &[f32]) -> Result<Color, SceneError> {
+    match values {
+        [value] => Ok(Color::from(*value)),
+        [r, g, b] => Ok(Color::new(*r, *g, *b)),
+        _ => Err(statement.error("expected a color, as 3 numbers or 1 for the three channels")),
+    }
+}
+
+fn integer<T: std::str::FromStr>(statement: &Statement, text: &str) -> Result<T, SceneError> {
+    text.parse().map_err(|_| {
+        statement.error(format!(
+            "`{}` takes whole numbers, but `{}` isn't one",
+            statement.keyword, text
+        ))
+    })
+}
+
+fn unknown_property(property: &Statement, object: &Statement) -> SceneError {
+    property.error(format!(
+        "unknown property `{}` of `{}`",
+        property.keyword, object.keyword
+    ))
+}
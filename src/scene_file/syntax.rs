@@ -0,0 +1,252 @@
+use super::SceneError;
+use crate::vec3::*;
+use std::{fmt::Display, str::FromStr};
+
+/// A line of a scene file: a keyword, the values after it, and the statements of the
+/// block between braces that can follow them
+#[derive(Debug)]
+pub struct Statement {
+    pub keyword: String,
+    pub arguments: Vec<String>,
+    pub block: Option<Vec<Statement>>,
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Open,
+    Close,
+    /// End of a line, or a `;`
+    End,
+}
+
+/// Splits the source into statements. Values are kept as text, what they mean depends
+/// on the statement, so they get parsed when the scene is built.
+pub fn parse(source: &str) -> Result<Vec<Statement>, SceneError> {
+    let tokens = tokenize(source)?;
+    let mut tokens = tokens.iter().peekable();
+
+    let statements = parse_block(&mut tokens)?;
+    match tokens.next() {
+        Some((Token::Close, line)) => Err(SceneError::new(*line, "`}` without a `{` to close")),
+        _ => Ok(statements),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, SceneError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                tokens.push((Token::End, line));
+                line += 1;
+            }
+            ';' => tokens.push((Token::End, line)),
+            '{' => tokens.push((Token::Open, line)),
+            '}' => tokens.push((Token::Close, line)),
+            '#' => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '"' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(SceneError::new(line, "string without a closing `\"`"))
+                        }
+                        Some(c) => word.push(c),
+                    }
+                }
+                tokens.push((Token::Word(word), line));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{};#\"".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Word(word), line));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+type Tokens<'a> = std::iter::Peekable<std::slice::Iter<'a, (Token, usize)>>;
+
+/// Statements until the `}` that closes the block, which is left for the caller
+fn parse_block(tokens: &mut Tokens) -> Result<Vec<Statement>, SceneError> {
+    let mut statements = Vec::new();
+
+    while let Some((token, line)) = tokens.peek() {
+        let line = *line;
+        let keyword = match token {
+            Token::End => {
+                tokens.next();
+                continue;
+            }
+            Token::Close => break,
+            Token::Open => return Err(SceneError::new(line, "`{` without a statement before it")),
+            Token::Word(word) => word.clone(),
+        };
+        tokens.next();
+
+        let mut statement = Statement {
+            keyword,
+            arguments: Vec::new(),
+            block: None,
+            line,
+        };
+        while let Some((Token::Word(word), _)) = tokens.peek() {
+            statement.arguments.push(word.clone());
+            tokens.next();
+        }
+
+        if let Some((Token::Open, _)) = tokens.peek() {
+            tokens.next();
+            statement.block = Some(parse_block(tokens)?);
+            match tokens.next() {
+                Some((Token::Close, _)) => {}
+                _ => {
+                    return Err(SceneError::new(
+                        line,
+                        format!("the block of `{}` is never closed", statement.keyword),
+                    ))
+                }
+            }
+        }
+
+        statements.push(statement);
+    }
+
+    Ok(statements)
+}
+
+/// Parses a number, which can also be written as a fraction like `225/255`
+pub fn parse_number(text: &str) -> Option<f32> {
+    if let Ok(number) = text.parse() {
+        return Some(number);
+    }
+
+    let (numerator, denominator) = text.split_once('/')?;
+    let numerator: f32 = numerator.parse().ok()?;
+    let denominator: f32 = denominator.parse().ok()?;
+    Some(numerator / denominator)
+}
+
+impl Statement {
+    pub fn error(&self, message: impl Into<String>) -> SceneError {
+        SceneError::new(self.line, message)
+    }
+
+    /// The arguments of a property, which has to have between `min` and `max` of them
+    /// and no block
+    pub fn arguments(&self, min: usize, max: usize) -> Result<&[String], SceneError> {
+        if self.block.is_some() {
+            return Err(self.error(format!("`{}` doesn't take a block", self.keyword)));
+        }
+
+        let count = self.arguments.len();
+        if count < min || count > max {
+            let expected = if min == max {
+                format!("{}", min)
+            } else {
+                format!("{} to {}", min, max)
+            };
+            return Err(self.error(format!(
+                "`{}` takes {} values, got {}",
+                self.keyword, expected, count
+            )));
+        }
+
+        Ok(&self.arguments)
+    }
+
+    /// A property without values, like `aovs`
+    pub fn flag(&self) -> Result<(), SceneError> {
+        self.arguments(0, 0).map(|_| ())
+    }
+
+    pub fn number_at(&self, index: usize) -> Result<f32, SceneError> {
+        let text = &self.arguments[index];
+        parse_number(text).ok_or_else(|| {
+            self.error(format!(
+                "`{}` takes numbers, but `{}` isn't one",
+                self.keyword, text
+            ))
+        })
+    }
+
+    pub fn number(&self) -> Result<f32, SceneError> {
+        self.arguments(1, 1)?;
+        self.number_at(0)
+    }
+
+    pub fn numbers(&self, count: usize) -> Result<Vec<f32>, SceneError> {
+        self.arguments(count, count)?;
+        (0..count).map(|index| self.number_at(index)).collect()
+    }
+
+    /// A vector or color, as its three components or a single number for all of them
+    pub fn vector(&self) -> Result<Vec3, SceneError> {
+        match self.arguments(1, 3)?.len() {
+            1 => Ok(Vec3::from(self.number_at(0)?)),
+            3 => Ok(Vec3::new(
+                self.number_at(0)?,
+                self.number_at(1)?,
+                self.number_at(2)?,
+            )),
+            _ => Err(self.error(format!(
+                "`{}` takes a vector, as 3 numbers or 1 for all the components",
+                self.keyword
+            ))),
+        }
+    }
+
+    /// A single value of a type with its own syntax, like a sampler or an integer
+    pub fn value<T>(&self) -> Result<T, SceneError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let text = &self.arguments(1, 1)?[0];
+        text.parse()
+            .map_err(|why| self.error(format!("invalid `{}`: {}", self.keyword, why)))
+    }
+
+    /// The statements inside the braces
+    pub fn block(&self) -> Result<&[Statement], SceneError> {
+        match &self.block {
+            Some(block) => Ok(block),
+            None => Err(self.error(format!(
+                "`{}` needs a block with its properties",
+                self.keyword
+            ))),
+        }
+    }
+}
+
+/// Sets a property that hasn't been set yet in the same block
+pub fn set<T>(field: &mut Option<T>, value: T, statement: &Statement) -> Result<(), SceneError> {
+    if field.replace(value).is_some() {
+        return Err(statement.error(format!("`{}` is set twice", statement.keyword)));
+    }
+    Ok(())
+}
+
+/// A property every object of a kind needs
+pub fn required<T>(field: Option<T>, object: &Statement, property: &str) -> Result<T, SceneError> {
+    field.ok_or_else(|| object.error(format!("`{}` is missing `{}`", object.keyword, property)))
+}
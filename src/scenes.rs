@@ -1,18 +1,25 @@
 #[allow(unused_imports)]
 use crate::{
+    adaptive::*,
     bvh::*,
     camera::*,
+    denoise::*,
     hittable::{
-        cube::*, cylinder::*, flip_face::*, medium::*, moving_sphere::*, pyramid::*, rectangle::*,
-        rotate::*, sdf::*, sphere::*, translate::*, triangle::*, *,
+        cube::*, cylinder::*, flip_face::*, instance::*, medium::*, moving_sphere::*, pyramid::*,
+        rectangle::*, sdf::*, sphere::*, transform::*, triangle::*, *,
     },
     hittable_list::*,
     material::*,
+    matrix::*,
     perlin::*,
+    sampler::*,
     texture,
+    tiles::*,
+    tonemap::*,
     vec3::*,
 };
 use rand::Rng;
+use std::sync::Arc;
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
@@ -30,20 +37,27 @@ pub enum Scene {
 
 #[cfg(target_arch = "wasm32")]
 pub fn generate_world(scene: Scene) -> World {
-    match scene {
+    // Scenes are always built with the same random numbers, the seed of the world only
+    // changes the noise of the render
+    let rng = &mut Sampler::default();
+
+    let mut world = match scene {
         Scene::CornellBox => cornell_box(),
         Scene::CornellSmokes => cornell_smokes(),
-        Scene::SpaceDonut => space_dount(),
+        Scene::SpaceDonut => space_dount(rng),
         Scene::MengerSponge => menger_sponge(),
-        Scene::Imagine => imagine(),
+        Scene::Imagine => imagine(rng),
         Scene::MandelBulb => mandelbulb(),
         Scene::MandelBox => mandelbox(),
         Scene::Knot => knot(),
         Scene::CornellMandelBox => cornell_mandelbox(),
-    }
+    };
+    world.number_objects();
+    world
 }
 
 #[cfg(not(target_arch = "wasm32"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Scene {
     Test,
     ManySpheres,
@@ -63,26 +77,95 @@ pub enum Scene {
     CornellMandelBox,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+impl Scene {
+    pub const ALL: [Scene; 16] = [
+        Scene::Test,
+        Scene::ManySpheres,
+        Scene::TwoPerlinSpheres,
+        Scene::Earth,
+        Scene::LightRectangle,
+        Scene::CornellBox,
+        Scene::CornellSmokes,
+        Scene::FinalScene,
+        Scene::CustomScene,
+        Scene::SpaceDonut,
+        Scene::Imagine,
+        Scene::MengerSponge,
+        Scene::MandelBulb,
+        Scene::MandelBox,
+        Scene::Knot,
+        Scene::CornellMandelBox,
+    ];
+
+    /// Name used to pick the scene from the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::Test => "test",
+            Scene::ManySpheres => "many-spheres",
+            Scene::TwoPerlinSpheres => "two-perlin-spheres",
+            Scene::Earth => "earth",
+            Scene::LightRectangle => "light-rectangle",
+            Scene::CornellBox => "cornell-box",
+            Scene::CornellSmokes => "cornell-smokes",
+            Scene::FinalScene => "final-scene",
+            Scene::CustomScene => "custom-scene",
+            Scene::SpaceDonut => "space-donut",
+            Scene::Imagine => "imagine",
+            Scene::MengerSponge => "menger-sponge",
+            Scene::MandelBulb => "mandelbulb",
+            Scene::MandelBox => "mandelbox",
+            Scene::Knot => "knot",
+            Scene::CornellMandelBox => "cornell-mandelbox",
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl std::str::FromStr for Scene {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase().replace('_', "-");
+        Scene::ALL
+            .iter()
+            .find(|scene| scene.name() == name)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "unknown scene {}, `sunny scenes` lists the available ones",
+                    s
+                )
+            })
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn generate_world(scene: Scene) -> World {
-    match scene {
+    // Scenes are always built with the same random numbers, the seed of the world only
+    // changes the noise of the render
+    let rng = &mut Sampler::default();
+
+    let mut world = match scene {
         Scene::Test => test(),
-        Scene::ManySpheres => many_spheres(),
-        Scene::TwoPerlinSpheres => two_perlin_spheres(),
+        Scene::ManySpheres => many_spheres(rng),
+        Scene::TwoPerlinSpheres => two_perlin_spheres(rng),
         Scene::Earth => earth(),
-        Scene::LightRectangle => light_rectangle(),
+        Scene::LightRectangle => light_rectangle(rng),
         Scene::CornellBox => cornell_box(),
         Scene::CornellSmokes => cornell_smokes(),
-        Scene::FinalScene => final_scene(),
-        Scene::CustomScene => custom_scene(),
-        Scene::SpaceDonut => space_dount(),
+        Scene::FinalScene => final_scene(rng),
+        Scene::CustomScene => custom_scene(rng),
+        Scene::SpaceDonut => space_dount(rng),
         Scene::MengerSponge => menger_sponge(),
-        Scene::Imagine => imagine(),
+        Scene::Imagine => imagine(rng),
         Scene::MandelBulb => mandelbulb(),
         Scene::MandelBox => mandelbox(),
         Scene::Knot => knot(),
         Scene::CornellMandelBox => cornell_mandelbox(),
-    }
+    };
+    world.number_objects();
+    world
 }
 
 pub struct World {
@@ -94,10 +177,60 @@ pub struct World {
     pub background_color_bottom: Color,
 
     // Image
+    /// With adaptive sampling, it's the most samples per pixel on average
     pub samples_per_pixel: u16,
+    /// If set, pixels stop once they converge, and the samples they don't take go to the
+    /// noisy ones
+    pub adaptive: Option<AdaptiveSampling>,
     pub aspect_ratio: f32,
     pub image_width: u16,
     pub max_depth: u16,
+    /// Renders with the same seed are identical, no matter how many threads they use
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    /// Side of the square tiles the image is rendered in, in pixels
+    pub tile_size: u16,
+    pub tile_order: TileOrder,
+
+    pub display: DisplayTransform,
+    /// Also render the auxiliary buffers (albedo, normal, depth...)
+    pub aovs: bool,
+    /// Filter the noise out of the image once it's rendered
+    pub denoise: Option<Denoiser>,
+    /// Problems loading the scene that didn't stop it, like a mesh's missing MTL file
+    pub warnings: Vec<String>,
+}
+
+impl World {
+    /// The denoiser is guided by the AOVs, so they get rendered even if they aren't output
+    pub fn needs_aovs(&self) -> bool {
+        self.aovs || self.denoise.is_some()
+    }
+
+    /// Most samples a pixel can get
+    pub fn max_samples(&self) -> u32 {
+        match &self.adaptive {
+            Some(adaptive) => adaptive.max_samples as u32,
+            None => self.samples_per_pixel as u32,
+        }
+    }
+
+    /// Changes the shape of the image, keeping the vertical field of view of the camera
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.aspect_ratio = aspect_ratio;
+        self.camera.set_aspect_ratio(aspect_ratio);
+    }
+
+    /// Numbers the objects for the `object_id` AOV, in the order they were added, so the
+    /// same world always gets the same ids. Building a world does it, but objects added to
+    /// `hittables` afterwards need another call.
+    pub fn number_objects(&mut self) {
+        self.hittables.number_objects(&mut 1);
+    }
+
+    pub fn image_height(&self) -> usize {
+        (self.image_width as f32 / self.aspect_ratio) as usize
+    }
 }
 
 impl Default for World {
@@ -125,9 +258,19 @@ impl Default for World {
             background_color_bottom: Color::zeros(),
 
             samples_per_pixel: 100,
+            adaptive: None,
             aspect_ratio,
             image_width: 800,
             max_depth: 50,
+            seed: 0,
+            sampler: SamplerKind::default(),
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+
+            display: DisplayTransform::default(),
+            aovs: false,
+            denoise: None,
+            warnings: Vec::new(),
         }
     }
 }
@@ -135,16 +278,16 @@ impl Default for World {
 fn test() -> World {
     let mut hittables = HittableList::new();
 
-    hittables.add(box TracedSDF {
-        sdf: box SDFRepetition {
-            a: box SDFSphere {
+    hittables.add(Box::new(TracedSDF::new(
+        Box::new(SDFRepetition {
+            a: Box::new(SDFSphere {
                 radius: 0.5,
                 center: Point::from(0.),
-            },
+            }),
             repetition: Vec3::from(10.),
-        },
-        material: Material::DiffuseLight(Color::from(0.7)),
-    });
+        }),
+        Material::DiffuseLight(Color::from(0.7)),
+    )));
 
     World {
         hittables,
@@ -153,7 +296,7 @@ fn test() -> World {
     }
 }
 
-fn many_spheres() -> World {
+fn many_spheres(rng: &mut Sampler) -> World {
     let mut hittables = HittableList::new();
 
     // Ground
@@ -161,79 +304,78 @@ fn many_spheres() -> World {
         texture::solid_color(Color::new(0.2, 0.3, 0.1)),
         texture::solid_color(Color::new(0.9, 0.9, 0.9)),
     );
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: Material::LambertianTexture(checker),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Material::LambertianTexture(checker),
+    )));
 
     // Spheres
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: Material::Dielectric(1.5),
-    }));
-    hittables.add(Box::new(Sphere {
-        center: Point::new(-4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: Material::LambertianTexture(texture::noise(Perlin::new(), 4.0)),
-    }));
-    hittables.add(Box::new(Sphere {
-        center: Point::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: Material::Metal(Color::new(0.8, 0.6, 0.2), 0.0),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        Material::Dielectric(1.5),
+    )));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(-4.0, 1.0, 0.0),
+        1.0,
+        Material::LambertianTexture(texture::noise(Perlin::new(rng), 4.0)),
+    )));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(4.0, 1.0, 0.0),
+        1.0,
+        Material::Metal(Color::new(0.8, 0.6, 0.2), 0.0),
+    )));
 
     // Illuminated sphere
-    hittables.add(Box::new(Sphere {
-        center: Point::new(-4.0, 0.5, 2.0),
-        radius: 0.5,
-        material: Material::Lambertian(Color::new(2.0, 2.0, 1.0)),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(-4.0, 0.5, 2.0),
+        0.5,
+        Material::Lambertian(Color::new(2.0, 2.0, 1.0)),
+    )));
 
     for a in -5..5 {
         for b in -5..5 {
-            let choose_mat = rand::thread_rng().gen::<f32>();
+            let choose_mat = rng.gen::<f32>();
             let center = Point {
-                x: a as f32 + 0.9 * rand::thread_rng().gen::<f32>(),
+                x: a as f32 + 0.9 * rng.gen::<f32>(),
                 y: 0.2,
-                z: b as f32 + 0.9 * rand::thread_rng().gen::<f32>(),
+                z: b as f32 + 0.9 * rng.gen::<f32>(),
             };
 
             if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.7 {
-                    let albedo = Color::random() * Color::random();
-                    let center1 =
-                        center + Vec3::new(0.0, rand::thread_rng().gen_range(0.0, 0.3), 0.0);
-                    hittables.add(Box::new(MovingSphere {
-                        center0: center,
+                    let albedo = Color::random(rng) * Color::random(rng);
+                    let center1 = center + Vec3::new(0.0, rng.gen_range(0.0, 0.3), 0.0);
+                    hittables.add(Box::new(MovingSphere::new(
+                        center,
                         center1,
-                        time0: 0.0,
-                        time1: 1.0,
-                        radius: 0.2,
-                        material: Material::Lambertian(albedo),
-                    }));
+                        0.0,
+                        1.0,
+                        0.2,
+                        Material::Lambertian(albedo),
+                    )));
                 } else if choose_mat < 0.8 {
                     let texture = texture::image("earthmap.jpg");
-                    hittables.add(Box::new(Sphere {
+                    hittables.add(Box::new(Sphere::new(
                         center,
-                        radius: 0.2,
-                        material: Material::LambertianTexture(texture),
-                    }));
+                        0.2,
+                        Material::LambertianTexture(texture),
+                    )));
                 } else if choose_mat < 0.95 {
-                    let albedo = Color::random_range(0.5, 1.0);
-                    let fuzz = rand::thread_rng().gen_range(0.0, 0.5);
-                    hittables.add(Box::new(Sphere {
+                    let albedo = Color::random_range(rng, 0.5, 1.0);
+                    let fuzz = rng.gen_range(0.0, 0.5);
+                    hittables.add(Box::new(Sphere::new(
                         center,
-                        radius: 0.2,
-                        material: Material::Metal(albedo, fuzz),
-                    }));
+                        0.2,
+                        Material::Metal(albedo, fuzz),
+                    )));
                 } else {
-                    hittables.add(Box::new(Sphere {
+                    hittables.add(Box::new(Sphere::new(
                         center,
-                        radius: 0.2,
-                        material: Material::Dielectric(1.5),
-                    }));
+                        0.2,
+                        Material::Dielectric(1.5),
+                    )));
                 }
             }
         }
@@ -247,20 +389,20 @@ fn many_spheres() -> World {
     }
 }
 
-fn two_perlin_spheres() -> World {
+fn two_perlin_spheres(rng: &mut Sampler) -> World {
     let mut hittables = HittableList::new();
 
-    let texture = texture::marble(Perlin::new(), 4.0);
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: Material::Metal(Color::new(1.0, 1.0, 1.0), 0.0),
-    }));
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: Material::LambertianTexture(texture.clone()),
-    }));
+    let texture = texture::marble(Perlin::new(rng), 4.0);
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        Material::Metal(Color::new(1.0, 1.0, 1.0), 0.0),
+    )));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Material::LambertianTexture(texture.clone()),
+    )));
 
     World {
         hittables,
@@ -274,11 +416,11 @@ fn earth() -> World {
     let mut hittables = HittableList::new();
 
     let texture = texture::image("earthmap.jpg");
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, 0.0, 0.0),
-        radius: 2.0,
-        material: Material::LambertianTexture(texture),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, 0.0, 0.0),
+        2.0,
+        Material::LambertianTexture(texture),
+    )));
 
     World {
         hittables,
@@ -288,36 +430,34 @@ fn earth() -> World {
     }
 }
 
-fn light_rectangle() -> World {
+fn light_rectangle(rng: &mut Sampler) -> World {
     let mut hittables = HittableList::new();
 
     // Two marble spheres
-    let texture = texture::marble(Perlin::new(), 4.0);
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, 2.0, 0.0),
-        radius: 2.0,
-        material: Material::Metal(Color::new(1.0, 1.0, 1.0), 0.0),
-    }));
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: Material::LambertianTexture(texture.clone()),
-    }));
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, 5.0, 0.0),
-        radius: 1.0,
-        material: Material::DiffuseLight(Color::new(4.0, 3.0, 1.0)),
-    }));
+    let texture = texture::marble(Perlin::new(rng), 4.0);
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, 2.0, 0.0),
+        2.0,
+        Material::Metal(Color::new(1.0, 1.0, 1.0), 0.0),
+    )));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Material::LambertianTexture(texture.clone()),
+    )));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, 5.0, 0.0),
+        1.0,
+        Material::DiffuseLight(Color::new(4.0, 3.0, 1.0)),
+    )));
 
-    hittables.add(Box::new(Rect {
-        a0: 3.0,
-        a1: 5.0,
-        b0: 1.0,
-        b1: 3.0,
-        k: -2.0,
-        material: Material::DiffuseLight(Color::new(4.0, 4.0, 4.0)),
-        plane: Plane::XY,
-    }));
+    hittables.add(Box::new(Rect::new(
+        Plane::XY,
+        (3.0, 5.0),
+        (1.0, 3.0),
+        -2.0,
+        Material::DiffuseLight(Color::new(4.0, 4.0, 4.0)),
+    )));
 
     let camera = Camera::new(
         Point::new(26.0, 3.0, 6.0),
@@ -342,51 +482,41 @@ fn cornell_box() -> World {
     let mut hittables = HittableList::new();
 
     // Walls
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 555.0,
-        material: Material::Lambertian(Color::new(0.12, 0.45, 0.15)),
-        plane: Plane::YZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 0.0,
-        material: Material::Lambertian(Color::new(0.65, 0.05, 0.05)),
-        plane: Plane::YZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 0.0,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 555.0,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 555.0,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XY,
-    }));
+    hittables.add(Box::new(Rect::new(
+        Plane::YZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        Material::Lambertian(Color::new(0.12, 0.45, 0.15)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::YZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        Material::Lambertian(Color::new(0.65, 0.05, 0.05)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        Material::Lambertian(Color::from(0.73)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        Material::Lambertian(Color::from(0.73)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::XY,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        Material::Lambertian(Color::from(0.73)),
+    )));
 
     let tall_cube = {
         let cube = Box::new(Cube::new(
@@ -394,8 +524,11 @@ fn cornell_box() -> World {
             Point::new(165.0, 330.0, 165.0),
             Material::Metal(Color::new(0.8, 0.85, 0.88), 0.),
         ));
-        let rot = Box::new(RotateY::new(cube, 15.0));
-        Box::new(Translate::new(rot, Vec3::new(265.0, 0.0, 295.0)))
+        Box::new(Transform::new(
+            cube,
+            Matrix::translation(Vec3::new(265.0, 0.0, 295.0))
+                * Matrix::rotation(Vec3::new(0., 1., 0.), 15.0),
+        ))
     };
     hittables.add(tall_cube);
     // let short_cube = {
@@ -404,43 +537,42 @@ fn cornell_box() -> World {
     //         Point::from(165.0),
     //         Material::Lambertian(Color::from(0.73)),
     //     ));
-    //     let rot = Box::new(RotateY::new(cube, -18.0));
-    //     Box::new(Translate::new(rot, Vec3::new(130.0, 0.0, 65.0)))
+    //     Box::new(Transform::new(
+    //         cube,
+    //         Matrix::translation(Vec3::new(130.0, 0.0, 65.0))
+    //             * Matrix::rotation(Vec3::new(0., 1., 0.), -18.0),
+    //     ))
     // };
     // hittables.add(short_cube);
-    hittables.add(box Sphere {
-        center: Point::new(190., 90., 190.),
-        radius: 90.,
-        material: Material::Dielectric(1.5),
-    });
+    hittables.add(Box::new(Sphere::new(
+        Point::new(190., 90., 190.),
+        90.,
+        Material::Dielectric(1.5),
+    )));
 
     // Light
     hittables.add(Box::new(FlipFace {
-        hittable: Box::new(Rect {
-            a0: 213.0,
-            a1: 343.0,
-            b0: 227.0,
-            b1: 332.0,
-            k: 550.0,
-            material: Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
-            plane: Plane::XZ,
-        }),
+        hittable: Box::new(Rect::new(
+            Plane::XZ,
+            (213.0, 343.0),
+            (227.0, 332.0),
+            550.0,
+            Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
+        )),
     }));
     let mut lights = HittableList::new();
-    lights.add(box Rect {
-        a0: 213.0,
-        a1: 343.0,
-        b0: 227.0,
-        b1: 332.0,
-        k: 550.0,
-        material: Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
-        plane: Plane::XZ,
-    });
-    lights.add(box Sphere {
-        center: Point::new(190., 90., 190.),
-        radius: 90.,
-        material: Material::Dielectric(1.5),
-    });
+    lights.add(Box::new(Rect::new(
+        Plane::XZ,
+        (213.0, 343.0),
+        (227.0, 332.0),
+        550.0,
+        Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
+    )));
+    lights.add(Box::new(Sphere::new(
+        Point::new(190., 90., 190.),
+        90.,
+        Material::Dielectric(1.5),
+    )));
 
     let lookfrom = Point::new(278.0, 278.0, -800.0);
     let lookat = Point::new(278.0, 278.0, 0.0);
@@ -463,6 +595,10 @@ fn cornell_box() -> World {
         camera,
         lights,
         samples_per_pixel: 100,
+        display: DisplayTransform {
+            tone_map: ToneMap::Aces,
+            exposure: 0.,
+        },
         ..World::default()
     }
 }
@@ -471,51 +607,41 @@ fn cornell_smokes() -> World {
     let mut hittables = HittableList::new();
 
     // Walls
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 555.0,
-        material: Material::Lambertian(Color::new(0.12, 0.45, 0.15)),
-        plane: Plane::YZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 0.0,
-        material: Material::Lambertian(Color::new(0.65, 0.05, 0.05)),
-        plane: Plane::YZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 0.0,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 555.0,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XZ,
-    }));
-    hittables.add(Box::new(Rect {
-        a0: 0.0,
-        a1: 555.0,
-        b0: 0.0,
-        b1: 555.0,
-        k: 555.0,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XY,
-    }));
+    hittables.add(Box::new(Rect::new(
+        Plane::YZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        Material::Lambertian(Color::new(0.12, 0.45, 0.15)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::YZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        Material::Lambertian(Color::new(0.65, 0.05, 0.05)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        Material::Lambertian(Color::from(0.73)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        Material::Lambertian(Color::from(0.73)),
+    )));
+    hittables.add(Box::new(Rect::new(
+        Plane::XY,
+        (0.0, 555.0),
+        (0.0, 555.0),
+        555.0,
+        Material::Lambertian(Color::from(0.73)),
+    )));
 
     let tall_cube = {
         let cube = Box::new(Cube::new(
@@ -523,9 +649,12 @@ fn cornell_smokes() -> World {
             Point::new(165.0, 330.0, 165.0),
             Material::Lambertian(Color::from(0.73)),
         ));
-        let rot = Box::new(RotateY::new(cube, 15.0));
-        let translate = Box::new(Translate::new(rot, Vec3::new(265.0, 0.0, 295.0)));
-        Box::new(ConstantMedium::new(translate, 0.01, Color::zeros()))
+        let transform = Box::new(Transform::new(
+            cube,
+            Matrix::translation(Vec3::new(265.0, 0.0, 295.0))
+                * Matrix::rotation(Vec3::new(0., 1., 0.), 15.0),
+        ));
+        Box::new(ConstantMedium::new(transform, 0.01, Color::zeros()))
     };
     hittables.add(tall_cube);
     let short_cube = {
@@ -534,22 +663,23 @@ fn cornell_smokes() -> World {
             Point::from(165.0),
             Material::Lambertian(Color::from(0.73)),
         ));
-        let rot = Box::new(RotateY::new(cube, -18.0));
-        let translate = Box::new(Translate::new(rot, Vec3::new(130.0, 0.0, 65.0)));
-        Box::new(ConstantMedium::new(translate, 0.01, Color::ones()))
+        let transform = Box::new(Transform::new(
+            cube,
+            Matrix::translation(Vec3::new(130.0, 0.0, 65.0))
+                * Matrix::rotation(Vec3::new(0., 1., 0.), -18.0),
+        ));
+        Box::new(ConstantMedium::new(transform, 0.01, Color::ones()))
     };
     hittables.add(short_cube);
 
     // Light
-    hittables.add(Box::new(Rect {
-        a0: 213.0,
-        a1: 343.0,
-        b0: 227.0,
-        b1: 332.0,
-        k: 550.0,
-        material: Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
-        plane: Plane::XZ,
-    }));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (213.0, 343.0),
+        (227.0, 332.0),
+        550.0,
+        Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
+    )));
 
     let lookfrom = Point::new(278.0, 278.0, -800.0);
     let lookat = Point::new(278.0, 278.0, 0.0);
@@ -574,23 +704,26 @@ fn cornell_smokes() -> World {
     }
 }
 
-fn final_scene() -> World {
+fn final_scene(rng: &mut Sampler) -> World {
     let mut boxes: Vec<Box<dyn Hittable>> = Vec::new();
 
-    // Floor
+    // Floor, where every box is the same cube stretched to its size
+    let ground: Arc<dyn Hittable> = Arc::new(Cube::new(
+        Point::zeros(),
+        Point::ones(),
+        Material::Lambertian(Color::new(0.48, 0.83, 0.53)),
+    ));
     for i in 0..20 {
         for j in 0..20 {
             let w = 100.0;
             let x0 = -1000.0 + i as f32 * w;
             let z0 = -1000.0 + j as f32 * w;
             let y0 = 0.0;
-            let x1 = x0 + w;
-            let y1 = 100.0 * (rand::thread_rng().gen::<f32>() + 0.01);
-            let z1 = z0 + w;
-            boxes.push(Box::new(Cube::new(
-                Point::new(x0, y0, z0),
-                Point::new(x1, y1, z1),
-                Material::Lambertian(Color::new(0.48, 0.83, 0.53)),
+            let y1 = 100.0 * (rng.gen::<f32>() + 0.01);
+            boxes.push(Box::new(Instance::new(
+                &ground,
+                Matrix::translation(Vec3::new(x0, y0, z0))
+                    * Matrix::scaling(Vec3::new(w, y1 - y0, w)),
             )));
         }
     }
@@ -600,55 +733,49 @@ fn final_scene() -> World {
     hittables.add(Box::new(bvh));
 
     // Ceiling light
-    hittables.add(Box::new(Rect {
-        a0: 123.0,
-        a1: 423.0,
-        b0: 147.0,
-        b1: 412.0,
-        k: 554.0,
-        material: Material::DiffuseLight(Color::new(7.0, 7.0, 7.0)),
-        plane: Plane::XZ,
-    }));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (123.0, 423.0),
+        (147.0, 412.0),
+        554.0,
+        Material::DiffuseLight(Color::new(7.0, 7.0, 7.0)),
+    )));
 
     // Moving sphere
-    hittables.add(Box::new(MovingSphere {
-        center0: Point::new(400.0, 400.0, 200.0),
-        center1: Point::new(430.0, 400.0, 200.0),
-        time0: 0.0,
-        time1: 1.0,
-        radius: 50.0,
-        material: Material::Lambertian(Color::new(0.7, 0.3, 0.1)),
-    }));
+    hittables.add(Box::new(MovingSphere::new(
+        Point::new(400.0, 400.0, 200.0),
+        Point::new(430.0, 400.0, 200.0),
+        0.0,
+        1.0,
+        50.0,
+        Material::Lambertian(Color::new(0.7, 0.3, 0.1)),
+    )));
 
     // Spheres
-    hittables.add(Box::new(Sphere {
-        center: Point::new(260.0, 150.0, 45.0),
-        radius: 50.0,
-        material: Material::Dielectric(1.5),
-    }));
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, 150.0, 145.0),
-        radius: 50.0,
-        material: Material::Metal(Color::new(0.8, 0.8, 0.9), 10.0),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(260.0, 150.0, 45.0),
+        50.0,
+        Material::Dielectric(1.5),
+    )));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, 150.0, 145.0),
+        50.0,
+        Material::Metal(Color::new(0.8, 0.8, 0.9), 10.0),
+    )));
 
     // Volumes
-    let boundary = Sphere {
-        center: Point::new(360.0, 150.0, 145.0),
-        radius: 70.0,
-        material: Material::Dielectric(1.5),
-    };
+    let boundary = Sphere::new(
+        Point::new(360.0, 150.0, 145.0),
+        70.0,
+        Material::Dielectric(1.5),
+    );
     hittables.add(Box::new(boundary.clone()));
     hittables.add(Box::new(ConstantMedium::new(
         Box::new(boundary),
         0.2,
         Color::new(0.2, 0.4, 0.9),
     )));
-    let boundary = Sphere {
-        center: Point::zeros(),
-        radius: 5000.0,
-        material: Material::Dielectric(1.5),
-    };
+    let boundary = Sphere::new(Point::zeros(), 5000.0, Material::Dielectric(1.5));
     hittables.add(Box::new(boundary.clone()));
     hittables.add(Box::new(ConstantMedium::new(
         Box::new(boundary),
@@ -656,32 +783,36 @@ fn final_scene() -> World {
         Color::ones(),
     )));
 
-    hittables.add(Box::new(Sphere {
-        center: Point::new(400.0, 200.0, 400.0),
-        radius: 100.0,
-        material: Material::LambertianTexture(texture::image("earthmap.jpg")),
-    }));
-    let perlin = Perlin::new();
-    hittables.add(Box::new(Sphere {
-        center: Point::new(220.0, 280.0, 300.0),
-        radius: 80.0,
-        material: Material::LambertianTexture(texture::noise(perlin, 0.1)),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(400.0, 200.0, 400.0),
+        100.0,
+        Material::LambertianTexture(texture::image("earthmap.jpg")),
+    )));
+    // Seeded on its own, so the noise doesn't depend on the numbers the floor took
+    let perlin = Perlin::with_seed(0);
+    hittables.add(Box::new(Sphere::new(
+        Point::new(220.0, 280.0, 300.0),
+        80.0,
+        Material::LambertianTexture(texture::noise(perlin, 0.1)),
+    )));
 
     let mut spheres: Vec<Box<dyn Hittable>> = Vec::new();
 
     for _ in 0..1000 {
-        spheres.push(Box::new(Sphere {
-            center: Vec3::random_range(0.0, 165.0),
-            radius: 10.0,
-            material: Material::Lambertian(Color::from(0.73)),
-        }))
+        spheres.push(Box::new(Sphere::new(
+            Vec3::random_range(rng, 0.0, 165.0),
+            10.0,
+            Material::Lambertian(Color::from(0.73)),
+        )))
     }
 
     let bvh = {
         let bvh = BVHNode::new(spheres, 0.0, 1.0);
-        let rot = RotateY::new(Box::new(bvh), 15.0);
-        Translate::new(Box::new(rot), Vec3::new(-100.0, 270.0, 395.0))
+        Transform::new(
+            Box::new(bvh),
+            Matrix::translation(Vec3::new(-100.0, 270.0, 395.0))
+                * Matrix::rotation(Vec3::new(0., 1., 0.), 15.0),
+        )
     };
     hittables.add(Box::new(bvh));
 
@@ -709,11 +840,9 @@ fn final_scene() -> World {
     }
 }
 
-fn custom_scene() -> World {
+fn custom_scene(rng: &mut Sampler) -> World {
     let mut boxes: Vec<Box<dyn Hittable>> = Vec::new();
 
-    let mut rng = rand::thread_rng();
-
     // Floor
     for i in 0..20 {
         for j in 0..20 {
@@ -722,7 +851,7 @@ fn custom_scene() -> World {
             let z0 = -1000.0 + j as f32 * w;
             let y0 = 0.0;
             let x1 = x0 + w;
-            let y1 = 100.0 * (rand::thread_rng().gen::<f32>() + 0.01);
+            let y1 = 100.0 * (rng.gen::<f32>() + 0.01);
             let z1 = z0 + w;
             boxes.push(Box::new(Cube::new(
                 Point::new(x0, y0, z0),
@@ -741,23 +870,23 @@ fn custom_scene() -> World {
     hittables.add(Box::new(bvh));
 
     // Spheres
-    hittables.add(Box::new(Sphere {
-        center: Point::new(260.0, 150.0, 45.0),
-        radius: 50.0,
-        material: Material::Dielectric(1.5),
-    }));
-    hittables.add(Box::new(Sphere {
-        center: Point::new(0.0, 150.0, 145.0),
-        radius: 50.0,
-        material: Material::Metal(Color::new(0.8, 0.8, 0.9), 10.0),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(260.0, 150.0, 45.0),
+        50.0,
+        Material::Dielectric(1.5),
+    )));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(0.0, 150.0, 145.0),
+        50.0,
+        Material::Metal(Color::new(0.8, 0.8, 0.9), 10.0),
+    )));
 
     // Volumes
-    let boundary = Sphere {
-        center: Point::new(360.0, 150.0, 145.0),
-        radius: 70.0,
-        material: Material::Dielectric(1.5),
-    };
+    let boundary = Sphere::new(
+        Point::new(360.0, 150.0, 145.0),
+        70.0,
+        Material::Dielectric(1.5),
+    );
     hittables.add(Box::new(boundary.clone()));
     hittables.add(Box::new(ConstantMedium::new(
         Box::new(boundary),
@@ -765,32 +894,36 @@ fn custom_scene() -> World {
         Color::new(0.6, 0.1, 0.1),
     )));
 
-    hittables.add(Box::new(Sphere {
-        center: Point::new(220.0, 280.0, 300.0),
-        radius: 100.0,
-        material: Material::LambertianTexture(texture::image("neptune.jpg")),
-    }));
-    let perlin = Perlin::new();
-    hittables.add(Box::new(Sphere {
-        center: Point::new(400.0, 200.0, 400.0),
-        radius: 80.0,
-        material: Material::LambertianTexture(texture::marble(perlin, 4.0)),
-    }));
+    hittables.add(Box::new(Sphere::new(
+        Point::new(220.0, 280.0, 300.0),
+        100.0,
+        Material::LambertianTexture(texture::image("neptune.jpg")),
+    )));
+    // Seeded on its own, so the noise doesn't depend on the numbers the floor took
+    let perlin = Perlin::with_seed(0);
+    hittables.add(Box::new(Sphere::new(
+        Point::new(400.0, 200.0, 400.0),
+        80.0,
+        Material::LambertianTexture(texture::marble(perlin, 4.0)),
+    )));
 
     let mut spheres: Vec<Box<dyn Hittable>> = Vec::new();
 
     for _ in 0..1000 {
-        spheres.push(Box::new(Sphere {
-            center: Vec3::random_range(0.0, 165.0),
-            radius: 10.0,
-            material: Material::Lambertian(Color::from(0.73)),
-        }))
+        spheres.push(Box::new(Sphere::new(
+            Vec3::random_range(rng, 0.0, 165.0),
+            10.0,
+            Material::Lambertian(Color::from(0.73)),
+        )))
     }
 
     let bvh = {
         let bvh = BVHNode::new(spheres, 0.0, 1.0);
-        let rot = RotateY::new(Box::new(bvh), 15.0);
-        Translate::new(Box::new(rot), Vec3::new(-100.0, 270.0, 395.0))
+        Transform::new(
+            Box::new(bvh),
+            Matrix::translation(Vec3::new(-100.0, 270.0, 395.0))
+                * Matrix::rotation(Vec3::new(0., 1., 0.), 15.0),
+        )
     };
     hittables.add(Box::new(bvh));
 
@@ -818,9 +951,8 @@ fn custom_scene() -> World {
     }
 }
 
-fn space_dount() -> World {
+fn space_dount(rng: &mut Sampler) -> World {
     let mut hittables = HittableList::new();
-    let mut rng = rand::thread_rng();
 
     // Floor
     let mut boxes: Vec<Box<dyn Hittable>> = Vec::new();
@@ -831,7 +963,7 @@ fn space_dount() -> World {
             let z0 = -1000.0 + j as f32 * w;
             let y0 = 0.0;
             let x1 = x0 + w;
-            let y1 = 100.0 * (rand::thread_rng().gen::<f32>() + 0.01);
+            let y1 = 100.0 * (rng.gen::<f32>() + 0.01);
             let z1 = z0 + w;
             boxes.push(Box::new(Cube::new(
                 Point::new(x0, y0, z0),
@@ -839,7 +971,7 @@ fn space_dount() -> World {
                 if rng.gen::<f32>() < 0.2 {
                     Material::DiffuseLight(Color::new(10., 5., 5.))
                 } else {
-                    Material::Lambertian(Color::random())
+                    Material::Lambertian(Color::random(rng))
                 },
             )));
         }
@@ -855,23 +987,23 @@ fn space_dount() -> World {
         let x = rad.cos() * 800.;
         let y = 400. + rng.gen_range(-400., 400.);
         let z = rad.sin() * 800.;
-        stars.push(Box::new(Sphere {
-            center: Point::new(x, y, z),
-            radius: 0.5,
-            material: Material::DiffuseLight(Color::ones() * 10.),
-        }));
+        stars.push(Box::new(Sphere::new(
+            Point::new(x, y, z),
+            0.5,
+            Material::DiffuseLight(Color::ones() * 10.),
+        )));
     }
     let bvh = BVHNode::new(stars, 0.0, 1.0);
     hittables.add(Box::new(bvh));
 
-    hittables.add(Box::new(TracedSDF {
-        sdf: Box::new(SDFDonut {
+    hittables.add(Box::new(TracedSDF::new(
+        Box::new(SDFDonut {
             center: Point::new(220.0, 380.0, 300.0),
             radius0: 100.,
             radius1: 50.,
         }),
-        material: Material::Metal(Color::from(0.7), 0.),
-    }));
+        Material::Metal(Color::from(0.7), 0.),
+    )));
     // hittables.add(Box::new(Sphere {
     //     center: Point::new(200., 200., 100.),
     //     radius: 50.,
@@ -879,15 +1011,13 @@ fn space_dount() -> World {
     // }));
 
     // Ceiling light
-    hittables.add(Box::new(Rect {
-        a0: 123.0,
-        a1: 423.0,
-        b0: 147.0,
-        b1: 412.0,
-        k: 554.0,
-        material: Material::DiffuseLight(Color::new(7.0, 7.0, 7.0)),
-        plane: Plane::XZ,
-    }));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (123.0, 423.0),
+        (147.0, 412.0),
+        554.0,
+        Material::DiffuseLight(Color::new(7.0, 7.0, 7.0)),
+    )));
 
     // Camera
     let lookfrom = Point::new(478.0, 278.0, -600.0);
@@ -913,33 +1043,44 @@ fn space_dount() -> World {
     }
 }
 
-fn imagine() -> World {
+fn imagine(rng: &mut Sampler) -> World {
     let mut hittables = HittableList::new();
-    let mut rng = rand::thread_rng();
 
     let orange = Color::new(270. / 255., 106. / 255., 7. / 255.);
     let teal = Color::new(64. / 255., 231. / 255., 184. / 255.);
     let blue = Color::new(142. / 255., 226. / 255., 224. / 255.);
 
-    // Cylinder lights
+    // Cylinder lights, so long that they go past everything in view
     let orange_cylinder = Cylinder {
-        center: Point::new(100., 0., -30.),
-        radius: 10.,
-        material: Material::DiffuseLight(orange),
+        caps: false,
+        ..Cylinder::new(
+            Point::new(100., -10000., -30.),
+            Point::new(100., 10000., -30.),
+            10.,
+            Material::DiffuseLight(orange),
+        )
     };
     let teal_cylinder = Cylinder {
-        center: Point::new(100., 0., 0.),
-        radius: 10.,
-        material: Material::DiffuseLight(teal),
+        caps: false,
+        ..Cylinder::new(
+            Point::new(100., -10000., 0.),
+            Point::new(100., 10000., 0.),
+            10.,
+            Material::DiffuseLight(teal),
+        )
     };
     let blue_cylinder = Cylinder {
-        center: Point::new(100., 0., 30.),
-        radius: 10.,
-        material: Material::DiffuseLight(blue),
+        caps: false,
+        ..Cylinder::new(
+            Point::new(100., -10000., 30.),
+            Point::new(100., 10000., 30.),
+            10.,
+            Material::DiffuseLight(blue),
+        )
     };
-    hittables.add(box orange_cylinder.clone());
-    hittables.add(box teal_cylinder.clone());
-    hittables.add(box blue_cylinder.clone());
+    hittables.add(Box::new(orange_cylinder.clone()));
+    hittables.add(Box::new(teal_cylinder.clone()));
+    hittables.add(Box::new(blue_cylinder.clone()));
 
     // Gasses
     hittables.add(Box::new(ConstantMedium::new(
@@ -961,25 +1102,23 @@ fn imagine() -> World {
         Color::ones(),
     )));
     hittables.add(Box::new(ConstantMedium::new(
-        Box::new(Sphere {
-            center: Point::zeros(),
-            radius: 5000.,
-            material: Material::Dielectric(1.5),
-        }),
+        Box::new(Sphere::new(
+            Point::zeros(),
+            5000.,
+            Material::Dielectric(1.5),
+        )),
         0.0001,
         Color::ones(),
     )));
 
     // Floor
-    hittables.add(Box::new(Rect {
-        a0: -100.,
-        a1: 90.,
-        b0: -300.,
-        b1: 300.,
-        k: 0.,
-        material: Material::Metal(Color::from(0.9), 0.2),
-        plane: Plane::XZ,
-    }));
+    hittables.add(Box::new(Rect::new(
+        Plane::XZ,
+        (-100., 90.),
+        (-300., 300.),
+        0.,
+        Material::Metal(Color::from(0.9), 0.2),
+    )));
 
     // Pyramids
     let mut pyramids: Vec<Box<dyn Hittable>> = Vec::new();
@@ -991,7 +1130,7 @@ fn imagine() -> World {
             rng.gen_range(-140., 140.),
         );
 
-        pyramids.push(Box::new(RotateY::new(
+        pyramids.push(Box::new(Transform::new(
             Box::new(Pyramid::new(
                 top,
                 Point::new(top.x - size, -10., top.z + size),
@@ -1000,7 +1139,7 @@ fn imagine() -> World {
                 Point::new(top.x - size, -10., top.z - size),
                 Material::Metal(Color::from(0.9), 0.2),
             )),
-            rng.gen_range(-60., 60.),
+            Matrix::rotation(Vec3::new(0., 1., 0.), rng.gen_range(-60., 60.)),
         )));
     }
     let bvh = BVHNode::new(pyramids, 0.0, 1.0);
@@ -1070,25 +1209,23 @@ fn menger_sponge() -> World {
 fn mandelbulb() -> World {
     let mut hittables = HittableList::new();
 
-    hittables.add(box TracedSDF {
-        sdf: box SDFMandelBulb {
+    hittables.add(Box::new(TracedSDF::new(
+        Box::new(SDFMandelBulb {
             center: Vec3::zeros(),
-        },
-        material: Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
-    });
+        }),
+        Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
+    )));
 
     // Ceiling light
-    hittables.add(box FlipFace {
-        hittable: box Rect {
-            a0: -1.0,
-            a1: 1.0,
-            b0: -1.0,
-            b1: 1.0,
-            k: 5.0,
-            material: Material::DiffuseLight(Color::new(7.0, 7.0, 7.0)),
-            plane: Plane::XZ,
-        },
-    });
+    hittables.add(Box::new(FlipFace {
+        hittable: Box::new(Rect::new(
+            Plane::XZ,
+            (-1.0, 1.0),
+            (-1.0, 1.0),
+            5.0,
+            Material::DiffuseLight(Color::new(7.0, 7.0, 7.0)),
+        )),
+    }));
 
     // Camera
     let lookfrom = Point::new(13.0, 9.0, 13.0) * 0.25;
@@ -1120,13 +1257,13 @@ fn mandelbulb() -> World {
 fn mandelbox() -> World {
     let mut hittables = HittableList::new();
 
-    hittables.add(box TracedSDF {
-        sdf: box SDFMandelBox {
+    hittables.add(Box::new(TracedSDF::new(
+        Box::new(SDFMandelBox {
             center: Vec3::zeros(),
             scale: 2.,
-        },
-        material: Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
-    });
+        }),
+        Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
+    )));
 
     // Camera
     let lookfrom = Point::new(13.0, 9.0, 13.0) * 1.8;
@@ -1158,13 +1295,13 @@ fn mandelbox() -> World {
 fn knot() -> World {
     let mut hittables = HittableList::new();
 
-    hittables.add(box TracedSDF {
-        sdf: box SDFKnot {
+    hittables.add(Box::new(TracedSDF::new(
+        Box::new(SDFKnot {
             center: Point::zeros(),
             k: 3.5,
-        },
-        material: Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
-    });
+        }),
+        Material::Lambertian(Color::new(0.8, 0.1, 0.1)),
+    )));
 
     // Camera
     let lookfrom = Point::new(0.0, 0.0, 50.0) * 1.;
@@ -1198,77 +1335,68 @@ fn cornell_mandelbox() -> World {
 
     // Walls
     let size = 17.0;
-    cornell.add(Box::new(Rect {
-        a0: -size,
-        a1: size,
-        b0: -size,
-        b1: size,
-        k: size,
-        material: Material::Lambertian(Color::new(0.12, 0.45, 0.15)),
-        plane: Plane::YZ,
-    }));
-    cornell.add(Box::new(Rect {
-        a0: -size,
-        a1: size,
-        b0: -size,
-        b1: size,
-        k: -size,
-        material: Material::Lambertian(Color::new(0.65, 0.05, 0.05)),
-        plane: Plane::YZ,
-    }));
-    cornell.add(Box::new(Rect {
-        a0: -size,
-        a1: size,
-        b0: -size,
-        b1: size,
-        k: -size,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XZ,
-    }));
-    cornell.add(Box::new(Rect {
-        a0: -size,
-        a1: size,
-        b0: -size,
-        b1: size,
-        k: size,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XZ,
-    }));
-    cornell.add(Box::new(Rect {
-        a0: -size,
-        a1: size,
-        b0: -size,
-        b1: size,
-        k: size,
-        material: Material::Lambertian(Color::from(0.73)),
-        plane: Plane::XY,
-    }));
+    cornell.add(Box::new(Rect::new(
+        Plane::YZ,
+        (-size, size),
+        (-size, size),
+        size,
+        Material::Lambertian(Color::new(0.12, 0.45, 0.15)),
+    )));
+    cornell.add(Box::new(Rect::new(
+        Plane::YZ,
+        (-size, size),
+        (-size, size),
+        -size,
+        Material::Lambertian(Color::new(0.65, 0.05, 0.05)),
+    )));
+    cornell.add(Box::new(Rect::new(
+        Plane::XZ,
+        (-size, size),
+        (-size, size),
+        -size,
+        Material::Lambertian(Color::from(0.73)),
+    )));
+    cornell.add(Box::new(Rect::new(
+        Plane::XZ,
+        (-size, size),
+        (-size, size),
+        size,
+        Material::Lambertian(Color::from(0.73)),
+    )));
+    cornell.add(Box::new(Rect::new(
+        Plane::XY,
+        (-size, size),
+        (-size, size),
+        size,
+        Material::Lambertian(Color::from(0.73)),
+    )));
 
     // Light
     cornell.add(Box::new(FlipFace {
-        hittable: Box::new(Rect {
-            a0: -3.0,
-            a1: 3.0,
-            b0: -3.0,
-            b1: 3.0,
-            k: size,
-            material: Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
-            plane: Plane::XZ,
-        }),
+        hittable: Box::new(Rect::new(
+            Plane::XZ,
+            (-3.0, 3.0),
+            (-3.0, 3.0),
+            size,
+            Material::DiffuseLight(Color::new(15.0, 15.0, 15.0)),
+        )),
     }));
 
     let mut hittables = HittableList::new();
-    hittables.add(box Translate::new(box cornell, Vec3::new(0., 11., 0.)));
-    hittables.add(box RotateY::new(
-        box TracedSDF {
-            sdf: box SDFMandelBox {
+    hittables.add(Box::new(Transform::new(
+        Box::new(cornell),
+        Matrix::translation(Vec3::new(0., 11., 0.)),
+    )));
+    hittables.add(Box::new(Transform::new(
+        Box::new(TracedSDF::new(
+            Box::new(SDFMandelBox {
                 center: Point::zeros(),
                 scale: 2.,
-            },
-            material: Material::Metal(Color::new(0.8, 0.8, 0.8), 0.),
-        },
-        15.0,
-    ));
+            }),
+            Material::Metal(Color::new(0.8, 0.8, 0.8), 0.),
+        )),
+        Matrix::rotation(Vec3::new(0., 1., 0.), 15.0),
+    )));
 
     let lookfrom = Point::new(0.0, 15.0, -61.0);
     let lookat = Point::new(0., 10., 0.);
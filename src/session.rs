@@ -0,0 +1,120 @@
+use crate::stats::*;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Handle to a render, which other threads can use to follow its progress or cancel it.
+/// Share it with an `Arc`, or a reference from a scoped thread.
+///
+/// Cancelling stops the render after the pixels that are being rendered, and the render
+/// returns the image as it is, with black in the pixels that have no samples yet.
+#[derive(Default)]
+pub struct RenderSession {
+    cancelled: AtomicBool,
+    /// When the render started, and how long it took once it's done
+    time: Mutex<(Option<Instant>, Option<Duration>)>,
+    /// Most samples the render takes
+    total_samples: AtomicU64,
+    /// Samples taken, counting the ones of the checkpoint the render started from
+    finished_samples: AtomicU64,
+    samples: AtomicU64,
+    rays: AtomicU64,
+    intersection_tests: AtomicU64,
+    bvh_nodes_visited: AtomicU64,
+}
+
+impl RenderSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Starts counting. A render resumed from a checkpoint already has some samples finished.
+    pub fn start(&self, total_samples: u64, finished_samples: u64) {
+        *self.time.lock().unwrap() = (Some(Instant::now()), None);
+        self.total_samples.store(total_samples, Ordering::Relaxed);
+        self.finished_samples
+            .store(finished_samples, Ordering::Relaxed);
+        for counter in &[
+            &self.samples,
+            &self.rays,
+            &self.intersection_tests,
+            &self.bvh_nodes_visited,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Stops the clock
+    pub fn finish(&self) {
+        let mut time = self.time.lock().unwrap();
+        if let (Some(started), None) = *time {
+            time.1 = Some(started.elapsed());
+        }
+    }
+
+    /// Adds the work done on a part of the image
+    pub fn add(&self, finished_samples: u64, stats: &RenderStats) {
+        self.finished_samples
+            .fetch_add(finished_samples, Ordering::Relaxed);
+        self.samples.fetch_add(stats.samples, Ordering::Relaxed);
+        self.rays.fetch_add(stats.rays, Ordering::Relaxed);
+        self.intersection_tests
+            .fetch_add(stats.intersection_tests, Ordering::Relaxed);
+        self.bvh_nodes_visited
+            .fetch_add(stats.bvh_nodes_visited, Ordering::Relaxed);
+    }
+
+    /// How much of the render is done, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        let total = self.total_samples.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.;
+        }
+        let finished = self.finished_samples.load(Ordering::Relaxed);
+        (finished as f64 / total as f64).min(1.) as f32
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match *self.time.lock().unwrap() {
+            (_, Some(elapsed)) => elapsed,
+            (Some(started), None) => started.elapsed(),
+            (None, None) => Duration::default(),
+        }
+    }
+
+    /// Estimated time left, assuming the rest of the image takes as long as what's done.
+    /// With adaptive sampling it's pessimistic, as it expects the whole budget to be used.
+    pub fn eta(&self) -> Option<Duration> {
+        let progress = self.progress() as f64;
+        if progress <= 0. {
+            return None;
+        }
+        Some(self.elapsed().mul_f64((1. - progress) / progress))
+    }
+
+    pub fn samples_per_second(&self) -> f64 {
+        self.stats().samples_per_second()
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        RenderStats {
+            samples: self.samples.load(Ordering::Relaxed),
+            rays: self.rays.load(Ordering::Relaxed),
+            intersection_tests: self.intersection_tests.load(Ordering::Relaxed),
+            bvh_nodes_visited: self.bvh_nodes_visited.load(Ordering::Relaxed),
+            elapsed: self.elapsed(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{cell::Cell, fmt, time::Duration};
+
+/// Work done by a render
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub samples: u64,
+    /// Rays traced through the scene, from the camera and bounces
+    pub rays: u64,
+    /// Rays tested against a primitive (sphere, rectangle, triangle...)
+    pub intersection_tests: u64,
+    pub bvh_nodes_visited: u64,
+    pub elapsed: Duration,
+}
+
+impl RenderStats {
+    pub fn samples_per_second(&self) -> f64 {
+        per_second(self.samples, self.elapsed)
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        per_second(self.rays, self.elapsed)
+    }
+}
+
+fn per_second(count: u64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0. {
+        count as f64 / seconds
+    } else {
+        0.
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} samples in {:.2?} ({:.0} samples/s)",
+            self.samples,
+            self.elapsed,
+            self.samples_per_second()
+        )?;
+        writeln!(
+            f,
+            "{} rays ({:.0} rays/s)",
+            self.rays,
+            self.rays_per_second()
+        )?;
+        writeln!(f, "{} intersection tests", self.intersection_tests)?;
+        write!(f, "{} BVH nodes visited", self.bvh_nodes_visited)
+    }
+}
+
+// The counters are per thread, so counting is just an increment. The renderer takes them
+// after every tile and adds them to the session.
+thread_local! {
+    static RAYS: Cell<u64> = const { Cell::new(0) };
+    static INTERSECTION_TESTS: Cell<u64> = const { Cell::new(0) };
+    static BVH_NODES_VISITED: Cell<u64> = const { Cell::new(0) };
+}
+
+#[inline(always)]
+fn increment(counter: &'static std::thread::LocalKey<Cell<u64>>) {
+    counter.with(|count| count.set(count.get() + 1));
+}
+
+#[inline(always)]
+pub fn count_ray() {
+    increment(&RAYS);
+}
+
+#[inline(always)]
+pub fn count_intersection_test() {
+    increment(&INTERSECTION_TESTS);
+}
+
+#[inline(always)]
+pub fn count_bvh_node() {
+    increment(&BVH_NODES_VISITED);
+}
+
+/// Counts of this thread since the last time they were taken, which get reset
+pub fn take_thread_stats() -> RenderStats {
+    RenderStats {
+        rays: RAYS.with(|count| count.replace(0)),
+        intersection_tests: INTERSECTION_TESTS.with(|count| count.replace(0)),
+        bvh_nodes_visited: BVH_NODES_VISITED.with(|count| count.replace(0)),
+        ..RenderStats::default()
+    }
+}
@@ -1,6 +1,9 @@
+//! Textures for materials, which give a color from the `u` and `v` coordinates of the
+//! surface and the point that was hit.
+
 use crate::{perlin::Perlin, vec3::*};
 use image as img;
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 pub type Texture = Arc<dyn Fn(f32, f32, Vec3) -> Vec3 + Send + Sync>;
 
@@ -8,6 +11,7 @@ pub fn solid_color(color: Vec3) -> Texture {
     Arc::new(move |_, _, _| color)
 }
 
+/// 3D checkerboard of two textures
 pub fn checker(even: Texture, odd: Texture) -> Texture {
     Arc::new(move |u, v, p| {
         let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
@@ -40,13 +44,24 @@ pub fn marble(perlin: Perlin, scale: f32) -> Texture {
     })
 }
 
+/// Image wrapped around the object. Panics if it can't be loaded, `load_image` returns the error.
 pub fn image(filename: &str) -> Texture {
-    let image = img::open(filename).expect("Image doesn't exist").to_rgb();
+    load_image(filename).expect("Image doesn't exist")
+}
+
+pub fn load_image(path: impl AsRef<Path>) -> img::ImageResult<Texture> {
+    let image = img::open(path)?.to_rgb();
     let (width, height) = image.dimensions();
-    let width = width as usize;
-    let height = height as usize;
+    Ok(rgb_image(width as usize, height as usize, image.into_raw()))
+}
 
-    let data = image.into_raw();
+/// Image from its pixels, 3 bytes each, row by row from the top
+pub fn rgb_image(width: usize, height: usize, data: Vec<u8>) -> Texture {
+    assert_eq!(
+        data.len(),
+        3 * width * height,
+        "Wrong size of the image data"
+    );
 
     Arc::new(move |u, v, _| {
         let u = u.clamp(0.0, 1.0);
@@ -0,0 +1,189 @@
+use std::str::FromStr;
+
+/// Rectangle of the image that gets rendered as a unit. Coordinates are in pixels,
+/// from the top left corner, like the framebuffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Indices of the pixels of the tile in an image `image_width` pixels wide,
+    /// row by row from the top
+    pub fn pixel_indices(&self, image_width: usize) -> impl Iterator<Item = usize> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| x + y * image_width))
+    }
+}
+
+/// Order in which the tiles are rendered
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// From the center outwards, so the interesting part of the image shows up first
+    #[default]
+    Spiral,
+    /// Following a Hilbert curve, which keeps consecutive tiles close to each other
+    Hilbert,
+    /// Row by row, from the top
+    Scanline,
+}
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "spiral" => Ok(Self::Spiral),
+            "hilbert" => Ok(Self::Hilbert),
+            "scanline" => Ok(Self::Scanline),
+            _ => Err(format!(
+                "unknown tile order {}, expected spiral, hilbert or scanline",
+                s
+            )),
+        }
+    }
+}
+
+/// Splits a `width` x `height` image in tiles of `size` x `size` pixels, smaller at the
+/// right and bottom edges, sorted in `order`
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    let tile = |(column, row): (usize, usize)| {
+        let x = column * size;
+        let y = row * size;
+        Tile {
+            x,
+            y,
+            width: size.min(width - x),
+            height: size.min(height - y),
+        }
+    };
+
+    let coordinates: Vec<(usize, usize)> = match order {
+        TileOrder::Scanline => (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => hilbert(columns, rows),
+    };
+
+    coordinates.into_iter().map(tile).collect()
+}
+
+/// Walks a square spiral from the center of the grid, keeping the cells inside it
+fn spiral(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let total = columns * rows;
+    let mut cells = Vec::with_capacity(total);
+
+    let mut x = (columns as isize - 1) / 2;
+    let mut y = (rows as isize - 1) / 2;
+    let (mut dx, mut dy) = (1, 0);
+    let mut leg_length = 1;
+
+    let push = |x: isize, y: isize, cells: &mut Vec<(usize, usize)>| {
+        if x >= 0 && y >= 0 && (x as usize) < columns && (y as usize) < rows {
+            cells.push((x as usize, y as usize));
+        }
+    };
+
+    push(x, y, &mut cells);
+    while cells.len() < total {
+        // Every two legs of the spiral, they get one cell longer
+        for _ in 0..2 {
+            for _ in 0..leg_length {
+                x += dx;
+                y += dy;
+                push(x, y, &mut cells);
+            }
+            let turned = (-dy, dx);
+            dx = turned.0;
+            dy = turned.1;
+        }
+        leg_length += 1;
+    }
+
+    cells
+}
+
+/// Walks a Hilbert curve over the smallest power of two square that covers the grid,
+/// keeping the cells inside it
+fn hilbert(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let side = columns.max(rows).next_power_of_two();
+
+    (0..side * side)
+        .map(|index| hilbert_cell(side, index))
+        .filter(|(x, y)| *x < columns && *y < rows)
+        .collect()
+}
+
+/// Cell at `index` along a Hilbert curve that fills a `side` x `side` square
+fn hilbert_cell(side: usize, index: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = index;
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+
+        // Rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the tiles cover every pixel of the image once, and stay inside it
+    fn check(width: usize, height: usize, size: usize, order: TileOrder) {
+        let mut covered = vec![0; width * height];
+        for tile in tiles(width, height, size, order) {
+            assert!(tile.width > 0 && tile.height > 0, "{:?} is empty", tile);
+            assert!(tile.x + tile.width <= width && tile.y + tile.height <= height);
+            for index in tile.pixel_indices(width) {
+                covered[index] += 1;
+            }
+        }
+
+        assert!(
+            covered.iter().all(|&count| count == 1),
+            "{:?} tiles of {} don't cover {}x{} once: {:?}",
+            order,
+            size,
+            width,
+            height,
+            covered
+        );
+    }
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        for &order in &[TileOrder::Spiral, TileOrder::Hilbert, TileOrder::Scanline] {
+            for &(width, height) in &[(64, 64), (100, 37), (37, 100), (1, 1), (1, 19), (300, 2)] {
+                for &size in &[1, 7, 16, 64, 1000] {
+                    check(width, height, size, order);
+                }
+            }
+            assert!(tiles(0, 10, 16, order).is_empty());
+        }
+    }
+}
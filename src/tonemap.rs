@@ -0,0 +1,121 @@
+use crate::vec3::*;
+use std::str::FromStr;
+
+/// Operators that compress linear HDR radiance into the [0, 1] display range
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ToneMap {
+    /// Just clamps, everything over 1 blows out
+    #[default]
+    Clamp,
+    Reinhard,
+    /// Reinhard that maps `white` to 1 instead of infinity
+    ExtendedReinhard {
+        white: f32,
+    },
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+    /// Hable's Uncharted 2 filmic curve
+    Hable,
+}
+
+impl ToneMap {
+    pub fn map(&self, color: Color) -> Color {
+        match self {
+            Self::Clamp => color,
+            Self::Reinhard => color / (Color::ones() + color),
+            Self::ExtendedReinhard { white } => {
+                color * (Color::ones() + color / (white * white)) / (Color::ones() + color)
+            }
+            Self::Aces => {
+                let a = 2.51;
+                let b = 0.03;
+                let c = 2.43;
+                let d = 0.59;
+                let e = 0.14;
+                (color * (a * color + Color::from(b)))
+                    / (color * (c * color + Color::from(d)) + Color::from(e))
+            }
+            Self::Hable => {
+                let exposure_bias = 2.0;
+                let white = 11.2;
+                hable(exposure_bias * color) / hable(Color::from(white))
+            }
+        }
+        .clamp(0.0, 1.0)
+    }
+}
+
+fn hable(x: Color) -> Color {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    (x * (a * x + Color::from(c * b)) + Color::from(d * e))
+        / (x * (a * x + Color::from(b)) + Color::from(d * f))
+        - Color::from(e / f)
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clamp" | "linear" => Ok(Self::Clamp),
+            "reinhard" => Ok(Self::Reinhard),
+            "extended-reinhard" => Ok(Self::ExtendedReinhard { white: 4.0 }),
+            "aces" => Ok(Self::Aces),
+            "hable" | "filmic" => Ok(Self::Hable),
+            _ => {
+                // extended-reinhard:<white>
+                if let Some(white) = s.strip_prefix("extended-reinhard:") {
+                    if let Ok(white) = white.parse() {
+                        return Ok(Self::ExtendedReinhard { white });
+                    }
+                }
+                Err(format!("Unknown tone mapping operator: {}", s))
+            }
+        }
+    }
+}
+
+/// Everything needed to go from scene linear radiance to 8 bit sRGB
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DisplayTransform {
+    pub tone_map: ToneMap,
+    /// Exposure compensation in stops
+    pub exposure: f32,
+}
+
+impl DisplayTransform {
+    pub fn to_rgb8(&self, color: Color) -> [u8; 3] {
+        let color = color.nan_to_zero() * 2_f32.powf(self.exposure);
+        let color = self.tone_map.map(color);
+
+        let ir = (255.999 * srgb_oetf(color.x)) as u8;
+        let ig = (255.999 * srgb_oetf(color.y)) as u8;
+        let ib = (255.999 * srgb_oetf(color.z)) as u8;
+
+        [ir, ig, ib]
+    }
+}
+
+/// sRGB transfer function, from linear to encoded values
+pub fn srgb_oetf(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_oetf`, from encoded values to linear
+pub fn srgb_eotf(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
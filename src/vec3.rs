@@ -136,8 +136,7 @@ impl Vec3 {
     }
 
     #[inline(always)]
-    pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         Self {
             x: rng.gen::<f32>(),
             y: rng.gen::<f32>(),
@@ -146,8 +145,7 @@ impl Vec3 {
     }
 
     #[inline(always)]
-    pub fn random_range(min: f32, max: f32) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn random_range<R: Rng + ?Sized>(rng: &mut R, min: f32, max: f32) -> Self {
         Self {
             x: rng.gen_range(min, max),
             y: rng.gen_range(min, max),
@@ -156,9 +154,9 @@ impl Vec3 {
     }
 
     #[inline(always)]
-    pub fn random_in_unit_sphere() -> Self {
+    pub fn random_in_unit_sphere<R: Rng + ?Sized>(rng: &mut R) -> Self {
         loop {
-            let vec = Self::random_range(-1.0, 1.0);
+            let vec = Self::random_range(rng, -1.0, 1.0);
 
             if vec.length() < 1.0 {
                 return vec;
@@ -167,11 +165,16 @@ impl Vec3 {
     }
 
     #[inline(always)]
-    pub fn random_unit_vector() -> Self {
-        let mut rng = rand::thread_rng();
-        let a = rng.gen_range(0.0, TAU);
-        let z: f32 = rng.gen_range(-1.0, 1.0);
-        let r = (1.0 - z * z).sqrt();
+    pub fn random_unit_vector<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::on_unit_sphere((rng.gen(), rng.gen()))
+    }
+
+    /// Maps a point of the unit square to the unit sphere, keeping the areas
+    #[inline(always)]
+    pub fn on_unit_sphere((u, v): (f32, f32)) -> Self {
+        let a = TAU * u;
+        let z = 1.0 - 2.0 * v;
+        let r = (1.0 - z * z).max(0.0).sqrt();
         Self {
             x: r * a.cos(),
             y: r * a.sin(),
@@ -180,8 +183,8 @@ impl Vec3 {
     }
 
     #[inline(always)]
-    pub fn random_in_hemisphere(normal: &Vec3) -> Self {
-        let in_unit_sphere = Self::random_in_unit_sphere();
+    pub fn random_in_hemisphere<R: Rng + ?Sized>(rng: &mut R, normal: &Vec3) -> Self {
+        let in_unit_sphere = Self::random_in_unit_sphere(rng);
         if in_unit_sphere.dot(normal) > 0.0 {
             in_unit_sphere
         } else {
@@ -189,10 +192,12 @@ impl Vec3 {
         }
     }
 
-    pub fn random_cosine_direction() -> Self {
-        let mut rng = rand::thread_rng();
-        let r1 = rng.gen::<f32>();
-        let r2 = rng.gen::<f32>();
+    pub fn random_cosine_direction<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::cosine_direction((rng.gen(), rng.gen()))
+    }
+
+    /// Maps a point of the unit square to a direction around z, with a pdf of cos(theta) / pi
+    pub fn cosine_direction((r1, r2): (f32, f32)) -> Self {
         let z = (1. - r2).sqrt();
 
         let phi = 2. * PI * r1;
@@ -203,25 +208,39 @@ impl Vec3 {
     }
 
     #[inline(always)]
-    pub fn random_in_unit_disk() -> Self {
-        let mut rng = rand::thread_rng();
-        loop {
-            let vec = Self {
-                x: rng.gen_range(-1.0, 1.0),
-                y: rng.gen_range(-1.0, 1.0),
-                z: 0.0,
-            };
+    pub fn random_in_unit_disk<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::in_unit_disk((rng.gen(), rng.gen()))
+    }
 
-            if vec.length_squared() < 1.0 {
-                return vec;
-            }
+    /// Maps a point of the unit square to the unit disk, keeping the areas and
+    /// without distorting the strata much (Shirley's concentric mapping)
+    #[inline(always)]
+    pub fn in_unit_disk((u, v): (f32, f32)) -> Self {
+        let x = 2.0 * u - 1.0;
+        let y = 2.0 * v - 1.0;
+        if x == 0.0 && y == 0.0 {
+            return Self::zeros();
         }
+
+        let (r, theta) = if x.abs() > y.abs() {
+            (x, FRAC_PI_4 * (y / x))
+        } else {
+            (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+        };
+        Self::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
-    pub fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        let r1 = rng.gen::<f32>();
-        let r2 = rng.gen::<f32>();
+    pub fn random_to_sphere<R: Rng + ?Sized>(
+        rng: &mut R,
+        radius: f32,
+        distance_squared: f32,
+    ) -> Vec3 {
+        Self::to_sphere(radius, distance_squared, (rng.gen(), rng.gen()))
+    }
+
+    /// Maps a point of the unit square to a direction around z that points to a sphere
+    /// of `radius` at `distance_squared`, uniformly on the solid angle
+    pub fn to_sphere(radius: f32, distance_squared: f32, (r1, r2): (f32, f32)) -> Vec3 {
         let z = 1. + r2 * ((1. - radius * radius / distance_squared).sqrt() - 1.);
 
         let phi = 2. * PI * r1;
@@ -246,34 +265,17 @@ impl Vec3 {
 }
 
 impl Color {
-    pub fn write_color(&self, samples_per_pixel: u16) -> String {
-        let Color {
-            x: mut r,
-            y: mut g,
-            z: mut b,
-        } = self;
-
-        // Replace NaN components with zero. See explanation in Ray Tracing: The Rest of Your Life.
-        if r.is_nan() {
-            r = 0.0
-        }
-        if g.is_nan() {
-            g = 0.0
-        }
-        if b.is_nan() {
-            b = 0.0
+    /// Replaces NaN components with zero. See explanation in Ray Tracing: The Rest of Your Life.
+    pub fn nan_to_zero(&self) -> Self {
+        fn fix(value: f32) -> f32 {
+            if value.is_nan() {
+                0.0
+            } else {
+                value
+            }
         }
 
-        let scale = 1.0 / samples_per_pixel as f32;
-        r = (scale * r).sqrt();
-        g = (scale * g).sqrt();
-        b = (scale * b).sqrt();
-
-        let ir = (255.999 * r.clamp(0.0, 0.999)) as u16;
-        let ig = (255.999 * g.clamp(0.0, 0.999)) as u16;
-        let ib = (255.999 * b.clamp(0.0, 0.999)) as u16;
-
-        format!("{} {} {}\n", ir, ig, ib)
+        Self::new(fix(self.x), fix(self.y), fix(self.z))
     }
 }
 
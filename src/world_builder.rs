@@ -0,0 +1,115 @@
+use crate::{
+    adaptive::*, camera::*, denoise::*, hittable::*, sampler::*, scenes::World, tiles::*,
+    tonemap::*, vec3::*,
+};
+
+/// Builds a `World` from its objects and settings. Anything that isn't set keeps the value of
+/// `World::default()`, and the camera gets built for the aspect ratio of the image at the end.
+#[derive(Default)]
+pub struct WorldBuilder {
+    world: World,
+    camera: CameraSettings,
+}
+
+impl World {
+    pub fn builder() -> WorldBuilder {
+        WorldBuilder::default()
+    }
+}
+
+impl WorldBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_object(mut self, object: impl Hittable + 'static) -> Self {
+        self.world.hittables.add(Box::new(object));
+        self
+    }
+
+    /// Adds an object that the renderer sends rays towards, so small or bright lights get
+    /// found with less noise. It isn't seen unless it's also added with `add_object`.
+    pub fn add_light(mut self, light: impl Hittable + 'static) -> Self {
+        self.world.lights.add(Box::new(light));
+        self
+    }
+
+    pub fn camera(mut self, camera: CameraSettings) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    /// Color of the sky, in every direction
+    pub fn background(self, color: Color) -> Self {
+        self.background_gradient(color, color)
+    }
+
+    /// Sky that goes from `bottom` when looking down to `top` when looking up
+    pub fn background_gradient(mut self, top: Color, bottom: Color) -> Self {
+        self.world.background_color_top = top;
+        self.world.background_color_bottom = bottom;
+        self
+    }
+
+    pub fn image_width(mut self, width: u16) -> Self {
+        self.world.image_width = width;
+        self
+    }
+
+    pub fn aspect_ratio(mut self, aspect_ratio: f32) -> Self {
+        self.world.aspect_ratio = aspect_ratio;
+        self
+    }
+
+    pub fn samples_per_pixel(mut self, samples: u16) -> Self {
+        self.world.samples_per_pixel = samples;
+        self
+    }
+
+    pub fn adaptive(mut self, adaptive: AdaptiveSampling) -> Self {
+        self.world.adaptive = Some(adaptive);
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: u16) -> Self {
+        self.world.max_depth = max_depth;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.world.seed = seed;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+        self.world.sampler = sampler;
+        self
+    }
+
+    pub fn tiles(mut self, size: u16, order: TileOrder) -> Self {
+        self.world.tile_size = size;
+        self.world.tile_order = order;
+        self
+    }
+
+    pub fn display(mut self, display: DisplayTransform) -> Self {
+        self.world.display = display;
+        self
+    }
+
+    pub fn aovs(mut self, aovs: bool) -> Self {
+        self.world.aovs = aovs;
+        self
+    }
+
+    pub fn denoise(mut self, denoiser: Denoiser) -> Self {
+        self.world.denoise = Some(denoiser);
+        self
+    }
+
+    pub fn build(mut self) -> World {
+        self.world.camera = self.camera.build(self.world.aspect_ratio);
+        self.world.number_objects();
+        self.world
+    }
+}
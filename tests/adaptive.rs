@@ -0,0 +1,70 @@
+//! Adaptive sampling: pixels of the flat sky stop early, and the samples they don't take go
+//! to the noisy ones, without the image going over `samples_per_pixel` on average.
+
+mod common;
+
+use sunray::{Accumulation, AdaptiveSampling, Progressive, World};
+
+const WIDTH: u16 = 16;
+const SAMPLES_PER_PIXEL: u16 = 16;
+
+/// The sphere of `common::world` is noisy, and the sky around it has no noise at all
+fn world(adaptive: AdaptiveSampling) -> World {
+    common::world(WIDTH, SAMPLES_PER_PIXEL, 5)
+        .adaptive(adaptive)
+        .build()
+}
+
+#[test]
+fn samples_stay_within_the_budget() {
+    // Pixels on the sphere never converge with such a low threshold
+    let adaptive = AdaptiveSampling {
+        min_samples: 4,
+        max_samples: 1024,
+        noise_threshold: 1e-4,
+    };
+    let world = world(adaptive);
+
+    let output = sunray::render_world(&world);
+    assert!(
+        output.average_samples <= SAMPLES_PER_PIXEL as f32,
+        "{} samples on average",
+        output.average_samples
+    );
+
+    let mut accumulation = Accumulation::new(&world);
+    Progressive::default()
+        .render(&world, &mut accumulation)
+        .unwrap();
+    let budget = SAMPLES_PER_PIXEL as u64 * accumulation.pixels.len() as u64;
+    assert!(accumulation.samples() <= budget);
+
+    // The sky only takes the least samples, and the sphere gets what it left
+    let samples: Vec<u32> = accumulation
+        .pixels
+        .iter()
+        .map(|pixel| pixel.samples)
+        .collect();
+    let least = *samples.iter().min().unwrap();
+    let most = *samples.iter().max().unwrap();
+    assert_eq!(least, adaptive.min_samples as u32);
+    assert!(
+        most > 2 * SAMPLES_PER_PIXEL as u32,
+        "the noisiest pixel only got {} samples",
+        most
+    );
+    // And almost nothing of the budget is left
+    assert!(accumulation.samples() * 10 >= budget * 9);
+}
+
+#[test]
+fn converged_images_stop_early() {
+    // Everything converges with a high threshold, so the render takes less than the budget
+    let world = world(AdaptiveSampling {
+        min_samples: 4,
+        max_samples: 1024,
+        noise_threshold: 10.,
+    });
+    let output = sunray::render_world(&world);
+    assert_eq!(output.average_samples, 4.);
+}
@@ -0,0 +1,93 @@
+//! AOVs: every object has its own id, even inside a BVH or as an instance of a shared
+//! prototype, the ids are the same every time a world is built, and images of a single
+//! pixel row or column still see the whole view.
+
+mod common;
+
+use common::material;
+use std::{collections::BTreeSet, sync::Arc};
+use sunray::{BVHNode, Hittable, Instance, Matrix, Point, Sphere, Vec3, World};
+
+/// Id of the object seen in each pixel, 0 for the background
+fn id_image(world: &World) -> Vec<u32> {
+    let aovs = sunray::render_world(world).aovs.unwrap();
+    aovs.object_id
+        .pixels()
+        .iter()
+        .map(|id| id.x as u32)
+        .collect()
+}
+
+/// Ids of the objects seen in the image, without the background's
+fn object_ids(world: &World) -> BTreeSet<u32> {
+    id_image(world).into_iter().filter(|&id| id != 0).collect()
+}
+
+fn world(object: impl Hittable + 'static) -> World {
+    common::builder(object)
+        .image_width(32)
+        .samples_per_pixel(1)
+        .aovs(true)
+        .seed(2)
+        .build()
+}
+
+/// Three spheres in a row, in a BVH
+fn spheres() -> BVHNode {
+    let spheres: Vec<Box<dyn Hittable>> = (0..3)
+        .map(|i| {
+            Box::new(Sphere::new(
+                Point::new(i as f32 * 2.5 - 2.5, 0., 0.),
+                1.,
+                material(),
+            )) as Box<dyn Hittable>
+        })
+        .collect();
+    BVHNode::new(spheres, 0., 1.)
+}
+
+#[test]
+fn objects_in_a_bvh_have_their_own_id() {
+    let ids = object_ids(&world(spheres()));
+    assert_eq!(ids.len(), 3, "{:?}", ids);
+}
+
+#[test]
+fn ids_are_the_same_every_time() {
+    let ids = id_image(&world(spheres()));
+    assert_eq!(ids, id_image(&world(spheres())));
+    // Counted from 1 in every world, whatever was built before it
+    assert_eq!(object_ids(&world(spheres())), (1..=3).collect());
+}
+
+#[test]
+fn instances_have_their_own_id() {
+    let prototype: Arc<dyn Hittable> = Arc::new(Sphere::new(Point::zeros(), 1., material()));
+    let instances: Vec<Box<dyn Hittable>> = (0..2)
+        .map(|i| {
+            let matrix = Matrix::translation(Vec3::new(i as f32 * 3. - 1.5, 0., 0.));
+            Box::new(Instance::new(&prototype, matrix)) as Box<dyn Hittable>
+        })
+        .collect();
+    let ids = object_ids(&world(BVHNode::new(instances, 0., 1.)));
+    assert_eq!(ids.len(), 2, "{:?}", ids);
+}
+
+#[test]
+fn images_one_pixel_wide_see_the_view() {
+    for &(width, aspect_ratio) in &[(1, 1.), (1, 0.25), (2, 16. / 9.), (0, 1.)] {
+        let mut world = world(Sphere::new(Point::zeros(), 1., material()));
+        world.image_width = width;
+        world.aspect_ratio = aspect_ratio;
+        let image = sunray::render_world(&world).image;
+        assert_eq!(image.width, width as usize);
+        // Lit by the sky, so a pixel that's black didn't get its rays
+        assert!(
+            image.pixels().iter().all(|pixel| pixel.x > 0.),
+            "{}x{} has black pixels: {:?}",
+            image.width,
+            image.height,
+            image.pixels()
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Benchmark reports: they read back from their CSV, and comparing with a baseline finds
+//! the scenes that got slower than the tolerance.
+
+use std::time::Duration;
+use sunray::{BenchReport, BenchResult, RenderStats};
+
+fn result(scene: &str, seconds: f64, width: u16) -> BenchResult {
+    BenchResult {
+        scene: scene.to_string(),
+        width,
+        height: width as usize / 2,
+        samples_per_pixel: 16,
+        seed: 3,
+        build_time: Duration::from_micros(12_345),
+        stats: RenderStats {
+            samples: 320_000,
+            rays: 1_234_567,
+            intersection_tests: 98_765_432,
+            bvh_nodes_visited: 55_555_555,
+            elapsed: Duration::from_secs_f64(seconds),
+        },
+        peak_memory: 64 << 20,
+    }
+}
+
+#[test]
+fn reports_read_back() {
+    let report = BenchReport {
+        results: vec![result("cornell-box", 1.5, 200), result("knot", 0.25, 100)],
+    };
+    let read: BenchReport = report.to_string().parse().unwrap();
+    assert_eq!(read, report);
+
+    assert!("".parse::<BenchReport>().is_err());
+    let without_rays = report.to_string().replace(",rays,", ",beams,");
+    assert!(without_rays.parse::<BenchReport>().is_err());
+    let broken = report
+        .to_string()
+        .replace("cornell-box,200", "cornell-box,wide");
+    let error = broken.parse::<BenchReport>().unwrap_err();
+    assert!(error.contains("row 1"), "{}", error);
+}
+
+#[test]
+fn regressions_go_over_the_tolerance() {
+    let baseline = BenchReport {
+        results: vec![
+            result("cornell-box", 1., 200),
+            result("knot", 1., 200),
+            result("menger-sponge", 1., 200),
+            result("mandelbulb", 1., 200),
+        ],
+    };
+    let report = BenchReport {
+        results: vec![
+            result("cornell-box", 1.04, 200),
+            result("knot", 1.1, 200),
+            // Slower, but rendered with another width, so it can't be compared
+            result("menger-sponge", 2., 400),
+            result("mandelbulb", 0.5, 200),
+            // Not in the baseline
+            result("final-scene", 9., 200),
+        ],
+    };
+
+    let comparisons = report.compare(&baseline);
+    let scenes: Vec<&str> = comparisons
+        .iter()
+        .map(|comparison| comparison.result.scene.as_str())
+        .collect();
+    assert_eq!(
+        scenes,
+        ["cornell-box", "knot", "menger-sponge", "mandelbulb"]
+    );
+
+    let regressed: Vec<bool> = comparisons
+        .iter()
+        .map(|comparison| comparison.regressed(5.))
+        .collect();
+    assert_eq!(regressed, [false, true, false, false]);
+    assert!((comparisons[1].time_change() - 0.1).abs() < 1e-9);
+    assert!((comparisons[3].time_change() + 0.5).abs() < 1e-9);
+    assert!(!comparisons[2].same_settings());
+    assert!(!comparisons[0].different_work());
+}
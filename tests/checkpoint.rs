@@ -0,0 +1,91 @@
+//! Checkpoints of progressive renders: they come back as they were saved, broken files are
+//! rejected before anything big gets allocated, and a world can only resume one it matches.
+
+mod common;
+
+use std::io::{self, Cursor};
+use sunray::{Accumulation, Progressive, SamplerKind, World};
+
+fn world(samples_per_pixel: u16, seed: u64, sampler: SamplerKind) -> World {
+    common::world(8, samples_per_pixel, seed)
+        .sampler(sampler)
+        .build()
+}
+
+/// A checkpoint of `world`, after rendering all of its samples
+fn checkpoint(world: &World) -> Vec<u8> {
+    let mut accumulation = Accumulation::new(world);
+    Progressive::default()
+        .render(world, &mut accumulation)
+        .unwrap();
+    let mut bytes = Vec::new();
+    accumulation.write(&mut bytes).unwrap();
+    bytes
+}
+
+fn read(bytes: &[u8]) -> io::Result<Accumulation> {
+    Accumulation::read(&mut Cursor::new(bytes))
+}
+
+#[test]
+fn checkpoints_read_back() {
+    let world = world(4, 7, SamplerKind::Independent);
+    let bytes = checkpoint(&world);
+    let accumulation = read(&bytes).unwrap();
+
+    assert_eq!((accumulation.width, accumulation.height), (8, 8));
+    assert_eq!(accumulation.seed, 7);
+    assert_eq!(accumulation.samples_per_pixel, 4);
+    assert_eq!(accumulation.samples(), 4 * 64);
+    let mut again = Vec::new();
+    accumulation.write(&mut again).unwrap();
+    assert_eq!(again, bytes);
+}
+
+#[test]
+fn broken_checkpoints_are_invalid_data() {
+    let bytes = checkpoint(&world(4, 7, SamplerKind::Independent));
+
+    // Width and height are right after the magic and the version
+    let mut huge = bytes.clone();
+    huge[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    huge[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+    let error = read(&huge).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", error);
+
+    // Big enough to be allowed, but the file ends long before its pixels do
+    let mut tall = bytes.clone();
+    tall[16..20].copy_from_slice(&60000u32.to_le_bytes());
+    let error = read(&tall).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{}", error);
+
+    let error = read(&bytes[..bytes.len() - 1]).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{}", error);
+}
+
+#[test]
+fn resuming_needs_the_same_world() {
+    let world = world(4, 7, SamplerKind::Independent);
+    let bytes = checkpoint(&world);
+    let resume = |world: &World| {
+        let mut accumulation = read(&bytes).unwrap();
+        Progressive::default().render(world, &mut accumulation)
+    };
+
+    for (world, message) in &[
+        (self::world(4, 8, SamplerKind::Independent), "seed"),
+        (self::world(4, 7, SamplerKind::Sobol), "sampler"),
+        (
+            self::world(2, 7, SamplerKind::Independent),
+            "samples per pixel",
+        ),
+    ] {
+        let error = resume(world).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", error);
+        assert!(error.to_string().contains(message), "{}", error);
+    }
+
+    // More samples carry on where the checkpoint stopped
+    let output = resume(&self::world(8, 7, SamplerKind::Independent)).unwrap();
+    assert_eq!(output.average_samples, 8.);
+}
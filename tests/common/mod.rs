@@ -0,0 +1,32 @@
+//! The world most of the tests render: a matte sphere in the middle of a sky of one color,
+//! which is noisy where the sphere is and has no noise at all around it.
+
+#![allow(dead_code)] // Each test uses some of it
+
+use sunray::{CameraSettings, Color, Hittable, Material, Point, Sphere, World, WorldBuilder};
+
+pub fn material() -> Material {
+    Material::Lambertian(Color::from(0.5))
+}
+
+/// `object` under the sky, seen from the front, in a square image. The sphere of `world`
+/// takes up about a quarter of its width.
+pub fn builder(object: impl Hittable + 'static) -> WorldBuilder {
+    World::builder()
+        .add_object(object)
+        .background(Color::ones())
+        .camera(CameraSettings {
+            look_from: Point::new(0., 0., 10.),
+            look_at: Point::zeros(),
+            fov: 40.,
+            ..CameraSettings::default()
+        })
+        .aspect_ratio(1.)
+}
+
+pub fn world(width: u16, samples_per_pixel: u16, seed: u64) -> WorldBuilder {
+    builder(Sphere::new(Point::zeros(), 1., material()))
+        .image_width(width)
+        .samples_per_pixel(samples_per_pixel)
+        .seed(seed)
+}
@@ -0,0 +1,89 @@
+//! Denoising: noise on a flat image averages out, edges in the AOVs stay sharp, and fireflies
+//! don't spread over their neighbours.
+
+use sunray::{AOVBuffers, Color, Denoiser, Framebuffer, Vec3};
+
+const WIDTH: usize = 32;
+const HEIGHT: usize = 16;
+
+/// Noise between -0.5 and 0.5 that's the same every run
+fn noise(i: usize) -> f32 {
+    (i as u32).wrapping_mul(2_654_435_761) as f32 / u32::MAX as f32 - 0.5
+}
+
+/// AOVs of a flat wall, with its right half facing another way when `edge` is set
+fn aovs(edge: bool) -> AOVBuffers {
+    let buffer = |f: &dyn Fn(usize) -> Vec3| {
+        Framebuffer::from_pixels(WIDTH, HEIGHT, (0..WIDTH * HEIGHT).map(f).collect())
+    };
+    AOVBuffers {
+        albedo: buffer(&|_| Color::ones()),
+        normal: buffer(&|i| {
+            if edge && i % WIDTH >= WIDTH / 2 {
+                Vec3::new(1., 0., 0.)
+            } else {
+                Vec3::new(0., 0., 1.)
+            }
+        }),
+        depth: buffer(&|_| Vec3::ones()),
+        position: buffer(&|_| Vec3::zeros()),
+        uv: buffer(&|_| Vec3::zeros()),
+        object_id: buffer(&|_| Vec3::ones()),
+    }
+}
+
+/// Largest difference to `expected`, which is the value of each pixel without noise
+fn error(image: &Framebuffer, expected: impl Fn(usize) -> f32) -> f32 {
+    image
+        .pixels()
+        .iter()
+        .enumerate()
+        .map(|(i, pixel)| (pixel.x - expected(i)).abs())
+        .fold(0., f32::max)
+}
+
+#[test]
+fn noise_on_a_flat_image_averages_out() {
+    let image = Framebuffer::from_pixels(
+        WIDTH,
+        HEIGHT,
+        (0..WIDTH * HEIGHT)
+            .map(|i| Color::from(0.5 + 0.4 * noise(i)))
+            .collect(),
+    );
+    let before = error(&image, |_| 0.5);
+    let after = error(&Denoiser::default().denoise(&image, &aovs(false)), |_| 0.5);
+    assert!(before > 0.15, "{}", before);
+    assert!(after < 0.05, "still {} away", after);
+}
+
+#[test]
+fn edges_stay_sharp() {
+    let side = |i: usize| if i % WIDTH >= WIDTH / 2 { 0.8 } else { 0.2 };
+    let image = Framebuffer::from_pixels(
+        WIDTH,
+        HEIGHT,
+        (0..WIDTH * HEIGHT)
+            .map(|i| Color::from(side(i) + 0.1 * noise(i)))
+            .collect(),
+    );
+    let denoised = Denoiser::default().denoise(&image, &aovs(true));
+    // Pixels right next to the edge included
+    let after = error(&denoised, side);
+    assert!(after < 0.05, "{} away from their side", after);
+}
+
+#[test]
+fn fireflies_dont_spread() {
+    let firefly = WIDTH * HEIGHT / 2 + WIDTH / 2;
+    let image = Framebuffer::from_pixels(
+        WIDTH,
+        HEIGHT,
+        (0..WIDTH * HEIGHT)
+            .map(|i| Color::from(if i == firefly { 1000. } else { 0.5 }))
+            .collect(),
+    );
+    let denoised = Denoiser::default().denoise(&image, &aovs(false));
+    let after = error(&denoised, |_| 0.5);
+    assert!(after < 0.05, "{} away from the flat value", after);
+}
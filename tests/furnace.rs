@@ -0,0 +1,180 @@
+//! White furnace tests: an object under a uniform white sky can't send back more light than
+//! reaches it, and sends back exactly as much if its material doesn't absorb any. Every
+//! material is checked through the whole renderer, so `scatter`, `scattering_pdf` and the
+//! PDFs have to agree with each other for the tests to pass.
+
+use sunray::{
+    texture, CameraSettings, Color, ConstantMedium, Hittable, Material, Point, Sphere, World,
+};
+
+const WIDTH: u16 = 16;
+const SAMPLES_PER_PIXEL: u16 = 64;
+
+/// Radiance that comes back from the object, with its standard error. The camera is close
+/// enough for the object to cover the whole image.
+fn furnace(object: impl Hittable + 'static, sample_lights: bool) -> (Color, Color) {
+    let mut builder = World::builder()
+        .add_object(object)
+        .background(Color::ones())
+        .camera(CameraSettings {
+            look_from: Point::new(0., 0., 3.),
+            look_at: Point::zeros(),
+            fov: 20.,
+            ..CameraSettings::default()
+        })
+        .image_width(WIDTH)
+        .aspect_ratio(1.)
+        .samples_per_pixel(SAMPLES_PER_PIXEL)
+        .max_depth(100)
+        .seed(3);
+    if sample_lights {
+        // Only in the lights, so it doesn't block the sky but bounces get sent towards it
+        builder = builder.add_light(Sphere::new(
+            Point::new(2., 3., 1.),
+            1.,
+            Material::DiffuseLight(Color::zeros()),
+        ));
+    }
+    let image = sunray::render_world(&builder.build()).image;
+
+    let pixels = image.pixels();
+    let count = pixels.len() as f32;
+    let mean = pixels
+        .iter()
+        .fold(Color::zeros(), |sum, pixel| sum + *pixel)
+        / count;
+    let variance = pixels.iter().fold(Color::zeros(), |sum, pixel| {
+        let difference = *pixel - mean;
+        sum + difference * difference
+    }) / (count - 1.);
+    let standard_error = Color::new(
+        (variance.x / count).sqrt(),
+        (variance.y / count).sqrt(),
+        (variance.z / count).sqrt(),
+    );
+    (mean, standard_error)
+}
+
+fn sphere(material: Material) -> Sphere {
+    Sphere::new(Point::zeros(), 1., material)
+}
+
+/// Tolerance for a mean, from its standard error and the rounding of the floats
+fn tolerance(standard_error: f32) -> f32 {
+    4. * standard_error + 1e-3
+}
+
+/// Checks the light sent back is `expected`, with and without sampling lights
+fn assert_reflects<H, F>(name: &str, expected: Color, object: F)
+where
+    H: Hittable + 'static,
+    F: Fn() -> H,
+{
+    for &sample_lights in &[false, true] {
+        let (mean, error) = furnace(object(), sample_lights);
+        for channel in 0..3 {
+            assert!(
+                (mean[channel] - expected[channel]).abs() <= tolerance(error[channel]),
+                "{} sends back {:?} ± {:?}, expected {:?} (sampling lights: {})",
+                name,
+                mean,
+                error,
+                expected,
+                sample_lights
+            );
+        }
+    }
+}
+
+/// Checks the light sent back is at most `bound`, for materials that lose some light on
+/// top of what their color absorbs
+fn assert_reflects_at_most<H, F>(name: &str, bound: Color, object: F)
+where
+    H: Hittable + 'static,
+    F: Fn() -> H,
+{
+    for &sample_lights in &[false, true] {
+        let (mean, error) = furnace(object(), sample_lights);
+        for channel in 0..3 {
+            assert!(
+                mean[channel] <= bound[channel] + tolerance(error[channel]),
+                "{} sends back {:?} ± {:?}, more than {:?} (sampling lights: {})",
+                name,
+                mean,
+                error,
+                bound,
+                sample_lights
+            );
+        }
+    }
+}
+
+#[test]
+fn lambertian() {
+    assert_reflects("white lambertian", Color::ones(), || {
+        sphere(Material::Lambertian(Color::ones()))
+    });
+    let albedo = Color::new(0.8, 0.5, 0.2);
+    assert_reflects("colored lambertian", albedo, || {
+        sphere(Material::Lambertian(albedo))
+    });
+}
+
+#[test]
+fn lambertian_texture() {
+    assert_reflects("white textured lambertian", Color::ones(), || {
+        sphere(Material::LambertianTexture(texture::solid_color(
+            Color::ones(),
+        )))
+    });
+    let albedo = Color::new(0.3, 0.6, 0.9);
+    assert_reflects("colored textured lambertian", albedo, || {
+        sphere(Material::LambertianTexture(texture::solid_color(albedo)))
+    });
+}
+
+#[test]
+fn metal() {
+    assert_reflects("polished metal", Color::ones(), || {
+        sphere(Material::Metal(Color::ones(), 0.))
+    });
+    let albedo = Color::new(0.9, 0.6, 0.4);
+    assert_reflects("colored polished metal", albedo, || {
+        sphere(Material::Metal(albedo, 0.))
+    });
+    // Fuzz sends some rays into the surface, which get lost inside
+    for &fuzz in &[0.3, 1.] {
+        assert_reflects_at_most("fuzzy metal", Color::ones(), || {
+            sphere(Material::Metal(Color::ones(), fuzz))
+        });
+    }
+}
+
+#[test]
+fn dielectric() {
+    for &refraction_index in &[1.1, 1.5, 2.4] {
+        assert_reflects("dielectric", Color::ones(), || {
+            sphere(Material::Dielectric(refraction_index))
+        });
+    }
+}
+
+#[test]
+fn isotropic() {
+    assert_reflects("white medium", Color::ones(), || {
+        ConstantMedium::new(
+            Box::new(sphere(Material::Lambertian(Color::ones()))),
+            2.,
+            Color::ones(),
+        )
+    });
+    // Some light goes through without being scattered, so only the total is bounded
+    let albedo = Color::new(0.7, 0.5, 0.3);
+    assert_reflects_at_most("colored medium", Color::ones(), || {
+        ConstantMedium::new(
+            Box::new(sphere(Material::Lambertian(Color::ones()))),
+            2.,
+            albedo,
+        )
+    });
+}
@@ -0,0 +1,104 @@
+//! Loading glTF scenes, with their node hierarchy, camera, lights and materials
+
+use std::path::PathBuf;
+use sunray::{load_gltf, HitRecord, Hittable, Material, Point, Ray, Sampler, Vec3, World};
+
+fn scene_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/meshes")
+        .join(name)
+}
+
+/// What a ray from `origin` towards `target` hits first
+fn hit(object: &dyn Hittable, origin: Point, target: Point) -> Option<HitRecord> {
+    let ray = Ray {
+        origin,
+        direction: target - origin,
+        time: 0.,
+    };
+    let mut hit_record = HitRecord::default();
+    if object.hit(
+        &ray,
+        0.001,
+        f32::INFINITY,
+        &mut hit_record,
+        &mut Sampler::new(0, 0),
+    ) {
+        Some(hit_record)
+    } else {
+        None
+    }
+}
+
+/// The floor is a quad scaled 5 times, and the panel is a triangle moved by its node and
+/// then turned by its parent, so it faces +x at x = -1
+fn check_scene(world: &World) {
+    let floor = hit(
+        &world.hittables,
+        Point::new(4., 1., 4.),
+        Point::new(4., 0., 4.),
+    )
+    .unwrap();
+    assert!(floor.front_face);
+    assert!(floor.normal.dot(&Vec3::new(0., 1., 0.)) > 0.9999);
+    match floor.material {
+        Material::Lambertian(color) => assert_eq!((color.x, color.y, color.z), (0.8, 0.1, 0.1)),
+        _ => panic!("the floor should be red"),
+    }
+    assert!(hit(
+        &world.hittables,
+        Point::new(6., 1., 0.),
+        Point::new(6., 0., 0.)
+    )
+    .is_none());
+
+    let panel = hit(
+        &world.hittables,
+        Point::new(0., 2.2, -0.2),
+        Point::new(-2., 2.2, -0.2),
+    )
+    .unwrap();
+    assert!((panel.point - Point::new(-1., 2.2, -0.2)).length() < 1e-4);
+    assert!(panel.front_face);
+    assert!(matches!(panel.material, Material::DiffuseLight(_)));
+
+    // The camera is where its node is, with the aspect ratio it has
+    assert!((world.camera.origin - Point::new(0., 1., 10.)).length() < 1e-4);
+    assert_eq!(world.aspect_ratio, 2.);
+
+    // The point light is a small sphere, that's also sampled with the emissive panel
+    let light = hit(
+        &world.lights,
+        Point::new(0., 0.5, 0.),
+        Point::new(0., 4., 0.),
+    )
+    .unwrap();
+    assert!((light.point.y - 4.).abs() < 0.1);
+    assert!(matches!(light.material, Material::DiffuseLight(_)));
+    assert!(hit(
+        &world.lights,
+        Point::new(0., 2.2, -0.2),
+        Point::new(-2., 2.2, -0.2)
+    )
+    .is_some());
+}
+
+#[test]
+fn gltf_nodes_are_placed_in_the_world() {
+    check_scene(&load_gltf(scene_path("scene.gltf")).unwrap());
+}
+
+#[test]
+fn glb_files_have_the_same_scene() {
+    check_scene(&load_gltf(scene_path("scene.glb")).unwrap());
+}
+
+#[test]
+fn gltf_errors_have_the_path() {
+    let error = load_gltf(scene_path("missing.gltf")).err().unwrap();
+    assert!(error.to_string().contains("missing.gltf"), "{}", error);
+
+    // An .obj isn't glTF
+    let error = load_gltf(scene_path("cube.obj")).err().unwrap();
+    assert!(error.to_string().contains("cube.obj"), "{}", error);
+}
@@ -0,0 +1,589 @@
+//! Property tests for the objects: random rays are shot at every kind of `Hittable`, and
+//! every hit they get has to be between `t_min` and `t_max`, on the ray and inside the
+//! bounding box, with a unit normal facing the ray and `u` and `v` in [0, 1].
+//!
+//! `check_hittable` can check any object, so new ones only need a test that builds them.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{path::PathBuf, sync::Arc};
+use sunray::{
+    load_mesh,
+    sdf::{SDFCube, SDFDonut, SDFOctahedron, SDFSphere, SDFSubstraction, SDFUnion},
+    BVHNode, Color, Cube, Cylinder, FlipFace, HitRecord, Hittable, Instance, Material, Matrix,
+    MovingSphere, Plane, Point, Pyramid, Ray, Rect, Sampler, Sphere, TracedSDF, Transform,
+    Triangle, Vec3, AABB,
+};
+
+const RAYS: usize = 5000;
+
+/// Fraction of the rays that have to hit, so the invariants are checked on enough hits
+const MIN_HIT_FRACTION: f32 = 0.05;
+
+fn material() -> Material {
+    Material::Lambertian(Color::from(0.5))
+}
+
+fn mesh_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/meshes")
+        .join(name)
+}
+
+/// Checks the invariants on random rays aimed at the bounding box of the object
+fn check_hittable(name: &str, object: &dyn Hittable) {
+    let region = object
+        .bounding_box(0., 1.)
+        .unwrap_or_else(|| panic!("{} has no bounding box", name));
+    check_hittable_in(name, object, region);
+}
+
+/// Checks the invariants on random rays aimed at `region`, for objects without a bounding
+/// box or with a huge one
+fn check_hittable_in(name: &str, object: &dyn Hittable, region: AABB) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut sampler = Sampler::new(0, 0);
+    let bounding_box = object.bounding_box(0., 1.);
+    let extent = region.max - region.min;
+    let scale = extent.length().max(1.);
+
+    let mut hits = 0;
+    for i in 0..RAYS {
+        let ray = random_ray(&mut rng, &region, i);
+        let (t_min, t_max) = random_interval(&mut rng, scale / ray.direction.length());
+
+        let mut hit_record = HitRecord::default();
+        if !object.hit(&ray, t_min, t_max, &mut hit_record, &mut sampler) {
+            continue;
+        }
+        hits += 1;
+
+        let context = || {
+            format!(
+                "{} hit by {:?}, with t in [{}, {}]",
+                name, ray, t_min, t_max
+            )
+        };
+        let t = hit_record.t;
+        assert!(
+            t >= t_min && t <= t_max,
+            "{}: t is {}, out of the interval",
+            context(),
+            t
+        );
+
+        let point = hit_record.point;
+        assert!(
+            (point - ray.at(t)).length() <= 1e-3 * scale,
+            "{}: the point {:?} isn't on the ray at t {}",
+            context(),
+            point,
+            t
+        );
+        if let Some(bounding_box) = bounding_box {
+            let margin = 1e-3 * scale;
+            for axis in 0..3 {
+                assert!(
+                    point[axis] >= bounding_box.min[axis] - margin
+                        && point[axis] <= bounding_box.max[axis] + margin,
+                    "{}: the point {:?} is outside the bounding box {:?}",
+                    context(),
+                    point,
+                    bounding_box
+                );
+            }
+        }
+
+        let normal = hit_record.normal;
+        assert!(
+            (normal.length() - 1.).abs() <= 1e-3,
+            "{}: the normal {:?} isn't unit length",
+            context(),
+            normal
+        );
+        assert!(
+            normal.dot(&ray.direction.normalize()) <= 1e-4,
+            "{}: the normal {:?} doesn't face the ray",
+            context(),
+            normal
+        );
+
+        let (u, v) = (hit_record.u, hit_record.v);
+        assert!(
+            (0. ..=1.).contains(&u) && (0. ..=1.).contains(&v),
+            "{}: u and v are {} and {}, out of [0, 1]",
+            context(),
+            u,
+            v
+        );
+    }
+
+    assert!(
+        hits as f32 >= MIN_HIT_FRACTION * RAYS as f32,
+        "{} only got hit by {} of the {} rays",
+        name,
+        hits,
+        RAYS
+    );
+}
+
+/// Ray from around the region towards a point in it. Some rays go along an axis, or start
+/// inside the region, as those are where intersections tend to break.
+fn random_ray(rng: &mut StdRng, region: &AABB, i: usize) -> Ray {
+    let extent = region.max - region.min;
+    let point_in = |rng: &mut StdRng, margin: f32| {
+        Point::new(
+            rng.gen_range(
+                region.min.x - margin * extent.x,
+                region.max.x + margin * extent.x + 1e-6,
+            ),
+            rng.gen_range(
+                region.min.y - margin * extent.y,
+                region.max.y + margin * extent.y + 1e-6,
+            ),
+            rng.gen_range(
+                region.min.z - margin * extent.z,
+                region.max.z + margin * extent.z + 1e-6,
+            ),
+        )
+    };
+
+    let target = point_in(rng, 0.);
+    let origin = match i % 8 {
+        0 | 4 => point_in(rng, 0.),
+        _ => point_in(rng, 1.),
+    };
+    let mut direction = target - origin;
+    if i % 8 == 1 {
+        // Along the axis it's furthest on, so it still goes towards the region
+        let axis = (0..3)
+            .max_by(|a, b| direction[*a].abs().total_cmp(&direction[*b].abs()))
+            .unwrap();
+        let mut along_axis = Vec3::zeros();
+        along_axis[axis] = direction[axis];
+        direction = along_axis;
+    }
+
+    Ray {
+        origin,
+        // The renderer doesn't normalize every ray, so neither do the tests
+        direction: direction * rng.gen_range(0.1, 10.),
+        time: rng.gen(),
+    }
+}
+
+/// Interval of t the ray is tested in: from the usual `0.001` to infinity, or a random one
+fn random_interval(rng: &mut StdRng, scale: f32) -> (f32, f32) {
+    if rng.gen_bool(0.5) {
+        (0.001, f32::INFINITY)
+    } else {
+        let t_min = rng.gen_range(0., scale);
+        (t_min, t_min + rng.gen_range(0., 2. * scale))
+    }
+}
+
+#[test]
+fn sphere() {
+    check_hittable(
+        "sphere",
+        &Sphere::new(Point::new(1., 2., -3.), 1.5, material()),
+    );
+    check_hittable(
+        "tiny sphere",
+        &Sphere::new(Point::zeros(), 0.01, material()),
+    );
+}
+
+#[test]
+fn moving_sphere() {
+    check_hittable(
+        "moving sphere",
+        &MovingSphere::new(
+            Point::new(0., 0., 0.),
+            Point::new(2., 1., 0.),
+            0.,
+            1.,
+            0.7,
+            material(),
+        ),
+    );
+}
+
+#[test]
+fn rect() {
+    for &plane in &[Plane::XY, Plane::XZ, Plane::YZ] {
+        check_hittable(
+            &format!("{:?} rect", plane),
+            &Rect::new(plane, (-1., 2.), (0.5, 3.), 1.5, material()),
+        );
+    }
+}
+
+#[test]
+fn cube() {
+    check_hittable(
+        "cube",
+        &Cube::new(Point::new(-1., 0., 2.), Point::new(3., 0.5, 4.), material()),
+    );
+}
+
+#[test]
+fn cylinder() {
+    let cylinder = Cylinder::new(
+        Point::new(-1., -2., 0.5),
+        Point::new(1.5, 1., -0.5),
+        1.2,
+        material(),
+    );
+    check_hittable("cylinder", &cylinder);
+    check_hittable(
+        "open cylinder",
+        &Cylinder {
+            caps: false,
+            ..cylinder
+        },
+    );
+}
+
+#[test]
+fn triangle() {
+    check_hittable(
+        "triangle",
+        &Triangle::new(
+            Point::new(0., 0., 0.),
+            Point::new(2., 0.5, -1.),
+            Point::new(0.5, 2., 1.),
+            material(),
+        ),
+    );
+    // Right in a plane, where the bounding box is flat
+    check_hittable(
+        "axis aligned triangle",
+        &Triangle::new(
+            Point::new(0., 1., 0.),
+            Point::new(1., 1., 0.),
+            Point::new(0., 1., 1.),
+            material(),
+        ),
+    );
+}
+
+#[test]
+fn pyramid() {
+    check_hittable(
+        "pyramid",
+        &Pyramid::new(
+            Point::new(0., 2., 0.),
+            Point::new(-1., 0., -1.),
+            Point::new(1., 0., -1.),
+            Point::new(1., 0., 1.),
+            Point::new(-1., 0., 1.),
+            material(),
+        ),
+    );
+}
+
+#[test]
+fn traced_sdf() {
+    let sdfs = vec![
+        (
+            "sdf sphere",
+            TracedSDF::new(
+                Box::new(SDFSphere {
+                    center: Point::new(1., 0., 0.),
+                    radius: 1.,
+                }),
+                material(),
+            ),
+        ),
+        (
+            "sdf box",
+            TracedSDF::new(
+                Box::new(SDFCube {
+                    center: Point::new(0., 1., 0.),
+                    dimensions: Vec3::new(1., 0.5, 2.),
+                }),
+                material(),
+            ),
+        ),
+        (
+            "sdf donut",
+            TracedSDF::new(
+                Box::new(SDFDonut {
+                    center: Point::new(0., 0., 3.),
+                    radius0: 2.,
+                    radius1: 0.5,
+                }),
+                material(),
+            ),
+        ),
+        (
+            "sdf octahedron",
+            TracedSDF::new(
+                Box::new(SDFOctahedron {
+                    center: Point::zeros(),
+                    size: 1.5,
+                }),
+                material(),
+            ),
+        ),
+        (
+            "sdf union",
+            TracedSDF::new(
+                Box::new(SDFUnion {
+                    a: Box::new(SDFSphere {
+                        center: Point::zeros(),
+                        radius: 1.,
+                    }),
+                    b: Box::new(SDFSphere {
+                        center: Point::new(1.5, 0., 0.),
+                        radius: 0.8,
+                    }),
+                }),
+                material(),
+            ),
+        ),
+        (
+            "sdf subtraction",
+            TracedSDF::new(
+                Box::new(SDFSubstraction {
+                    a: Box::new(SDFCube {
+                        center: Point::zeros(),
+                        dimensions: Vec3::from(1.),
+                    }),
+                    b: Box::new(SDFSphere {
+                        center: Point::zeros(),
+                        radius: 1.3,
+                    }),
+                }),
+                material(),
+            ),
+        ),
+    ];
+    for (name, sdf) in &sdfs {
+        check_hittable(name, sdf);
+    }
+}
+
+#[test]
+fn translate() {
+    check_hittable(
+        "translated sphere",
+        &Transform::new(
+            Box::new(Sphere::new(Point::zeros(), 1., material())),
+            Matrix::translation(Vec3::new(3., -1., 2.)),
+        ),
+    );
+    check_hittable(
+        "translated cube",
+        &Transform::new(
+            Box::new(Cube::new(Point::zeros(), Point::ones(), material())),
+            Matrix::translation(Vec3::new(-2., 0., 1.)),
+        ),
+    );
+}
+
+#[test]
+fn rotate_y() {
+    for &angle in &[15., 90., -120.] {
+        check_hittable(
+            &format!("cube rotated {} degrees", angle),
+            &Transform::new(
+                Box::new(Cube::new(
+                    Point::new(1., 0., 0.),
+                    Point::new(2., 1., 3.),
+                    material(),
+                )),
+                Matrix::rotation(Vec3::new(0., 1., 0.), angle),
+            ),
+        );
+    }
+}
+
+#[test]
+fn transform() {
+    let matrices = [
+        (
+            "tilted and stretched",
+            Matrix::translation(Vec3::new(1., 2., -1.))
+                * Matrix::rotation(Vec3::new(1., 0., 1.), 35.)
+                * Matrix::scaling(Vec3::new(2., 0.5, 1.)),
+        ),
+        ("mirrored", Matrix::scaling(Vec3::new(-1., 1., 1.5))),
+        (
+            "sheared",
+            Matrix {
+                rows: [
+                    [1., 0.6, 0., 0.],
+                    [0., 1., 0., 0.5],
+                    [0.3, 0., 1., 0.],
+                    [0., 0., 0., 1.],
+                ],
+            },
+        ),
+    ];
+    for (name, matrix) in &matrices {
+        check_hittable(
+            &format!("{} cylinder", name),
+            &Transform::new(
+                Box::new(Cylinder::new(
+                    Point::new(0., -1., 0.),
+                    Point::new(0., 1., 0.5),
+                    0.8,
+                    material(),
+                )),
+                *matrix,
+            ),
+        );
+        check_hittable(
+            &format!("{} triangle mesh", name),
+            &Transform::new(
+                Box::new(load_mesh(mesh_path("octahedron.obj"), material()).unwrap()),
+                *matrix,
+            ),
+        );
+    }
+}
+
+/// Sphere tracing steps along unit directions, so a scaled SDF has to be hit where the
+/// same scaled sphere is
+#[test]
+fn scaled_sdf() {
+    // Shrinking it makes the steps in the space of the sphere longer than in the world
+    let center = Point::new(1., 0., -2.);
+    let scale = Vec3::new(0.5, 2., 0.25);
+    let matrix = Matrix::translation(center) * Matrix::scaling(scale);
+    let sdf = Transform::new(
+        Box::new(TracedSDF::new(
+            Box::new(SDFSphere {
+                center: Point::zeros(),
+                radius: 1.,
+            }),
+            material(),
+        )),
+        matrix,
+    );
+    let sphere = Transform::new(
+        Box::new(Sphere::new(Point::zeros(), 1., material())),
+        matrix,
+    );
+    check_hittable("scaled sdf sphere", &sdf);
+
+    let region = sphere.bounding_box(0., 1.).unwrap();
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut sampler = Sampler::new(0, 0);
+    let mut hits = 0;
+    for i in 0..RAYS {
+        let mut ray = random_ray(&mut rng, &region, i);
+        // So t_min is a short distance, and doesn't skip where the ray goes in
+        ray.direction = ray.direction.normalize();
+        // Sphere tracing hits right away from inside, where the sphere has a far side, and
+        // the two can also disagree about rays that start on the surface
+        if ((ray.origin - center) / scale).length() < 1.01 {
+            continue;
+        }
+        let mut sphere_hit = HitRecord::default();
+        if !sphere.hit(&ray, 0.001, f32::INFINITY, &mut sphere_hit, &mut sampler) {
+            continue;
+        }
+        let mut sdf_hit = HitRecord::default();
+        assert!(
+            sdf.hit(&ray, 0.001, f32::INFINITY, &mut sdf_hit, &mut sampler),
+            "ray {}: {:?} misses the sdf, but hits the sphere at {}",
+            i,
+            ray,
+            sphere_hit.t
+        );
+        let distance = (sdf_hit.point - sphere_hit.point).length();
+        assert!(
+            distance < 1e-3,
+            "ray {}: {:?} hits the sdf {} away from the sphere",
+            i,
+            ray,
+            distance
+        );
+        hits += 1;
+    }
+    assert!(hits as f32 >= MIN_HIT_FRACTION * RAYS as f32);
+}
+
+#[test]
+fn instance() {
+    let prototype: Arc<dyn Hittable> =
+        Arc::new(load_mesh(mesh_path("octahedron.obj"), material()).unwrap());
+    let metal = Material::Metal(Color::from(0.9), 0.1);
+    let instances = [
+        Instance::new(&prototype, Matrix::translation(Vec3::new(4., 0., 0.))),
+        Instance::new(
+            &prototype,
+            Matrix::rotation(Vec3::new(0., 0., 1.), 30.) * Matrix::scaling(Vec3::new(1., 3., 1.)),
+        )
+        .with_material(metal),
+    ];
+    for (i, instance) in instances.iter().enumerate() {
+        check_hittable(&format!("instance {}", i), instance);
+    }
+    assert_eq!(Arc::strong_count(&prototype), 3);
+
+    // The material of the prototype is replaced only where the instance has one
+    let ray = Ray {
+        origin: Point::new(0., 0., 5.),
+        direction: Vec3::new(0., 0., -1.),
+        time: 0.,
+    };
+    let mut sampler = Sampler::new(0, 0);
+    for (instance, metal) in instances.iter().zip(&[false, true]) {
+        let ray = Ray {
+            origin: instance.matrix().transform_point(&ray.origin),
+            ..ray
+        };
+        let mut hit_record = HitRecord::default();
+        assert!(instance.hit(&ray, 0.001, f32::INFINITY, &mut hit_record, &mut sampler));
+        assert_eq!(matches!(hit_record.material, Material::Metal(..)), *metal);
+    }
+}
+
+#[test]
+fn flip_face() {
+    check_hittable(
+        "flipped rect",
+        &FlipFace::new(Box::new(Rect::new(
+            Plane::XZ,
+            (0., 1.),
+            (0., 1.),
+            2.,
+            material(),
+        ))),
+    );
+}
+
+#[test]
+fn triangle_mesh() {
+    for name in &["cube.obj", "octahedron.obj", "quad.ply"] {
+        check_hittable(name, &load_mesh(mesh_path(name), material()).unwrap());
+    }
+}
+
+#[test]
+fn bvh_node() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+    for _ in 0..40 {
+        let center = Point::new(
+            rng.gen_range(-5., 5.),
+            rng.gen_range(-5., 5.),
+            rng.gen_range(-5., 5.),
+        );
+        let object: Box<dyn Hittable> = match rng.gen_range(0, 3) {
+            0 => Box::new(Sphere::new(center, rng.gen_range(0.2, 1.), material())),
+            1 => Box::new(Cube::new(center, center + Vec3::from(0.7), material())),
+            _ => Box::new(Triangle::new(
+                center,
+                center + Vec3::new(1., 0., 0.3),
+                center + Vec3::new(0., 1., -0.5),
+                material(),
+            )),
+        };
+        objects.push(object);
+    }
+    check_hittable("bvh", &BVHNode::new(objects, 0., 1.));
+}
@@ -0,0 +1,115 @@
+//! HDR image files: PFM and EXR read back what was written, and broken headers are
+//! rejected before anything big gets allocated for them.
+
+use std::io::{self, Cursor};
+use sunray::{Color, ExrPixelType, Framebuffer};
+
+/// A small image that isn't square, with values that need more than 8 bits
+fn image() -> Framebuffer {
+    let (width, height) = (5, 3);
+    let pixels = (0..width * height)
+        .map(|i| {
+            let i = i as f32;
+            Color::new(i * 0.25, 100. - i, 1. / (i + 1.))
+        })
+        .collect();
+    Framebuffer::from_pixels(width, height, pixels)
+}
+
+fn pfm(image: &Framebuffer) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image.write_pfm(&mut bytes).unwrap();
+    bytes
+}
+
+fn exr(image: &Framebuffer, pixel_type: ExrPixelType) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image.write_exr(&mut bytes, pixel_type).unwrap();
+    bytes
+}
+
+fn check_same(read: &Framebuffer, written: &Framebuffer, tolerance: f32) {
+    assert_eq!((read.width, read.height), (written.width, written.height));
+    for (i, (read, written)) in read.pixels().iter().zip(written.pixels()).enumerate() {
+        for channel in 0..3 {
+            let error = (read[channel] - written[channel]).abs();
+            assert!(
+                error <= tolerance * written[channel].abs(),
+                "pixel {} reads back as {:?} instead of {:?}",
+                i,
+                read,
+                written
+            );
+        }
+    }
+}
+
+fn check_invalid(result: io::Result<Framebuffer>) {
+    match result {
+        Ok(image) => panic!("read a {}x{} image", image.width, image.height),
+        Err(error) => assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", error),
+    }
+}
+
+#[test]
+fn pfm_reads_back() {
+    let image = image();
+    let read = Framebuffer::read_pfm(&mut Cursor::new(pfm(&image))).unwrap();
+    check_same(&read, &image, 0.);
+}
+
+#[test]
+fn exr_reads_back() {
+    let image = image();
+    let read = Framebuffer::read_exr(&mut Cursor::new(exr(&image, ExrPixelType::Float))).unwrap();
+    check_same(&read, &image, 0.);
+
+    // Half floats have 11 bits of precision
+    let read = Framebuffer::read_exr(&mut Cursor::new(exr(&image, ExrPixelType::Half))).unwrap();
+    check_same(&read, &image, 1. / 1024.);
+}
+
+#[test]
+fn broken_pfm_headers_are_rejected() {
+    let read = |bytes: &[u8]| Framebuffer::read_pfm(&mut Cursor::new(bytes));
+
+    check_invalid(read(b"PF\n100000 100000\n-1.0\n"));
+    check_invalid(read(b"PF\n18446744073709551615 2\n-1.0\n"));
+    check_invalid(read(b"PF\n-5 3\n-1.0\n"));
+    check_invalid(read(b"PF\n5\n"));
+
+    // Allowed, but the file ends long before its pixels do
+    let error = read(b"PF\n60000 60000\n-1.0\n\0\0\0\0").err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{}", error);
+    let bytes = pfm(&image());
+    let error = read(&bytes[..bytes.len() - 1]).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "{}", error);
+}
+
+#[test]
+fn broken_exr_headers_are_rejected() {
+    let bytes = exr(&image(), ExrPixelType::Float);
+    // The 4 numbers of the dataWindow come after its name, type and size
+    let name = b"dataWindow\0box2i\0";
+    let window = bytes
+        .windows(name.len())
+        .position(|window| window == name)
+        .unwrap()
+        + name.len()
+        + 4;
+    let with_window = |values: [i32; 4]| {
+        let mut bytes = bytes.clone();
+        for (i, value) in values.iter().enumerate() {
+            bytes[window + 4 * i..window + 4 * (i + 1)].copy_from_slice(&value.to_le_bytes());
+        }
+        Framebuffer::read_exr(&mut Cursor::new(bytes))
+    };
+
+    // Overflows i32
+    check_invalid(with_window([i32::MIN, 0, i32::MAX, 2]));
+    // Far more pixels than the file has bytes
+    check_invalid(with_window([0, 0, 60000, 60000]));
+    check_invalid(Framebuffer::read_exr(&mut Cursor::new(
+        &bytes[..bytes.len() - 1],
+    )));
+}
@@ -0,0 +1,308 @@
+//! Loading meshes from files, with their materials, normals and texture coordinates
+
+use std::{fs, path::PathBuf};
+use sunray::{
+    load_mesh, load_obj, load_ply, load_scene, Color, HitRecord, Hittable, Material, Point, Ray,
+    Sampler, Vec3,
+};
+
+fn mesh_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/meshes")
+        .join(name)
+}
+
+fn default_material() -> Material {
+    Material::Lambertian(Color::from(0.5))
+}
+
+/// What a ray from `origin` towards `target` hits first
+fn hit(object: &dyn Hittable, origin: Point, target: Point) -> Option<HitRecord> {
+    let ray = Ray {
+        origin,
+        direction: target - origin,
+        time: 0.,
+    };
+    let mut hit_record = HitRecord::default();
+    if object.hit(
+        &ray,
+        0.001,
+        f32::INFINITY,
+        &mut hit_record,
+        &mut Sampler::new(0, 0),
+    ) {
+        Some(hit_record)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn obj_faces_get_their_material() {
+    let cube = load_obj(mesh_path("cube.obj"), default_material()).unwrap();
+    // Quads are split in two
+    assert_eq!(cube.mesh().faces.len(), 12);
+
+    let top = hit(&cube, Point::new(0.5, 2., 0.5), Point::new(0.5, 0., 0.5)).unwrap();
+    assert!(matches!(top.material, Material::DiffuseLight(_)));
+    assert!(top.front_face);
+
+    let side = hit(&cube, Point::new(-1., 0.5, 0.5), Point::new(1., 0.5, 0.5)).unwrap();
+    match side.material {
+        Material::Lambertian(color) => assert_eq!((color.x, color.y, color.z), (0.8, 0.1, 0.1)),
+        _ => panic!("the sides of the cube should be red"),
+    }
+}
+
+#[test]
+fn obj_texture_coordinates_are_interpolated() {
+    let cube = load_obj(mesh_path("cube.obj"), default_material()).unwrap();
+
+    let bottom = hit(
+        &cube,
+        Point::new(0.25, -1., 0.75),
+        Point::new(0.25, 0., 0.75),
+    )
+    .unwrap();
+    assert!((bottom.u - 0.25).abs() < 1e-4 && (bottom.v - 0.75).abs() < 1e-4);
+}
+
+#[test]
+fn obj_smoothing_groups_get_smooth_normals() {
+    let octahedron = load_mesh(mesh_path("octahedron.obj"), default_material()).unwrap();
+
+    // Close to the top vertex, the normal is close to its normal, and not the flat one
+    let target = Point::new(0.01, 0.98, 0.01);
+    let near_top = hit(&octahedron, target + Vec3::new(1., 1., 1.), target).unwrap();
+    assert!(near_top.normal.dot(&Vec3::new(0., 1., 0.)) > 0.95);
+
+    // The middle of a face is as far from every vertex, which gives the flat normal
+    let center = Point::from(1. / 3.);
+    let middle = hit(&octahedron, Point::from(1.), center).unwrap();
+    assert!(middle.normal.dot(&Vec3::from(1.).normalize()) > 0.9999);
+}
+
+#[test]
+fn obj_errors_have_their_line() {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("meshes");
+    fs::create_dir_all(&directory).unwrap();
+
+    let cases = [
+        ("index.obj", "v 0 0 0\nv 1 0 0\nf 1 2 3\n", ":3: index 3"),
+        (
+            "number.obj",
+            "v 0 0 0\nv 1 zero 0\n",
+            ":2: expected a number",
+        ),
+        (
+            "material.obj",
+            "v 0 0 0\nusemtl missing\n",
+            ":2: unknown material `missing`",
+        ),
+    ];
+    for (name, source, expected) in &cases {
+        let path = directory.join(name);
+        fs::write(&path, source).unwrap();
+
+        let error = load_obj(&path, default_material()).err().unwrap();
+        assert!(
+            error.to_string().contains(expected),
+            "`{}` should contain `{}`",
+            error,
+            expected
+        );
+    }
+}
+
+#[test]
+fn obj_missing_material_libraries_are_warnings() {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("meshes");
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("no-library.obj");
+    fs::write(
+        &path,
+        "mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n",
+    )
+    .unwrap();
+
+    let triangle = load_obj(&path, default_material()).unwrap();
+    let warnings = &triangle.mesh().warnings;
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains(":1: ") && warnings[0].contains("missing.mtl"));
+    let front = hit(
+        &triangle,
+        Point::new(0.2, 0.2, 1.),
+        Point::new(0.2, 0.2, 0.),
+    )
+    .unwrap();
+    match front.material {
+        Material::Lambertian(color) => assert_eq!(color.x, 0.5),
+        _ => panic!("the triangle should have the default material"),
+    }
+
+    // And scene files pass them on
+    let scene = directory.join("no-library.scene");
+    fs::write(&scene, "mesh { file no-library.obj }\n").unwrap();
+    let world = load_scene(&scene).unwrap();
+    assert_eq!(&world.warnings, warnings);
+}
+
+#[test]
+fn ply_colors_replace_the_albedo() {
+    let quad = load_mesh(mesh_path("quad.ply"), default_material()).unwrap();
+    assert_eq!(quad.mesh().faces.len(), 2);
+
+    let hit = hit(&quad, Point::new(0.2, 1., 0.1), Point::new(0.2, 0., 0.1)).unwrap();
+    assert!((hit.u - 0.2).abs() < 1e-4 && (hit.v - 0.1).abs() < 1e-4);
+    assert!(hit.normal.dot(&Vec3::new(0., 1., 0.)) > 0.9999);
+    match hit.material {
+        Material::Lambertian(color) => {
+            assert!(
+                (color - Color::new(0.9, 0., 0.1)).length() < 1e-4,
+                "{:?}",
+                color
+            )
+        }
+        _ => panic!("the quad should be lambertian"),
+    }
+}
+
+/// A triangle with a vertex element that has a double and a list, and an element that isn't
+/// used, so all of them are skipped right
+fn binary_ply(big_endian: bool) -> Vec<u8> {
+    let format = if big_endian {
+        "binary_big_endian"
+    } else {
+        "binary_little_endian"
+    };
+    let mut data = format!(
+        "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty double weight\n\
+         property float y\nproperty list uchar short tags\nproperty float z\n\
+         element edge 1\nproperty int vertex1\nproperty int vertex2\n\
+         element face 1\nproperty list uchar uint vertex_indices\nend_header\n",
+        format
+    )
+    .into_bytes();
+
+    macro_rules! push {
+        ($value:expr) => {
+            if big_endian {
+                data.extend_from_slice(&$value.to_be_bytes())
+            } else {
+                data.extend_from_slice(&$value.to_le_bytes())
+            }
+        };
+    }
+    for [x, y, z] in &[[0f32, 0., 0.], [1., 0., 0.], [0., 1., 0.]] {
+        push!(x);
+        push!(2f64);
+        push!(y);
+        push!(2u8);
+        push!(7i16);
+        push!(-7i16);
+        push!(z);
+    }
+    push!(0i32);
+    push!(1i32);
+    push!(3u8);
+    for index in &[0u32, 1, 2] {
+        push!(index);
+    }
+    data
+}
+
+#[test]
+fn ply_binary_files_in_both_endians() {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("meshes");
+    fs::create_dir_all(&directory).unwrap();
+
+    for &big_endian in &[false, true] {
+        let path = directory.join(format!("triangle_{}.ply", big_endian));
+        fs::write(&path, binary_ply(big_endian)).unwrap();
+
+        let triangle = load_ply(&path, default_material()).unwrap();
+        let mesh = triangle.mesh();
+        assert_eq!(mesh.faces.len(), 1);
+        assert!((mesh.positions[2] - Point::new(0., 1., 0.)).length() < 1e-6);
+        assert!(hit(
+            &triangle,
+            Point::new(0.2, 0.2, 1.),
+            Point::new(0.2, 0.2, 0.)
+        )
+        .is_some());
+        assert!(hit(
+            &triangle,
+            Point::new(0.8, 0.8, 1.),
+            Point::new(0.8, 0.8, 0.)
+        )
+        .is_none());
+    }
+}
+
+#[test]
+fn ply_errors_are_not_panics() {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("meshes");
+    fs::create_dir_all(&directory).unwrap();
+
+    let vertices = "element vertex 3\nproperty float x\nproperty float y\nproperty float z\n";
+    let cases = [
+        ("magic.ply", "plx\n".to_string(), ":1: not a PLY file"),
+        (
+            "format.ply",
+            "ply\nformat binary 1.0\nend_header\n".to_string(),
+            ":2: unknown format `binary`",
+        ),
+        (
+            "property.ply",
+            "ply\nformat ascii 1.0\nproperty float x\nend_header\n".to_string(),
+            ":3: properties have to be after their element",
+        ),
+        (
+            "type.ply",
+            "ply\nformat ascii 1.0\nelement vertex 1\nproperty float3 x\n".to_string(),
+            ":4: unknown type `float3`",
+        ),
+        (
+            "end.ply",
+            format!("ply\nformat ascii 1.0\n{}", vertices),
+            ":6: the header doesn't have an end_header",
+        ),
+        (
+            "ascii.ply",
+            format!(
+                "ply\nformat ascii 1.0\n{}end_header\n0 0 0\n1 0 zero\n",
+                vertices
+            ),
+            ":9: vertex 1: expected a number, got `zero`",
+        ),
+        (
+            "index.ply",
+            format!(
+                "ply\nformat ascii 1.0\n{}element face 1\nproperty list uchar int vertex_indices\n\
+                 end_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n",
+                vertices
+            ),
+            "a face uses positions [0, 1, 3], but there are 3",
+        ),
+        (
+            "binary.ply",
+            format!(
+                "ply\nformat binary_little_endian 1.0\n{}end_header\n\0\0\0\0\0",
+                vertices
+            ),
+            "vertex 0: the file ends before all the data",
+        ),
+    ];
+    for (name, source, expected) in &cases {
+        let path = directory.join(name);
+        fs::write(&path, source).unwrap();
+
+        let error = load_ply(&path, default_material()).err().unwrap();
+        assert!(
+            error.to_string().contains(expected),
+            "`{}` should contain `{}`",
+            error,
+            expected
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Renders every built-in scene at a tiny size and compares it with the reference image in
+//! `tests/references`. The renders are seeded, so a change that doesn't mean to change the
+//! image, like a refactor of the BVH or the materials, gives the same image.
+//!
+//! When a change is meant to give a different image, check it with `sunny compare` at a
+//! higher sample count, and update the references with
+//! `SUNRAY_UPDATE_REFERENCES=1 cargo test --test reference_images`.
+
+use std::{env, fs, path::PathBuf};
+use sunray::{
+    compare_images, difference_image, difference_scale, generate_world, render_world, Framebuffer,
+    Scene,
+};
+
+const WIDTH: u16 = 48;
+const SAMPLES_PER_PIXEL: u16 = 8;
+const SEED: u64 = 1;
+
+/// Largest relMSE that still counts as the same image. The renders are deterministic, it
+/// only leaves room for floating point differences between platforms.
+const TOLERANCE: f64 = 1e-3;
+
+fn render_scene(scene: Scene) -> Framebuffer {
+    let mut world = generate_world(scene);
+    world.image_width = WIDTH;
+    world.samples_per_pixel = SAMPLES_PER_PIXEL;
+    world.adaptive = None;
+    world.seed = SEED;
+    render_world(&world).image
+}
+
+fn reference_path(scene: Scene) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/references")
+        .join(scene.name())
+        .with_extension("pfm")
+}
+
+#[test]
+fn scenes_match_their_reference_images() {
+    let update = env::var_os("SUNRAY_UPDATE_REFERENCES").is_some();
+    // The renders of the scenes that don't match go here, to look at what changed
+    let failures_directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("reference_images");
+
+    let mut failures = Vec::new();
+    for scene in &Scene::ALL {
+        let image = render_scene(*scene);
+        let path = reference_path(*scene);
+
+        if update {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image.save(&path).unwrap();
+            continue;
+        }
+
+        let reference = match Framebuffer::load(&path) {
+            Ok(reference) => reference,
+            Err(why) => {
+                failures.push(format!("{}: no reference image ({})", scene.name(), why));
+                continue;
+            }
+        };
+        if (image.width, image.height) != (reference.width, reference.height) {
+            failures.push(format!(
+                "{}: rendered {}x{}, but the reference is {}x{}",
+                scene.name(),
+                image.width,
+                image.height,
+                reference.width,
+                reference.height
+            ));
+            continue;
+        }
+
+        let metrics = compare_images(&image, &reference);
+        if metrics.rel_mse > TOLERANCE {
+            fs::create_dir_all(&failures_directory).unwrap();
+            let render_path = failures_directory.join(format!("{}.pfm", scene.name()));
+            let diff_path = failures_directory.join(format!("{}-diff.png", scene.name()));
+            image.save(&render_path).unwrap();
+            difference_image(&image, &reference, difference_scale(&image, &reference))
+                .save(&diff_path)
+                .unwrap();
+
+            failures.push(format!(
+                "{}: relMSE {:.6}, PSNR {:.2} dB, SSIM {:.4}. The render is in {}",
+                scene.name(),
+                metrics.rel_mse,
+                metrics.psnr,
+                metrics.ssim,
+                render_path.display()
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} scenes don't match their reference image:\n{}\n\
+         If the change is meant to change them, update the references with \
+         SUNRAY_UPDATE_REFERENCES=1",
+        failures.len(),
+        failures.join("\n")
+    );
+}
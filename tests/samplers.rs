@@ -0,0 +1,180 @@
+//! Samplers: samples are in [0, 1), spread one per stratum, come back the same for the same
+//! seed, pixel and index, and aren't correlated between dimensions.
+
+use sunray::{Sampler, SamplerKind};
+
+const KINDS: [SamplerKind; 5] = [
+    SamplerKind::Independent,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+    SamplerKind::BlueNoise,
+];
+
+/// Kinds that put one sample in every stratum, with a power of two of samples
+const STRATIFIED: [SamplerKind; 4] = [
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+    SamplerKind::BlueNoise,
+];
+
+/// The first `dimensions` 1D samples of every sample of pixel (x, y)
+fn samples_1d(
+    kind: SamplerKind,
+    x: usize,
+    y: usize,
+    samples: u32,
+    dimensions: u32,
+) -> Vec<Vec<f32>> {
+    let mut sampler = Sampler::for_pixel(kind, 3, x, y, samples);
+    (0..samples)
+        .map(|sample| {
+            sampler.start_sample(sample);
+            (0..dimensions).map(|_| sampler.get_1d()).collect()
+        })
+        .collect()
+}
+
+/// The first 2D sample of every sample of pixel (x, y)
+fn samples_2d(kind: SamplerKind, x: usize, y: usize, samples: u32) -> Vec<(f32, f32)> {
+    let mut sampler = Sampler::for_pixel(kind, 3, x, y, samples);
+    (0..samples)
+        .map(|sample| {
+            sampler.start_sample(sample);
+            sampler.get_2d()
+        })
+        .collect()
+}
+
+#[test]
+fn samples_are_in_the_unit_interval() {
+    let in_range = |value: f32| (0. ..1.).contains(&value);
+    for &kind in &KINDS {
+        for &(x, y) in &[(0, 0), (5, 3), (1000, 2000)] {
+            let mut sampler = Sampler::for_pixel(kind, 3, x, y, 64);
+            for sample in 0..64 {
+                sampler.start_sample(sample);
+                // Past the primes of Halton too
+                for _ in 0..100 {
+                    let value = sampler.get_1d();
+                    assert!(in_range(value), "{:?} gave {}", kind, value);
+                    let (u, v) = sampler.get_2d();
+                    assert!(in_range(u) && in_range(v), "{:?} gave {:?}", kind, (u, v));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn every_stratum_gets_one_sample() {
+    let samples = 16;
+    for &kind in &STRATIFIED {
+        for &(x, y) in &[(0, 0), (7, 2)] {
+            // The first dimension, as Halton only stratifies powers of two in base 2
+            let mut strata: Vec<usize> = samples_1d(kind, x, y, samples, 1)
+                .iter()
+                .map(|values| (values[0] * samples as f32) as usize)
+                .collect();
+            strata.sort_unstable();
+            assert_eq!(
+                strata,
+                (0..samples as usize).collect::<Vec<_>>(),
+                "{:?}",
+                kind
+            );
+        }
+    }
+
+    // A 4x4 grid in 2D
+    for &kind in &[
+        SamplerKind::Stratified,
+        SamplerKind::Sobol,
+        SamplerKind::BlueNoise,
+    ] {
+        let mut cells: Vec<usize> = samples_2d(kind, 7, 2, samples)
+            .iter()
+            .map(|&(u, v)| (u * 4.) as usize + 4 * (v * 4.) as usize)
+            .collect();
+        cells.sort_unstable();
+        assert_eq!(cells, (0..16).collect::<Vec<_>>(), "{:?}", kind);
+    }
+
+    // Stratified doesn't need a power of two
+    let mut strata: Vec<usize> = samples_1d(SamplerKind::Stratified, 1, 1, 10, 3)
+        .iter()
+        .map(|values| (values[2] * 10.) as usize)
+        .collect();
+    strata.sort_unstable();
+    assert_eq!(strata, (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn samples_depend_only_on_seed_pixel_and_index() {
+    for &kind in &KINDS {
+        let first = samples_1d(kind, 4, 9, 16, 8);
+        assert_eq!(first, samples_1d(kind, 4, 9, 16, 8), "{:?}", kind);
+
+        // Going to the samples in another order
+        let mut sampler = Sampler::for_pixel(kind, 3, 4, 9, 16);
+        for sample in (0..16).rev() {
+            sampler.start_sample(sample);
+            let values: Vec<f32> = (0..8).map(|_| sampler.get_1d()).collect();
+            assert_eq!(values, first[sample as usize], "{:?}", kind);
+        }
+
+        let mut other_seed = Sampler::for_pixel(kind, 4, 4, 9, 16);
+        let values: Vec<f32> = (0..8).map(|_| other_seed.get_1d()).collect();
+        assert_ne!(values, first[0], "{:?} ignores the seed", kind);
+        assert_ne!(
+            samples_1d(kind, 5, 9, 16, 8),
+            first,
+            "{:?} ignores the pixel",
+            kind
+        );
+    }
+}
+
+/// Pearson correlation of two lists
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut covariance, mut variance_a, mut variance_b) = (0., 0., 0.);
+    for (a, b) in a.iter().zip(b) {
+        covariance += (a - mean_a) * (b - mean_b);
+        variance_a += (a - mean_a) * (a - mean_a);
+        variance_b += (b - mean_b) * (b - mean_b);
+    }
+    covariance / (variance_a * variance_b).sqrt()
+}
+
+#[test]
+fn dimensions_are_not_correlated() {
+    let dimensions = 8;
+    for &kind in &KINDS {
+        // Every sample of 64 pixels, one list per dimension
+        let mut values = vec![Vec::new(); dimensions];
+        for pixel in 0..64 {
+            for sample in samples_1d(kind, pixel % 8, pixel / 8, 16, dimensions as u32) {
+                for (dimension, value) in sample.into_iter().enumerate() {
+                    values[dimension].push(value);
+                }
+            }
+        }
+
+        for a in 0..dimensions {
+            for b in a + 1..dimensions {
+                let r = correlation(&values[a], &values[b]);
+                assert!(
+                    r.abs() < 0.1,
+                    "{:?}: dimensions {} and {} have a correlation of {}",
+                    kind,
+                    a,
+                    b,
+                    r
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Parsing scene files: the errors point at the right line, and every built-in scene in
+//! `scenes/` is the same as the one `generate_world` builds.
+
+use std::path::PathBuf;
+use sunray::{
+    compare_images, generate_world, load_scene, parse_scene, render_world, Framebuffer, Hittable,
+    Scene, SceneError, World,
+};
+
+/// The error of a scene that shouldn't parse
+fn error(source: &str) -> SceneError {
+    match parse_scene(source) {
+        Ok(_) => panic!("the scene parsed:\n{}", source),
+        Err(error) => error,
+    }
+}
+
+fn check_error(source: &str, line: usize, message: &str) {
+    let error = error(source);
+    assert_eq!(error.line, line, "{}", error);
+    assert!(
+        error.message.contains(message),
+        "expected `{}` in `{}`",
+        message,
+        error
+    );
+}
+
+#[test]
+fn syntax_errors_have_their_line() {
+    check_error(
+        "background 0\nsphere {\n    center 0 0 0\n    radius 1\n",
+        2,
+        "the block of `sphere` is never closed",
+    );
+    check_error(
+        "background 0\n\nsphere { center 0 0 0; radius 1; material normal }\n}\n",
+        4,
+        "`}` without a `{` to close",
+    );
+    check_error(
+        "background 0\nmesh { file \"bunny.obj; material normal }\n",
+        2,
+        "string without a closing `\"`",
+    );
+    check_error(
+        "background 0\n{ sphere }\n",
+        2,
+        "`{` without a statement before it",
+    );
+}
+
+#[test]
+fn object_errors_have_their_line() {
+    check_error(
+        "sphere {\n    center 0 0 0\n    radius 1\n    radius 2\n    material normal\n}\n",
+        4,
+        "`radius` is set twice",
+    );
+    check_error(
+        "background 0\nsphere { center 0 0 0; material normal }\n",
+        2,
+        "`sphere` is missing `radius`",
+    );
+    check_error(
+        "background 0\n\nsphere { center 0 0 0; radius 1; material normal }\nsphear { radius 1 }\n",
+        4,
+        "unknown statement `sphear`",
+    );
+    check_error(
+        "sphere {\n    center 0 0 0\n    radius 1\n    colour 1\n    material normal\n}\n",
+        4,
+        "unknown property `colour` of `sphere`",
+    );
+    check_error(
+        "material red lambertian 1 0 0\nmaterial red lambertian 0.9 0 0\n",
+        2,
+        "there's already a material called `red`",
+    );
+    check_error(
+        "sphere { center 0 0 0; radius one; material normal }",
+        1,
+        "`radius` takes numbers, but `one` isn't one",
+    );
+    check_error(
+        "instance tree { translate 1 0 0 }",
+        1,
+        "unknown prototype `tree`",
+    );
+}
+
+/// Renders with the settings of the reference images, which are small enough for a test
+/// and deterministic
+fn render(mut world: World) -> Framebuffer {
+    world.image_width = 48;
+    world.samples_per_pixel = 8;
+    world.adaptive = None;
+    world.seed = 1;
+    render_world(&world).image
+}
+
+#[test]
+fn scene_files_match_the_built_in_scenes() {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("scenes");
+
+    let mut failures = Vec::new();
+    for scene in &Scene::ALL {
+        let path = directory.join(scene.name()).with_extension("scene");
+        let file = load_scene(&path).unwrap_or_else(|why| panic!("{}", why));
+        let built_in = generate_world(*scene);
+
+        let settings = |world: &World| {
+            format!(
+                "{}x{} {} spp, {} bounces, {:?} sampler, {:?} {:?}, camera {:?}",
+                world.image_width,
+                world.aspect_ratio,
+                world.samples_per_pixel,
+                world.max_depth,
+                world.sampler,
+                world.background_color_top,
+                world.background_color_bottom,
+                world.camera,
+            )
+        };
+        if settings(&file) != settings(&built_in) {
+            failures.push(format!(
+                "{}: the settings are\n    {}\n  instead of\n    {}",
+                scene.name(),
+                settings(&file),
+                settings(&built_in)
+            ));
+            continue;
+        }
+
+        let metrics = compare_images(&render(file), &render(built_in));
+        if metrics.rel_mse > 1e-3 {
+            failures.push(format!(
+                "{}: relMSE {:.6} against the built-in scene",
+                scene.name(),
+                metrics.rel_mse
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn bvhs_use_the_camera_time_wherever_it_is() {
+    // At times 2 to 3 the sphere goes from 0 to 10, far from where it is at 0 to 1
+    let world = parse_scene(
+        "bvh { moving-sphere { center0 0 0 0; center1 10 0 0; time 2 3; radius 1; material normal } }
+        camera { look-from 0 0 20; look-at 0 0 0; time 2 3 }",
+    )
+    .unwrap();
+    let bbox = world.hittables.bounding_box(2., 3.).unwrap();
+    assert_eq!((bbox.min.x, bbox.max.x), (-1., 11.));
+}
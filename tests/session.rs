@@ -0,0 +1,90 @@
+//! Render sessions: cancelling stops the render early with what it has so far, and the
+//! progress only ever goes up.
+
+mod common;
+
+use std::sync::Mutex;
+use sunray::{Accumulation, Framebuffer, Progressive, RenderSession, Tile, TileOrder, World};
+
+const SAMPLES_PER_PIXEL: u16 = 4;
+
+/// 64 tiles, so there are plenty left after cancelling
+fn world() -> World {
+    common::world(64, SAMPLES_PER_PIXEL, 1)
+        .tiles(8, TileOrder::Scanline)
+        .build()
+}
+
+fn check_increasing(progress: &[f32]) {
+    assert!(
+        progress.windows(2).all(|pair| pair[0] <= pair[1]),
+        "the progress goes back: {:?}",
+        progress
+    );
+}
+
+#[test]
+fn cancelling_returns_the_image_so_far() {
+    let world = world();
+    let session = RenderSession::new();
+    let progress = Mutex::new(Vec::new());
+    let output = sunray::render_world_with(&world, &session, |_: &Tile, _: &Framebuffer| {
+        let mut progress = progress.lock().unwrap();
+        progress.push(session.progress());
+        if progress.len() == 4 {
+            session.cancel();
+        }
+    });
+    let progress = progress.into_inner().unwrap();
+
+    assert!(session.is_cancelled());
+    check_increasing(&progress);
+    let last = *progress.last().unwrap();
+    assert!(last > 0. && last < 0.5, "cancelled at {}", last);
+    assert_eq!(session.progress(), last);
+
+    // The tiles that were done have the sky, the others are still black
+    assert!(output.average_samples < SAMPLES_PER_PIXEL as f32 / 2.);
+    let black = output
+        .image
+        .pixels()
+        .iter()
+        .filter(|pixel| pixel.x == 0.)
+        .count();
+    assert!(
+        black > output.image.pixels().len() / 2,
+        "{} black pixels",
+        black
+    );
+    assert!(black < output.image.pixels().len());
+}
+
+#[test]
+fn cancelling_a_progressive_render_keeps_the_passes_so_far() {
+    let world = world();
+    let session = RenderSession::new();
+    let mut accumulation = Accumulation::new(&world);
+    let mut progress = Vec::new();
+    let progressive = Progressive {
+        pass_samples: 1,
+        ..Progressive::default()
+    };
+    let output = progressive
+        .render_with_passes(
+            &world,
+            &mut accumulation,
+            &session,
+            |_: &Tile, _: &Framebuffer| {},
+            |_: &Accumulation| {
+                progress.push(session.progress());
+                if progress.len() == 2 {
+                    session.cancel();
+                }
+            },
+        )
+        .unwrap();
+
+    check_increasing(&progress);
+    assert_eq!(progress, vec![0.25, 0.5]);
+    assert_eq!(output.average_samples, 2.);
+}
@@ -0,0 +1,88 @@
+//! Renders with the same seed are identical however many threads they use, with sampling
+//! that's progressive or adaptive too.
+
+mod common;
+
+use sunray::{
+    Accumulation, AdaptiveSampling, Color, Framebuffer, Material, Point, Progressive, SamplerKind,
+    Sphere, World,
+};
+
+/// The shared sphere next to a glass and a metal one, so rays bounce around
+fn world(adaptive: Option<AdaptiveSampling>, sampler: SamplerKind) -> World {
+    let mut builder = common::world(24, 16, 11)
+        .add_object(Sphere::new(
+            Point::new(-2.2, 0., 0.),
+            1.,
+            Material::Dielectric(1.5),
+        ))
+        .add_object(Sphere::new(
+            Point::new(2.2, 0., 0.),
+            1.,
+            Material::Metal(Color::from(0.8), 0.3),
+        ))
+        .sampler(sampler);
+    if let Some(adaptive) = adaptive {
+        builder = builder.adaptive(adaptive);
+    }
+    builder.build()
+}
+
+/// Runs `render` with `threads` threads
+fn with_threads<T: Send>(threads: usize, render: impl FnOnce() -> T + Send) -> T {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap()
+        .install(render)
+}
+
+fn bits(image: &Framebuffer) -> Vec<[u32; 3]> {
+    image
+        .pixels()
+        .iter()
+        .map(|pixel| [pixel.x.to_bits(), pixel.y.to_bits(), pixel.z.to_bits()])
+        .collect()
+}
+
+fn progressive(world: &World) -> Framebuffer {
+    let mut accumulation = Accumulation::new(world);
+    let progressive = Progressive {
+        pass_samples: 3,
+        ..Progressive::default()
+    };
+    progressive.render(world, &mut accumulation).unwrap().image
+}
+
+fn check_same_with_1_and_4_threads(world: &World) {
+    let once = |threads| {
+        with_threads(threads, || {
+            let image = sunray::render_world(world).image;
+            (bits(&image), bits(&progressive(world)))
+        })
+    };
+    let (image, progressive) = once(1);
+    let (image_4, progressive_4) = once(4);
+    assert!(image == image_4, "4 threads change the image");
+    assert!(
+        progressive == progressive_4,
+        "4 threads change the progressive image"
+    );
+}
+
+#[test]
+fn threads_dont_change_the_image() {
+    for &sampler in &[SamplerKind::Independent, SamplerKind::Sobol] {
+        check_same_with_1_and_4_threads(&world(None, sampler));
+    }
+}
+
+#[test]
+fn threads_dont_change_adaptive_images() {
+    let adaptive = AdaptiveSampling {
+        min_samples: 4,
+        max_samples: 64,
+        noise_threshold: 0.05,
+    };
+    check_same_with_1_and_4_threads(&world(Some(adaptive), SamplerKind::Independent));
+}
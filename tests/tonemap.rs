@@ -0,0 +1,74 @@
+//! Display transforms: sRGB encoding keeps black and white where they are, and every tone
+//! mapping operator keeps brighter values brighter.
+
+use sunray::{Color, DisplayTransform, ToneMap};
+
+const OPERATORS: [ToneMap; 5] = [
+    ToneMap::Clamp,
+    ToneMap::Reinhard,
+    ToneMap::ExtendedReinhard { white: 4. },
+    ToneMap::Aces,
+    ToneMap::Hable,
+];
+
+#[test]
+fn clamp_maps_black_and_white_to_the_ends() {
+    let display = DisplayTransform::default();
+    assert_eq!(display.tone_map, ToneMap::Clamp);
+    assert_eq!(display.to_rgb8(Color::zeros()), [0, 0, 0]);
+    assert_eq!(display.to_rgb8(Color::ones()), [255, 255, 255]);
+    assert_eq!(display.to_rgb8(Color::from(10.)), [255, 255, 255]);
+    assert_eq!(display.to_rgb8(Color::from(-1.)), [0, 0, 0]);
+    // Middle gray is about 118 once encoded
+    assert_eq!(display.to_rgb8(Color::from(0.18)), [118, 118, 118]);
+}
+
+#[test]
+fn exposure_is_in_stops() {
+    let display = DisplayTransform {
+        exposure: 1.,
+        ..DisplayTransform::default()
+    };
+    assert_eq!(
+        display.to_rgb8(Color::from(0.25)),
+        DisplayTransform::default().to_rgb8(Color::from(0.5))
+    );
+}
+
+#[test]
+fn operators_increase_steadily() {
+    for operator in &OPERATORS {
+        let black = operator.map(Color::zeros()).x;
+        assert!(black.abs() < 1e-6, "{:?} maps 0 to {}", operator, black);
+
+        let mut last = 0.;
+        for i in 1..=1000 {
+            let value = i as f32 * 0.02;
+            let mapped = operator.map(Color::from(value)).x;
+            assert!(
+                (0. ..=1.).contains(&mapped),
+                "{:?} maps {} out of [0, 1], to {}",
+                operator,
+                value,
+                mapped
+            );
+            assert!(
+                mapped >= last,
+                "{:?} maps {} to {}, under the {} of a smaller value",
+                operator,
+                value,
+                mapped,
+                last
+            );
+            last = mapped;
+        }
+    }
+}
+
+#[test]
+fn extended_reinhard_maps_white_to_1() {
+    let operator = ToneMap::ExtendedReinhard { white: 4. };
+    assert!((operator.map(Color::from(4.)).x - 1.).abs() < 1e-6);
+    assert!(operator.map(Color::from(3.9)).x < 1.);
+    assert_eq!("extended-reinhard:4".parse::<ToneMap>(), Ok(operator));
+}